@@ -0,0 +1,209 @@
+use vec3::{Vector3, Quaternion};
+use color::Color;
+use std::ops::{Add, Mul};
+
+/// How a track moves from one key to the next.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Curve {
+    /// At a constant rate.
+    Linear,
+    /// Along a Catmull-Rom spline through the keys either side, so the
+    /// value passes smoothly through each key rather than turning sharply.
+    Cubic,
+    /// Starting and stopping gently, at rest at both keys.
+    EaseInOut,
+}
+
+/// A value that can be animated by a `Track`.
+pub trait Animatable: Copy {
+    /// `a` at `t = 0` to `b` at `t = 1`.
+    fn lerp(a: Self, b: Self, t: f64) -> Self;
+
+    /// The part from `b` at `t = 0` to `c` at `t = 1` of a Catmull-Rom
+    /// spline through all four values.
+    fn catmull_rom(a: Self, b: Self, c: Self, d: Self, t: f64) -> Self;
+}
+
+fn catmull_rom_weights(t: f64) -> [f64; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+    return [0.5 * (-t + 2.0 * t2 - t3),
+            0.5 * (2.0 - 5.0 * t2 + 3.0 * t3),
+            0.5 * (t + 4.0 * t2 - 3.0 * t3),
+            0.5 * (t3 - t2)];
+}
+
+fn weighted_sum<T>(values: [T; 4], weights: [f64; 4]) -> T
+    where T: Copy + Add<Output = T> + Mul<f64, Output = T>
+{
+    return values[0] * weights[0] + values[1] * weights[1] + values[2] * weights[2] + values[3] * weights[3];
+}
+
+impl Animatable for f64 {
+    fn lerp(a: f64, b: f64, t: f64) -> f64 {
+        return a + (b - a) * t;
+    }
+
+    fn catmull_rom(a: f64, b: f64, c: f64, d: f64, t: f64) -> f64 {
+        return weighted_sum([a, b, c, d], catmull_rom_weights(t));
+    }
+}
+
+impl Animatable for Vector3 {
+    fn lerp(a: Vector3, b: Vector3, t: f64) -> Vector3 {
+        return a + (b - a) * t;
+    }
+
+    fn catmull_rom(a: Vector3, b: Vector3, c: Vector3, d: Vector3, t: f64) -> Vector3 {
+        return weighted_sum([a, b, c, d], catmull_rom_weights(t));
+    }
+}
+
+impl Animatable for Color {
+    fn lerp(a: Color, b: Color, t: f64) -> Color {
+        return a * (1.0 - t) + b * t;
+    }
+
+    // Channel by channel, as the end weights are negative and `Color`
+    // only scales by positive factors.
+    fn catmull_rom(a: Color, b: Color, c: Color, d: Color, t: f64) -> Color {
+        let channel = |f: fn(&Color) -> f64| f64::catmull_rom(f(&a), f(&b), f(&c), f(&d), t);
+        return Color::new(channel(|c| c.red), channel(|c| c.green), channel(|c| c.blue));
+    }
+}
+
+/// Rotations turn along the shortest arc.
+impl Animatable for Quaternion {
+    fn lerp(a: Quaternion, b: Quaternion, t: f64) -> Quaternion {
+        return a.slerp(b, t);
+    }
+
+    // The spline through the components, which is close to the spline
+    // through the rotations for keys less than a half turn apart.
+    fn catmull_rom(a: Quaternion, b: Quaternion, c: Quaternion, d: Quaternion, t: f64) -> Quaternion {
+        // q and -q are the same rotation; take each closest to the last.
+        let near = |q: Quaternion, to: Quaternion| if q.dot(to) < 0.0 { q * -1.0 } else { q };
+        let a = near(a, b);
+        let c = near(c, b);
+        let d = near(d, c);
+        return weighted_sum([a, b, c, d], catmull_rom_weights(t)).normalize();
+    }
+}
+
+/// A value at a moment in an animation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key<T> {
+    pub time: f64,
+    pub value: T,
+    /// How the value moves on to the next key.
+    pub curve: Curve,
+}
+
+impl<T> Key<T> {
+    pub fn new(time: f64, value: T) -> Key<T> {
+        Key {
+            time,
+            value,
+            curve: Curve::Linear,
+        }
+    }
+
+    pub fn with_curve(self, curve: Curve) -> Key<T> {
+        Key { curve, ..self }
+    }
+}
+
+/// A value that changes over time, set at keys and interpolated between
+/// them. Before the first key and after the last it holds still.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    /// In order of time; there is always at least one.
+    keys: Vec<Key<T>>,
+}
+
+impl<T: Animatable> Track<T> {
+    /// Errors if there are no keys, or a key's time is not finite.
+    pub fn new(mut keys: Vec<Key<T>>) -> Result<Track<T>, &'static str> {
+        if keys.is_empty() {
+            return Err("tracks need at least one key");
+        }
+        if keys.iter().any(|k| !k.time.is_finite()) {
+            return Err("key times must be finite");
+        }
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        return Ok(Track { keys });
+    }
+
+    /// The same value at all times.
+    pub fn constant(value: T) -> Track<T> {
+        return Track { keys: vec![Key::new(0.0, value)] };
+    }
+
+    pub fn keys(&self) -> &[Key<T>] {
+        return &self.keys;
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        let keys = &self.keys;
+        let next = keys.iter().position(|k| k.time > time).unwrap_or(keys.len());
+        if next == 0 {
+            return keys[0].value;
+        }
+        if next == keys.len() {
+            return keys[next - 1].value;
+        }
+        let (a, b) = (&keys[next - 1], &keys[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        match a.curve {
+            Curve::Linear => return T::lerp(a.value, b.value, t),
+            Curve::EaseInOut => return T::lerp(a.value, b.value, t * t * (3.0 - 2.0 * t)),
+            Curve::Cubic => {
+                // The ends of the track repeat their key.
+                let before = if next >= 2 { keys[next - 2].value } else { a.value };
+                let after = keys.get(next + 1).map_or(b.value, |k| k.value);
+                return T::catmull_rom(before, a.value, b.value, after, t);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_tracks() {
+    let track = Track::new(vec![Key::new(2.0, 4.0).with_curve(Curve::Cubic),
+                                Key::new(0.0, 0.0).with_curve(Curve::EaseInOut),
+                                Key::new(1.0, 2.0),
+                                Key::new(3.0, 0.0)]).unwrap();
+    assert_eq!(track.keys()[0].time, 0.0);
+    // Every curve passes through the keys, and holds still beyond them.
+    for &time in [0.0, 1.0, 2.0, 3.0].iter() {
+        assert_eq!(track.at(time), track.keys()[time as usize].value);
+    }
+    assert_eq!(track.at(-1.0), 0.0);
+    assert_eq!(track.at(5.0), 0.0);
+    // Linear and eased segments meet in the middle, but easing starts slowly.
+    assert_eq!(track.at(1.5), 3.0);
+    assert_eq!(track.at(0.5), 1.0);
+    assert!(track.at(0.1) < 0.2);
+    // The cubic segment follows the spline through its neighbours, so it
+    // leaves the key heading away from the one before.
+    assert_eq!(track.at(2.5), f64::catmull_rom(2.0, 4.0, 0.0, 0.0, 0.5));
+    assert!((track.at(2.0 + 1.0e-6) - 4.0) / 1.0e-6 < -0.99);
+    // A cubic spline through points on a line stays on it.
+    assert!((f64::catmull_rom(1.0, 2.0, 3.0, 4.0, 0.3) - 2.3).abs() < 1.0e-12);
+
+    let quarter = Quaternion::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), ::std::f64::consts::FRAC_PI_2);
+    let turn = Track::new(vec![Key::new(0.0, Quaternion::identity()).with_curve(Curve::Cubic),
+                               Key::new(1.0, quarter)]).unwrap();
+    let (_, angle) = turn.at(0.5).to_axis_angle();
+    assert!((angle - ::std::f64::consts::FRAC_PI_4).abs() < 1.0e-9);
+    let colors = Track::new(vec![Key::new(0.0, Color::new(1.0, 0.0, 0.0)),
+                                 Key::new(1.0, Color::new(0.0, 0.0, 1.0))]).unwrap();
+    assert_eq!(colors.at(0.25), Color::new(0.75, 0.0, 0.25));
+    let smooth_colors = Track::new(vec![Key::new(0.0, Color::new(1.0, 0.0, 0.0)).with_curve(Curve::Cubic),
+                                        Key::new(1.0, Color::new(0.0, 0.0, 1.0))]).unwrap();
+    assert_eq!(smooth_colors.at(0.5), Color::new(0.5, 0.0, 0.5));
+    assert_eq!(Track::constant(Vector3::new(1.0, 2.0, 3.0)).at(7.0), Vector3::new(1.0, 2.0, 3.0));
+
+    // Tracks need keys, at times that can be put in order.
+    assert!(Track::<f64>::new(vec![]).is_err());
+    assert!(Track::new(vec![Key::new(0.0, 1.0), Key::new(f64::NAN, 2.0)]).is_err());
+}
@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
     pub red: f64,
     pub green: f64,
@@ -35,8 +35,46 @@ impl Color {
         return result;
     }
 
-    pub fn to_rgb(&self) -> (u8, u8, u8) {
-        ((self.red * 255.0) as u8, (self.blue * 255.0) as u8, (self.green * 255.0) as u8)
+    pub fn new(red: f64, green: f64, blue: f64) -> Color {
+        Color { red, green, blue }
+    }
+
+    pub fn max_channel(self) -> f64 {
+        return self.red.max(self.green).max(self.blue);
+    }
+
+    /// Relative luminance, the Y of CIE XYZ, of a linear sRGB color.
+    pub fn luminance(self) -> f64 {
+        return 0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue;
+    }
+
+    /// Linear sRGB from CIE XYZ. Colors outside the sRGB gamut get
+    /// negative channels.
+    pub fn from_xyz(x: f64, y: f64, z: f64) -> Color {
+        Color {
+            red: 3.2406 * x - 1.5372 * y - 0.4986 * z,
+            green: -0.9689 * x + 1.8758 * y + 0.0415 * z,
+            blue: 0.0557 * x - 0.2040 * y + 1.0570 * z,
+        }
+    }
+
+    /// Linear from sRGB encoded channels in `[0, 1]`, as most 8 bit
+    /// images are stored.
+    pub fn srgb_to_linear(self) -> Color {
+        let decode = |x: f64| if x <= 0.04045 { x / 12.92 } else { ((x + 0.055) / 1.055).powf(2.4) };
+        Color::new(decode(self.red), decode(self.green), decode(self.blue))
+    }
+
+    /// sRGB encoded from linear, the inverse of `srgb_to_linear`.
+    pub fn linear_to_srgb(self) -> Color {
+        let encode = |x: f64| if x <= 0.0031308 { x * 12.92 } else { 1.055 * x.powf(1.0 / 2.4) - 0.055 };
+        Color::new(encode(self.red), encode(self.green), encode(self.blue))
+    }
+
+    /// Channels outside `[0, 1]` are clamped.
+    pub fn to_rgb(self) -> (u8, u8, u8) {
+        let byte = |x: f64| (x.clamp(0.0, 1.0) * 255.0) as u8;
+        (byte(self.red), byte(self.green), byte(self.blue))
     }
 }
 
@@ -52,28 +90,27 @@ impl ::std::ops::Mul<Color> for Color {
     }
 }
 
+// Colors are unbounded radiance values; they are only clamped on output by `to_rgb`.
 impl ::std::ops::Add<Color> for Color {
     type Output = Color;
 
     fn add(self, other: Color) -> Color {
+        return Color {
+            red: self.red + other.red,
+            green: self.green + other.green,
+            blue: self.blue + other.blue,
+        };
+    }
+}
 
-        let mut red: f64 = self.red + other.red;
-        let mut green: f64 = self.green + other.green;
-        let mut blue: f64 = self.blue + other.blue;
-        if red > 1.0 {
-            red = 1.0;
-        }
-        if green > 1.0 {
-            green = 1.0;
-        }
-        if blue > 1.0 {
-            blue = 1.0;
-        }
+impl ::std::ops::Sub<Color> for Color {
+    type Output = Color;
 
+    fn sub(self, other: Color) -> Color {
         return Color {
-            red: red,
-            green: green,
-            blue: blue,
+            red: self.red - other.red,
+            green: self.green - other.green,
+            blue: self.blue - other.blue,
         };
     }
 }
@@ -82,24 +119,10 @@ impl ::std::ops::Mul<f64> for Color {
     type Output = Color;
 
     fn mul(self, other: f64) -> Color {
-        assert!(other >= 0.0);
-        let mut red: f64 = self.red * other;
-        let mut green: f64 = self.green * other;
-        let mut blue: f64 = self.blue * other;
-        if red > 1.0 {
-            red = 1.0;
-        }
-        if green > 1.0 {
-            green = 1.0;
-        }
-        if blue > 1.0 {
-            blue = 1.0;
-        }
-
         return Color {
-            red: red,
-            green: green,
-            blue: blue,
+            red: self.red * other,
+            green: self.green * other,
+            blue: self.blue * other,
         };
     }
 }
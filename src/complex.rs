@@ -0,0 +1,211 @@
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+pub const ZERO: Complex = Complex { re: 0.0, im: 0.0 };
+pub const ONE: Complex = Complex { re: 1.0, im: 0.0 };
+pub const I: Complex = Complex { re: 0.0, im: 1.0 };
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    pub fn real(re: f64) -> Complex {
+        Complex { re, im: 0.0 }
+    }
+
+    pub fn from_polar(r: f64, theta: f64) -> Complex {
+        Complex::new(r * theta.cos(), r * theta.sin())
+    }
+
+    /// Returns `(r, theta)` with `theta` in `(-pi, pi]`.
+    pub fn to_polar(self) -> (f64, f64) {
+        (self.abs(), self.arg())
+    }
+
+    pub fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    pub fn norm_sqr(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    /// Principal square root, with non-negative real part.
+    pub fn sqrt(self) -> Complex {
+        if self.im == 0.0 {
+            if self.re >= 0.0 {
+                return Complex::real(self.re.sqrt());
+            } else {
+                return Complex::new(0.0, (-self.re).sqrt());
+            }
+        }
+        let r = self.abs();
+        // Written to avoid cancellation when re is large and negative.
+        let re = ((r + self.re) / 2.0).sqrt();
+        let im = ((r - self.re) / 2.0).sqrt();
+        return Complex::new(re, im.copysign(self.im));
+    }
+
+    /// Principal cube root, with argument in `(-pi / 3, pi / 3]`.
+    /// Unlike `f64::cbrt` this gives a complex root for negative reals.
+    pub fn cbrt(self) -> Complex {
+        let (r, theta) = self.to_polar();
+        return Complex::from_polar(r.cbrt(), theta / 3.0);
+    }
+
+    pub fn exp(self) -> Complex {
+        Complex::from_polar(self.re.exp(), self.im)
+    }
+
+    /// Principal natural logarithm, with imaginary part in `(-pi, pi]`.
+    pub fn ln(self) -> Complex {
+        Complex::new(self.abs().ln(), self.arg())
+    }
+
+    pub fn powi(self, n: i32) -> Complex {
+        if n < 0 {
+            return ONE / self.powi(-n);
+        }
+        let mut result = ONE;
+        for _ in 0..n {
+            result = result * self;
+        }
+        return result;
+    }
+
+    pub fn powf(self, x: f64) -> Complex {
+        if self == ZERO {
+            return ZERO;
+        }
+        return (self.ln() * x).exp();
+    }
+
+    /// The `n` `n`th roots of unity times the principal `n`th root.
+    pub fn roots(self, n: u32) -> Vec<Complex> {
+        let (r, theta) = self.to_polar();
+        let magnitude = r.powf(1.0 / n as f64);
+        return (0..n)
+            .map(|k| Complex::from_polar(magnitude, (theta + 2.0 * PI * k as f64) / n as f64))
+            .collect();
+    }
+
+    pub fn approx_eq(self, other: Complex, epsilon: f64) -> bool {
+        (self.re - other.re).abs() < epsilon && (self.im - other.im).abs() < epsilon
+    }
+
+    pub fn is_real(self, epsilon: f64) -> bool {
+        self.im.abs() < epsilon
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Complex {
+        Complex::real(re)
+    }
+}
+
+impl ::std::ops::Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl ::std::ops::Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl ::std::ops::Neg for Complex {
+    type Output = Complex;
+
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+impl ::std::ops::Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(self.re * other.re - self.im * other.im,
+                     self.re * other.im + self.im * other.re)
+    }
+}
+
+impl ::std::ops::Div for Complex {
+    type Output = Complex;
+
+    fn div(self, other: Complex) -> Complex {
+        let d = other.norm_sqr();
+        Complex::new((self.re * other.re + self.im * other.im) / d,
+                     (self.im * other.re - self.re * other.im) / d)
+    }
+}
+
+impl ::std::ops::Add<f64> for Complex {
+    type Output = Complex;
+
+    fn add(self, other: f64) -> Complex {
+        Complex::new(self.re + other, self.im)
+    }
+}
+
+impl ::std::ops::Mul<f64> for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: f64) -> Complex {
+        Complex::new(self.re * other, self.im * other)
+    }
+}
+
+impl ::std::ops::Div<f64> for Complex {
+    type Output = Complex;
+
+    fn div(self, other: f64) -> Complex {
+        Complex::new(self.re / other, self.im / other)
+    }
+}
+
+#[test]
+fn test_complex_roots() {
+    let z = Complex::new(-3.0, 4.0);
+    assert_eq!(z.sqrt(), Complex::new(1.0, 2.0));
+    assert_eq!(z.conj().sqrt(), Complex::new(1.0, -2.0));
+    assert_eq!(Complex::real(-4.0).sqrt(), Complex::new(0.0, 2.0));
+    assert!((z.cbrt().powi(3)).approx_eq(z, 1.0e-12));
+    assert!(Complex::real(-8.0).cbrt().approx_eq(Complex::new(1.0, 3.0f64.sqrt()), 1.0e-12));
+    for root in Complex::new(0.5, -2.0).roots(5) {
+        assert!(root.powi(5).approx_eq(Complex::new(0.5, -2.0), 1.0e-12));
+    }
+}
+
+#[test]
+fn test_complex_exp_ln() {
+    // e^(i pi) = -1
+    assert!((I * PI).exp().approx_eq(Complex::real(-1.0), 1.0e-15));
+    let z = Complex::new(0.3, -1.7);
+    assert!(z.ln().exp().approx_eq(z, 1.0e-15));
+    assert!(z.powf(2.0).approx_eq(z * z, 1.0e-14));
+    let (r, theta) = z.to_polar();
+    assert!(Complex::from_polar(r, theta).approx_eq(z, 1.0e-15));
+    assert!((z / z).approx_eq(ONE, 1.0e-15));
+}
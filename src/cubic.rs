@@ -1,3 +1,7 @@
+use polynomial::Polynomial;
+use complex;
+use complex::Complex;
+
 
 /// Solve equations of the form `ax^2 + bx + c = 0`.
 #[derive(PartialEq, Debug)]
@@ -10,7 +14,7 @@ pub enum QuadRoots {
 #[derive(PartialEq, Debug)]
 pub enum CubicRoots {
     ThreeReal(f64, f64, f64),
-    OneRealTwoComplex(f64, (f64, f64), (f64, f64)),
+    OneRealTwoComplex(f64, Complex, Complex),
 }
 
 pub fn solve_quadratic(a: f64, b: f64, c: f64) -> QuadRoots {
@@ -55,7 +59,9 @@ pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> CubicRoots {
         let u = t.cbrt();
         let real = -(s + u) / 2.0 - b / (3.0 * a);
         let img = (s - u) * three.sqrt() / 2.0;
-        return CubicRoots::OneRealTwoComplex(s + u - b / (3.0 * a), (real, img), (real, -img));
+        return CubicRoots::OneRealTwoComplex(s + u - b / (3.0 * a),
+                                             Complex::new(real, img),
+                                             Complex::new(real, -img));
     } else {
         if h > 0.0 {
             h = 0.0;
@@ -63,7 +69,7 @@ pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> CubicRoots {
 
         let i = (g * g / 4.0 - h).sqrt();
         let j = i.cbrt();
-        let k = (-g / (2.0 * i)).acos() / 3.0;
+        let k = (-g / (2.0 * i)).clamp(-1.0, 1.0).acos() / 3.0;
         if k.is_nan() {
             panic!("Got NaN from arcos.");
         }
@@ -76,14 +82,6 @@ pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> CubicRoots {
     }
 }
 
-/// Returns one of the two square roots of a complex number.
-fn sqrt_cmplx((a, b): Cmplx) -> Cmplx {
-    let r = (a * a + b * b).sqrt();
-    let y = ((r - a) / 2.0).sqrt();
-    let x = b / (2.0 * y);
-    return (x, y);
-}
-
 fn round_near_zero(x: f64) -> f64 {
     if near_zero(x) {
         return 0.0;
@@ -92,39 +90,7 @@ fn round_near_zero(x: f64) -> f64 {
     }
 }
 
-fn sqrt_real(x: f64) -> Cmplx {
-    if x >= 0.0 {
-        return (x.sqrt(), 0.0);
-    } else {
-        return (0.0, (-x).sqrt());
-    }
-}
-
-pub type Cmplx = (f64, f64);
-
-fn cmplx_add((x, xi): Cmplx, (y, yi): Cmplx) -> Cmplx {
-    return (x + y, xi + yi);
-}
-
-fn cmplx_mult((x, xi): Cmplx, (y, yi): Cmplx) -> Cmplx {
-    return (x * y - xi * yi, x * yi + y * xi);
-}
-
-fn cmplx_neg((x, xi): Cmplx) -> Cmplx {
-    return (-x, -xi);
-}
-
-fn real_or_img_div(y: f64, (x, xi): Cmplx) -> Cmplx {
-    if x == 0.0 {
-        return (0.0, -y / xi);
-    } else if xi == 0.0 {
-        return (y / x, 0.0);
-    } else {
-        panic!("real_or_img_div got complex number.");
-    }
-}
-
-pub fn solve_quartic(aa: f64, bb: f64, cc: f64, dd: f64, ee: f64) -> (Cmplx, Cmplx, Cmplx, Cmplx) {
+pub fn solve_quartic(aa: f64, bb: f64, cc: f64, dd: f64, ee: f64) -> (Complex, Complex, Complex, Complex) {
 
     assert!(!near_zero(aa));
     let a = 1.0;
@@ -137,8 +103,8 @@ pub fn solve_quartic(aa: f64, bb: f64, cc: f64, dd: f64, ee: f64) -> (Cmplx, Cmp
     let h = e - (3.0 * b * b * b * b / 256.0) + (b * b * c / 16.0) - (b * d / 4.0);
     let cb = solve_cubic(1.0, f / 2.0, (f * f - 4.0 * h) / 16.0, -g * g / 64.0);
 
-    let p: Cmplx;
-    let q: Cmplx;
+    let p: Complex;
+    let q: Complex;
 
     match cb {
         CubicRoots::ThreeReal(mut y1, mut y2, mut y3) => {
@@ -157,89 +123,117 @@ pub fn solve_quartic(aa: f64, bb: f64, cc: f64, dd: f64, ee: f64) -> (Cmplx, Cmp
                 psq = y1;
                 qsq = y2;
             }
-            p = sqrt_real(psq);
-            q = sqrt_real(qsq);
+            p = Complex::real(psq).sqrt();
+            q = Complex::real(qsq).sqrt();
         }
-        CubicRoots::OneRealTwoComplex(_, (y1, y1i), (y2, y2i)) => {
-            assert!(y1 == y2);
-            assert!(y1i == -y2i);
-            let (pr, pi) = sqrt_cmplx((y1, y1i));
-            p = (pr, pi);
-            q = (pr, -pi);
+        CubicRoots::OneRealTwoComplex(_, y1, y2) => {
+            assert!(y1 == y2.conj());
+            // Either square root will do; take the one in the upper half
+            // plane so the roots always come out in the same order.
+            let root = y1.sqrt();
+            p = if root.im < 0.0 { -root } else { root };
+            q = p.conj();
         }
     }
-    let (pqr, pqi) = cmplx_mult(p, q);
-    let r: Cmplx;
-    if (pqr, pqi) == (0.0, 0.0) {
-        r = (0.0, 0.0);
+    let pq = p * q;
+    let r = if pq == complex::ZERO {
+        complex::ZERO
     } else {
-        r = real_or_img_div(-g, (8.0 * pqr, 8.0 * pqi));
-    }
-    let s = b / (4.0 * a);
-    return ((cmplx_add(cmplx_add(cmplx_add(p, q), r), (-s, 0.0))),
-            (cmplx_add(cmplx_add(cmplx_add(p, cmplx_neg(q)), cmplx_neg(r)),
-                       (-s, 0.0))),
-            (cmplx_add(cmplx_add(cmplx_add(cmplx_neg(p), q), cmplx_neg(r)),
-                       (-s, 0.0))),
-            (cmplx_add(cmplx_add(cmplx_add(cmplx_neg(p), cmplx_neg(q)), r),
-                       (-s, 0.0))));
+        Complex::real(-g) / (pq * 8.0)
+    };
+    let s = Complex::real(b / (4.0 * a));
+    return (p + q + r - s, p - q - r - s, -p + q - r - s, -p - q + r - s);
 }
 
-fn flt_cmp(x: f64, y: f64) -> bool {
-    return near_zero(x - y);
+/// How `solve_quartic_smallest_positive_real` finds roots.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum QuarticMethod {
+    /// Closed form solution by Ferrari's method. Fast, but loses roots that
+    /// are close together or of very different magnitudes.
+    Ferrari,
+    /// Sturm sequence root isolation followed by safeguarded Newton iteration.
+    Sturm,
+    /// Ferrari's method, falling back to Sturm sequences when its roots look unreliable.
+    FerrariWithFallback,
 }
 
+pub const DEFAULT_QUARTIC_METHOD: QuarticMethod = QuarticMethod::FerrariWithFallback;
+
 pub fn solve_quartic_smallest_positive_real(a: f64, b: f64, c: f64, d: f64, e: f64, epsilon: f64) -> Option<f64> {
-    let mut smallest_real = 1.0 / 0.0;
-    let ((r1, i1), (r2, i2), (r3, i3), (r4, i4)) = solve_quartic(a, b, c, d, e);
-    if r1 < smallest_real && r1 > epsilon && i1.abs() < EPSILON {
-        smallest_real = r1;
-    }
-    if r2 < smallest_real && r2 > epsilon && i2.abs() < EPSILON {
-        smallest_real = r2;
-    }
-    if r3 < smallest_real && r3 > epsilon && i3.abs() < EPSILON {
-        smallest_real = r3;
+    return solve_quartic_smallest_positive_real_with(DEFAULT_QUARTIC_METHOD, a, b, c, d, e, epsilon);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn solve_quartic_smallest_positive_real_with(method: QuarticMethod,
+                                                 a: f64,
+                                                 b: f64,
+                                                 c: f64,
+                                                 d: f64,
+                                                 e: f64,
+                                                 epsilon: f64)
+                                                 -> Option<f64> {
+    match method {
+        QuarticMethod::Ferrari => return ferrari_smallest_positive_real(a, b, c, d, e, epsilon).0,
+        QuarticMethod::Sturm => return sturm_smallest_positive_real(&[a, b, c, d, e], epsilon),
+        QuarticMethod::FerrariWithFallback => {
+            let (root, reliable) = ferrari_smallest_positive_real(a, b, c, d, e, epsilon);
+            if reliable {
+                return root;
+            } else {
+                return sturm_smallest_positive_real(&[a, b, c, d, e], epsilon);
+            }
+        }
     }
-    if r4 < smallest_real && r4 > epsilon && i4.abs() < EPSILON {
-        smallest_real = r4;
+}
+
+// Imaginary parts smaller than this, relative to the root, are probably
+// real roots that Ferrari's method has perturbed off the real line.
+const SUSPECT_IMAGINARY: f64 = 1.0e-4;
+const MAX_RELATIVE_RESIDUAL: f64 = 1.0e-9;
+
+/// Also returns whether the roots passed some sanity checks.
+fn ferrari_smallest_positive_real(a: f64, b: f64, c: f64, d: f64, e: f64, epsilon: f64) -> (Option<f64>, bool) {
+    let mut smallest_real = 1.0 / 0.0;
+    let mut reliable = true;
+    let (z1, z2, z3, z4) = solve_quartic(a, b, c, d, e);
+    for &Complex { re: r, im: i } in [z1, z2, z3, z4].iter() {
+        if !r.is_finite() || !i.is_finite() {
+            reliable = false;
+        } else if i.abs() < EPSILON {
+            if r < smallest_real && r > epsilon {
+                smallest_real = r;
+            }
+        } else if i.abs() < SUSPECT_IMAGINARY * (1.0 + r.abs()) {
+            reliable = false;
+        }
     }
     if smallest_real.is_infinite() {
-        return None;
+        return (None, reliable);
     } else {
-        return Some(smallest_real);
+        let p = Polynomial::from_highest_first(&[a, b, c, d, e]);
+        let residual = p.evaluate(smallest_real).abs() / p.evaluate_abs(smallest_real);
+        return (Some(smallest_real), reliable && residual < MAX_RELATIVE_RESIDUAL);
     }
 }
 
-fn cmplx_cmp((x, y): (f64, f64), (x1, y1): (f64, f64)) -> bool {
-    return flt_cmp(x, x1) && flt_cmp(y, y1);
+fn sturm_smallest_positive_real(p: &[f64], epsilon: f64) -> Option<f64> {
+    return Polynomial::from_highest_first(p).smallest_root(epsilon, 1.0 / 0.0);
 }
 
-fn cmplx_cmp_4((x, y, z, u): (Cmplx, Cmplx, Cmplx, Cmplx),
-               (x1, y1, z1, u1): (Cmplx, Cmplx, Cmplx, Cmplx))
+fn cmplx_cmp_4((x, y, z, u): (Complex, Complex, Complex, Complex),
+               (x1, y1, z1, u1): (Complex, Complex, Complex, Complex))
                -> bool {
-    return cmplx_cmp(x, x1) && cmplx_cmp(y, y1) && cmplx_cmp(z, z1) && cmplx_cmp(u, u1);
+    return x.approx_eq(x1, EPSILON) && y.approx_eq(y1, EPSILON) && z.approx_eq(z1, EPSILON) &&
+           u.approx_eq(u1, EPSILON);
 }
 
-fn cmplx_pow(c: Cmplx, n: i32) -> Cmplx {
-    let mut r = (1.0, 0.0);
-    for _ in 0..n {
-        r = cmplx_mult(r, c);
-    }
-    return r;
+fn check_solution(solution: Complex, (a, b, c, d, e): (f64, f64, f64, f64, f64)) {
+    let t = solution.powi(4) * a + solution.powi(3) * b + solution.powi(2) * c + solution * d + e;
+    println!("Input: {:?}; Output: {:?}", (a, b, c, d, e), t);
+    assert!(t.approx_eq(complex::ZERO, EPSILON));
 }
 
-fn check_solution(solution: Cmplx, (a, b, c, d, e): (f64, f64, f64, f64, f64)) -> () {
-    let mut t = cmplx_mult((a, 0.0), cmplx_pow(solution, 4));
-    t = cmplx_add(t, cmplx_mult((b, 0.0), cmplx_pow(solution, 3)));
-    t = cmplx_add(t, cmplx_mult((c, 0.0), cmplx_pow(solution, 2)));
-    t = cmplx_add(t, cmplx_mult((d, 0.0), cmplx_pow(solution, 1)));
-    let (r, i) = cmplx_add(t, (e, 0.0));
-    println!("Input: {:?}; Output: {:?}", (a, b, c, d, e), (r, i));
-    assert!(r.abs() < EPSILON && i.abs() < EPSILON);
-}
-
-fn check_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> () {
+fn check_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) {
     let (s1, s2, s3, s4) = solve_quartic(a, b, c, d, e);
     check_solution(s1, (a, b, c, d, e));
     check_solution(s2, (a, b, c, d, e));
@@ -249,13 +243,14 @@ fn check_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> () {
 
 #[test]
 fn test_quartic() {
+    let c = Complex::new;
     assert!(cmplx_cmp_4(solve_quartic(3.0, 6.0, -123.0, -126.0, 1080.0),
-                        ((5.0, 0.0), (3.0, 0.0), (-4.0, 0.0), (-6.0, 0.0))));
+                        (c(5.0, 0.0), c(3.0, 0.0), c(-4.0, 0.0), c(-6.0, 0.0))));
     assert!(cmplx_cmp_4(solve_quartic(1.0, -5.0 / 20.0, -17.0 / 20.0, 29.0 / 20.0, -87.0 / 20.0),
-                        ((1.48758311033, 0.0),
-                         (0.222210408124, 1.29967219908),
-                         (0.222210408124, -1.29967219908),
-                         (-1.68200392658, 0.0))));
+                        (c(1.48758311033, 0.0),
+                         c(0.222210408124, 1.29967219908),
+                         c(0.222210408124, -1.29967219908),
+                         c(-1.68200392658, 0.0))));
     check_quartic(1.0, 0.0, 0.0, 0.0, 0.0);
     check_quartic(1.0, 0.0, 0.0, 0.0, -1.0);
     check_quartic(1.0, 0.0, 0.0, 0.0, 1.0);
@@ -296,6 +291,44 @@ fn test_quartic() {
                   625.5001);
 }
 
+/// Checks the robust solvers find every real root in `expected`, and nothing else.
+/// Roots closer together than the tolerance count as one.
+fn check_real_roots((a, b, c, d, e): (f64, f64, f64, f64, f64), expected: &[f64]) {
+    let close = |x: f64, y: f64| (x - y).abs() < 1.0e-6 * (1.0 + y.abs());
+    let roots = Polynomial::from_highest_first(&[a, b, c, d, e]).real_roots(-1.0 / 0.0, 1.0 / 0.0);
+    println!("Input: {:?}; Roots: {:?}", (a, b, c, d, e), roots);
+    assert!(expected.iter().all(|&x| roots.iter().any(|&r| close(r, x))));
+    assert!(roots.iter().all(|&r| expected.iter().any(|&x| close(r, x))));
+
+    let smallest = expected.iter().cloned().filter(|&x| x > 0.0).fold(1.0 / 0.0, f64::min);
+    for &method in [QuarticMethod::Sturm, QuarticMethod::FerrariWithFallback].iter() {
+        let found = solve_quartic_smallest_positive_real_with(method, a, b, c, d, e, 0.0).unwrap();
+        assert!(close(found, smallest));
+    }
+}
+
+// Quartics that Ferrari's method gets wrong, built from their roots.
+#[test]
+fn test_ill_conditioned_quartics() {
+    // Roots of very different magnitudes.
+    check_real_roots((1.0, -1011.001, 11011.011, -10011.009999999998, 10.0),
+                     &[0.001, 1.0, 10.0, 1000.0]);
+    check_real_roots((1.0, -100.6, 60.11000000000001, -11.006000000000002, 0.6000000000000001),
+                     &[0.1, 0.2, 0.3, 100.0]);
+    check_real_roots((1.0, -110.03, 3003.3002, -90.022, 0.6), &[0.01, 0.02, 50.0, 60.0]);
+    check_real_roots((1.0, 999.495, -504.997494, 2.505997, -0.003),
+                     &[-1000.0, 0.002, 0.003, 0.5]);
+    // A near double root and a double root, where Ferrari finds no real roots at all.
+    check_real_roots((1.0, -14.0000001, 69.0000012, -140.0000045, 100.00000499999999),
+                     &[2.0, 2.0000001, 5.0]);
+    // Multiple roots that Ferrari does handle.
+    check_real_roots((1.0, -4.0, 6.0, -4.0, 1.0), &[1.0]);
+    check_real_roots((1.0, -9.0, 30.0, -44.0, 24.0), &[2.0, 3.0]);
+    // A leading term that is rounding noise: really the cubic (x - 1)(x - 2)(x - 3).
+    let found = solve_quartic_smallest_positive_real_with(QuarticMethod::Sturm, 1.0e-20, 1.0, -6.0, 11.0, -6.0, 0.0);
+    assert!((found.unwrap() - 1.0).abs() < 1.0e-9);
+}
+
 #[test]
 fn test_qaudratic() {
     // x^2 + x = 0 => (0, -1)
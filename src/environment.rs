@@ -0,0 +1,247 @@
+use std::f64::consts::PI;
+use std::rc::Rc;
+use vec3::Vector3;
+use color::Color;
+use image::Image;
+use sampling::Distribution2D;
+use shape::{Light, LightSample};
+use transform::Transform;
+
+/// Light arriving from infinitely far away, as a function of direction.
+pub trait Environment {
+    /// Radiance arriving along rays travelling in `-direction`, that is
+    /// seen looking in `direction`. `direction` is a unit vector.
+    fn radiance(&self, direction: Vector3) -> Color;
+}
+
+/// The same in every direction.
+pub struct Constant(pub Color);
+
+impl Environment for Constant {
+    fn radiance(&self, _: Vector3) -> Color {
+        return self.0;
+    }
+}
+
+/// Blends from `horizon` up to `zenith`, with a flat `ground` below.
+pub struct Gradient {
+    pub up: Vector3,
+    pub zenith: Color,
+    pub horizon: Color,
+    pub ground: Color,
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: Vector3) -> Color {
+        let height = direction.dot(self.up.normalize());
+        if height < 0.0 {
+            return self.ground;
+        }
+        return self.horizon * (1.0 - height) + self.zenith * height;
+    }
+}
+
+/// Preetham, Shirley and Smits' analytic model of the clear daylight sky.
+///
+/// Only the sky is modelled; add the sun itself as a `DirectionalLight`.
+pub struct Sky {
+    /// Unit vector towards the sun, at or above the horizon.
+    pub sun: Vector3,
+    pub up: Vector3,
+    /// Haziness, from about 2 for a clear sky to 10 for a hazy one.
+    pub turbidity: f64,
+    /// Luminance of the sky at the zenith.
+    pub intensity: f64,
+    pub ground: Color,
+}
+
+// Perez's sky distribution function, with coefficients A to E.
+fn perez(c: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+    return (1.0 + c[0] * (c[1] / cos_theta).exp()) *
+           (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * gamma.cos() * gamma.cos());
+}
+
+impl Sky {
+    // Perez coefficients for luminance Y and chromaticities x and y.
+    fn coefficients(&self) -> [[f64; 5]; 3] {
+        let t = self.turbidity;
+        return [[0.1787 * t - 1.4630,
+                 -0.3554 * t + 0.4275,
+                 -0.0227 * t + 5.3251,
+                 0.1206 * t - 2.5771,
+                 -0.0670 * t + 0.3703],
+                [-0.0193 * t - 0.2592,
+                 -0.0665 * t + 0.0008,
+                 -0.0004 * t + 0.2125,
+                 -0.0641 * t - 0.8989,
+                 -0.0033 * t + 0.0452],
+                [-0.0167 * t - 0.2608,
+                 -0.0950 * t + 0.0092,
+                 -0.0079 * t + 0.2102,
+                 -0.0441 * t - 1.6537,
+                 -0.0109 * t + 0.0529]];
+    }
+
+    // Chromaticity x and y at the zenith.
+    fn zenith_chromaticity(&self, theta_sun: f64) -> (f64, f64) {
+        let t = self.turbidity;
+        let (t1, t2, t3) = (theta_sun, theta_sun * theta_sun, theta_sun * theta_sun * theta_sun);
+        let x = t * t * (0.00166 * t3 - 0.00375 * t2 + 0.00209 * t1) +
+                t * (-0.02903 * t3 + 0.06377 * t2 - 0.03202 * t1 + 0.00394) +
+                (0.11693 * t3 - 0.21196 * t2 + 0.06052 * t1 + 0.25886);
+        let y = t * t * (0.00275 * t3 - 0.00610 * t2 + 0.00317 * t1) +
+                t * (-0.04214 * t3 + 0.08970 * t2 - 0.04153 * t1 + 0.00516) +
+                (0.15346 * t3 - 0.26756 * t2 + 0.06670 * t1 + 0.26688);
+        return (x, y);
+    }
+}
+
+impl Environment for Sky {
+    fn radiance(&self, direction: Vector3) -> Color {
+        let up = self.up.normalize();
+        let cos_theta = direction.dot(up);
+        if cos_theta < 0.0 {
+            return self.ground;
+        }
+        // Keep away from the horizon, where the model blows up.
+        let cos_theta = cos_theta.max(0.01);
+        let sun = self.sun.normalize();
+        let theta_sun = sun.dot(up).clamp(0.0, 1.0).acos();
+        let gamma = direction.dot(sun).clamp(-1.0, 1.0).acos();
+        let c = self.coefficients();
+        let relative = |c: &[f64; 5]| perez(c, cos_theta, gamma) / perez(c, 1.0, theta_sun);
+        let (zenith_x, zenith_y) = self.zenith_chromaticity(theta_sun);
+        let luminance = self.intensity * relative(&c[0]);
+        let (x, y) = (zenith_x * relative(&c[1]), zenith_y * relative(&c[2]));
+        // From xyY to XYZ.
+        return Color::from_xyz(x * luminance / y, luminance, (1.0 - x - y) * luminance / y);
+    }
+}
+
+/// Direction to point on an equirectangular map, with `(0, 0)` the top left
+/// and the top row looking along +y.
+pub fn direction_to_equirect(direction: Vector3) -> (f64, f64) {
+    let theta = direction.y.clamp(-1.0, 1.0).acos();
+    let mut phi = direction.z.atan2(direction.x);
+    if phi < 0.0 {
+        phi += 2.0 * PI;
+    }
+    return (phi / (2.0 * PI), theta / PI);
+}
+
+pub fn equirect_to_direction(u: f64, v: f64) -> Vector3 {
+    let (sin_theta, cos_theta) = (v * PI).sin_cos();
+    let (sin_phi, cos_phi) = (u * 2.0 * PI).sin_cos();
+    return Vector3::new(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi);
+}
+
+/// An equirectangular (latitude-longitude) image of the surroundings, for
+/// example loaded with `Image::read` from a `.hdr` or `.pfm` file.
+pub struct EnvironmentMap {
+    pub image: Image,
+    pub scale: f64,
+}
+
+impl Environment for EnvironmentMap {
+    // Nearest texel, matching the piecewise constant importance sampling.
+    fn radiance(&self, direction: Vector3) -> Color {
+        let (u, v) = direction_to_equirect(direction);
+        let x = ((u * self.image.width as f64) as usize).min(self.image.width - 1);
+        let y = ((v * self.image.height as f64) as usize).min(self.image.height - 1);
+        return self.image.get(x, y) * self.scale;
+    }
+}
+
+/// Lights the scene from an `Environment`, which is also seen behind
+/// everything. Directions are importance sampled from a table of the
+/// environment's brightness.
+#[derive(Clone)]
+pub struct EnvironmentLight {
+    pub environment: Rc<dyn Environment>,
+    /// Rotates the environment into place.
+    pub transform: Transform,
+    pub samples: u32,
+    distribution: Rc<Distribution2D>,
+}
+
+impl EnvironmentLight {
+    /// Tabulates the environment on a `width` by `height` equirectangular
+    /// grid; use an environment map's own size for maps.
+    pub fn new(environment: Rc<dyn Environment>, width: usize, height: usize, samples: u32) -> EnvironmentLight {
+        let mut weights = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let v = (y as f64 + 0.5) / height as f64;
+            for x in 0..width {
+                let u = (x as f64 + 0.5) / width as f64;
+                let direction = equirect_to_direction(u, v);
+                // Rows near the poles cover less of the sphere.
+                weights.push(environment.radiance(direction).luminance() * (v * PI).sin());
+            }
+        }
+        return EnvironmentLight {
+            environment,
+            transform: Transform::identity(),
+            samples,
+            distribution: Rc::new(Distribution2D::new(&weights, width, height)),
+        };
+    }
+
+    pub fn for_map(map: EnvironmentMap, samples: u32) -> EnvironmentLight {
+        let (width, height) = (map.image.width, map.image.height);
+        return EnvironmentLight::new(Rc::new(map), width, height, samples);
+    }
+
+    fn to_local(&self, direction: Vector3) -> Vector3 {
+        return self.transform.inverse.transform_vector(direction).normalize();
+    }
+}
+
+impl Light for EnvironmentLight {
+    fn illuminate(&self, _: Vector3, (u, v): (f64, f64)) -> Option<LightSample> {
+        let ((x, y), pdf) = self.distribution.sample(u, v);
+        let sin_theta = (y * PI).sin();
+        if pdf == 0.0 || sin_theta == 0.0 {
+            return None;
+        }
+        let local = equirect_to_direction(x, y);
+        // From density over the unit square to density per steradian.
+        let pdf = pdf / (2.0 * PI * PI * sin_theta);
+        return Some(LightSample {
+            direction: self.transform.vector(local).normalize(),
+            distance: 1.0 / 0.0,
+            color: self.environment.radiance(local) * (1.0 / pdf),
+        });
+    }
+
+    fn sample_count(&self) -> u32 {
+        self.samples
+    }
+
+    fn background(&self, direction: Vector3) -> Color {
+        return self.environment.radiance(self.to_local(direction));
+    }
+
+    fn pdf(&self, _: Vector3, direction: Vector3) -> f64 {
+        let (x, y) = direction_to_equirect(self.to_local(direction));
+        let sin_theta = (y * PI).sin();
+        if sin_theta == 0.0 {
+            return 0.0;
+        }
+        return self.distribution.pdf(x, y) / (2.0 * PI * PI * sin_theta);
+    }
+
+    fn transformed(&self, transform: &Transform) -> Box<dyn Light> {
+        Box::new(EnvironmentLight {
+            transform: *transform * self.transform,
+            ..self.clone()
+        })
+    }
+}
+
+#[test]
+fn test_equirect() {
+    let d = Vector3::new(0.3, -0.5, -0.8).normalize();
+    let (u, v) = direction_to_equirect(d);
+    assert!((equirect_to_direction(u, v) - d).len() < 1.0e-12);
+    assert_eq!(direction_to_equirect(Vector3::new(0.0, 1.0, 0.0)).1, 0.0);
+}
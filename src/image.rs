@@ -0,0 +1,644 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::mem;
+use std::path::Path;
+use color;
+use color::Color;
+use inflate::zlib_decompress;
+
+/// A grid of colors, stored row by row from the top left.
+///
+/// High dynamic range images hold linear colors. Images read from 8 and
+/// 16 bit formats hold their values as stored, scaled to `[0, 1]`; these
+/// are usually sRGB encoded, and should go through `srgb_to_linear` before
+/// being used as colors, but not when they hold other data such as normals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![color::BLACK; width * height],
+        }
+    }
+
+    /// Like `new`, for sizes read from a file: sizes whose pixels could not
+    /// be counted or held in memory are an error rather than a panic.
+    pub fn checked_new(width: usize, height: usize) -> io::Result<Image> {
+        let bytes = width.checked_mul(height).and_then(|n| n.checked_mul(mem::size_of::<Color>()));
+        match bytes {
+            Some(bytes) if bytes <= isize::MAX as usize => return Ok(Image::new(width, height)),
+            _ => return Err(invalid_data("image too big")),
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        return self.pixels[y * self.width + x];
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /// Decodes sRGB encoded values to linear colors.
+    pub fn srgb_to_linear(self) -> Image {
+        Image {
+            pixels: self.pixels.into_iter().map(Color::srgb_to_linear).collect(),
+            ..self
+        }
+    }
+
+    /// Half the size, rounding up, with each pixel the average of the up to
+    /// four it covers.
+    pub fn downsample(&self) -> Image {
+        let (width, height) = (self.width.div_ceil(2), self.height.div_ceil(2));
+        let mut image = Image::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let xs = (2 * x)..(2 * x + 2).min(self.width);
+                let ys = (2 * y)..(2 * y + 2).min(self.height);
+                let count = (xs.len() * ys.len()) as f64;
+                let mut sum = color::BLACK;
+                for sy in ys {
+                    for sx in xs.clone() {
+                        sum = sum + self.get(sx, sy);
+                    }
+                }
+                image.set(x, y, sum * (1.0 / count));
+            }
+        }
+        return image;
+    }
+
+    /// Reads an image, choosing the format from the file extension.
+    pub fn read(path: &Path) -> io::Result<Image> {
+        let mut file = BufReader::new(File::open(path)?);
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("hdr") => return read_hdr(&mut file),
+            Some("pfm") => return read_pfm(&mut file),
+            Some("ppm") | Some("pnm") => return read_ppm(&mut file),
+            Some("png") => return read_png(&mut file),
+            _ => return Err(invalid_data("unknown image format")),
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+// Reads exactly `count` bytes, growing the buffer as they arrive rather
+// than trusting a size read from the file.
+fn read_bytes(file: &mut dyn BufRead, count: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    Read::take(&mut *file, count as u64).read_to_end(&mut data)?;
+    if data.len() != count {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "image data too short"));
+    }
+    return Ok(data);
+}
+
+fn read_line(file: &mut dyn BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    if file.read_line(&mut line)? == 0 {
+        return Err(invalid_data("unexpected end of header"));
+    }
+    return Ok(line.trim_end().to_string());
+}
+
+/// Reads a Portable Float Map, the floating point counterpart of PNM.
+pub fn read_pfm(file: &mut dyn BufRead) -> io::Result<Image> {
+    let channels = match read_line(file)?.as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid_data("not a PFM file")),
+    };
+    let size: Vec<usize> = read_line(file)?
+        .split_whitespace()
+        .map(|s| s.parse().map_err(|_| invalid_data("bad PFM size")))
+        .collect::<io::Result<_>>()?;
+    if size.len() != 2 {
+        return Err(invalid_data("bad PFM size"));
+    }
+    let (width, height) = (size[0], size[1]);
+    let scale: f64 = read_line(file)?.parse().map_err(|_| invalid_data("bad PFM scale"))?;
+    // The sign of the scale gives the byte order.
+    let little_endian = scale < 0.0;
+
+    let count = width.checked_mul(height)
+        .and_then(|n| n.checked_mul(channels * 4))
+        .ok_or_else(|| invalid_data("bad PFM size"))?;
+    let data = read_bytes(file, count)?;
+    let values: Vec<f64> = data.chunks(4)
+        .map(|b| {
+            let bytes = [b[0], b[1], b[2], b[3]];
+            if little_endian {
+                f32::from_le_bytes(bytes) as f64
+            } else {
+                f32::from_be_bytes(bytes) as f64
+            }
+        })
+        .collect();
+
+    let mut image = Image::checked_new(width, height)?;
+    for (i, pixel) in values.chunks(channels).enumerate() {
+        // Rows are stored from the bottom up.
+        let (x, y) = (i % width, height - 1 - i / width);
+        let c = if channels == 3 {
+            Color::new(pixel[0], pixel[1], pixel[2])
+        } else {
+            Color::new(pixel[0], pixel[0], pixel[0])
+        };
+        image.set(x, y, c);
+    }
+    return Ok(image);
+}
+
+/// Reads a Radiance RGBE (`.hdr`) image, flat or run length encoded.
+/// Only the usual `-Y height +X width` orientation is supported.
+pub fn read_hdr(file: &mut dyn BufRead) -> io::Result<Image> {
+    let magic = read_line(file)?;
+    if !magic.starts_with("#?") {
+        return Err(invalid_data("not a Radiance HDR file"));
+    }
+    loop {
+        let line = read_line(file)?;
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(invalid_data("unsupported HDR pixel format"));
+        }
+    }
+    let resolution: Vec<String> = read_line(file)?.split_whitespace().map(String::from).collect();
+    if resolution.len() != 4 || resolution[0] != "-Y" || resolution[2] != "+X" {
+        return Err(invalid_data("unsupported HDR orientation"));
+    }
+    let height: usize = resolution[1].parse().map_err(|_| invalid_data("bad HDR size"))?;
+    let width: usize = resolution[3].parse().map_err(|_| invalid_data("bad HDR size"))?;
+
+    if width == 0 || height == 0 || width.checked_mul(height).and_then(|n| n.checked_mul(4)).is_none() {
+        return Err(invalid_data("bad HDR size"));
+    }
+
+    // The pixels are gathered as they are read, so a size bigger than the
+    // file is found out before it is allocated.
+    let mut pixels = Vec::new();
+    for _ in 0..height {
+        let scanline = read_hdr_scanline(file, width)?;
+        pixels.extend(scanline.into_iter().map(rgbe_to_color));
+    }
+    return Ok(Image { width, height, pixels });
+}
+
+fn read_hdr_scanline(file: &mut dyn BufRead, width: usize) -> io::Result<Vec<[u8; 4]>> {
+    let mut start = [0u8; 4];
+    file.read_exact(&mut start)?;
+    let run_length_encoded = (8..0x8000).contains(&width) && start[0] == 2 && start[1] == 2 &&
+                             start[2] & 0x80 == 0;
+    if !run_length_encoded {
+        let rest = read_bytes(file, (width - 1) * 4)?;
+        let mut scanline = vec![start];
+        scanline.extend(rest.chunks(4).map(|b| [b[0], b[1], b[2], b[3]]));
+        return Ok(scanline);
+    }
+    if ((start[2] as usize) << 8 | start[3] as usize) != width {
+        return Err(invalid_data("bad HDR scanline width"));
+    }
+    let mut scanline = vec![[0u8; 4]; width];
+    // Each of the four components is stored separately, as runs of a
+    // repeated byte or literal bytes.
+    for component in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            file.read_exact(&mut count)?;
+            let (run, count) = if count[0] > 128 {
+                (true, count[0] as usize - 128)
+            } else {
+                (false, count[0] as usize)
+            };
+            if count == 0 || x + count > width {
+                return Err(invalid_data("bad HDR run length"));
+            }
+            let mut byte = [0u8; 1];
+            if run {
+                file.read_exact(&mut byte)?;
+            }
+            for pixel in scanline[x..x + count].iter_mut() {
+                if !run {
+                    file.read_exact(&mut byte)?;
+                }
+                pixel[component] = byte[0];
+            }
+            x += count;
+        }
+    }
+    return Ok(scanline);
+}
+
+// The next whitespace separated word of a PNM header, skipping comments.
+// The single whitespace byte after the word is consumed too.
+fn read_pnm_token(file: &mut dyn BufRead) -> io::Result<String> {
+    let mut token = String::new();
+    let mut byte = [0u8; 1];
+    loop {
+        file.read_exact(&mut byte)?;
+        match byte[0] {
+            b'#' if token.is_empty() => {
+                read_line(file)?;
+            }
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                if !token.is_empty() {
+                    return Ok(token);
+                }
+            }
+            b => token.push(b as char),
+        }
+    }
+}
+
+fn read_pnm_number(file: &mut dyn BufRead) -> io::Result<usize> {
+    return read_pnm_token(file)?.parse().map_err(|_| invalid_data("bad PNM header"));
+}
+
+/// Reads a binary (P6) or plain (P3) Portable Pixmap, such as those
+/// written by `pnm::write_pnm`.
+pub fn read_ppm(file: &mut dyn BufRead) -> io::Result<Image> {
+    let binary = match read_pnm_token(file)?.as_str() {
+        "P6" => true,
+        "P3" => false,
+        _ => return Err(invalid_data("not a PPM file")),
+    };
+    let width = read_pnm_number(file)?;
+    let height = read_pnm_number(file)?;
+    let max_value = read_pnm_number(file)?;
+    if max_value == 0 || max_value > 65535 {
+        return Err(invalid_data("bad PPM maximum value"));
+    }
+    let count = width.checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or_else(|| invalid_data("bad PPM size"))?;
+    let values: Vec<usize> = if !binary {
+        (0..count).map(|_| read_pnm_number(file)).collect::<io::Result<_>>()?
+    } else if max_value < 256 {
+        read_bytes(file, count)?.into_iter().map(|b| b as usize).collect()
+    } else {
+        let bytes = count.checked_mul(2).ok_or_else(|| invalid_data("bad PPM size"))?;
+        read_bytes(file, bytes)?.chunks(2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize).collect()
+    };
+
+    let scale = 1.0 / max_value as f64;
+    let mut image = Image::checked_new(width, height)?;
+    for (i, pixel) in values.chunks(3).enumerate() {
+        let c = Color::new(pixel[0] as f64 * scale, pixel[1] as f64 * scale, pixel[2] as f64 * scale);
+        image.set(i % width, i / width, c);
+    }
+    return Ok(image);
+}
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+// Pixel offset and spacing in x and y of each of the seven Adam7 passes.
+const ADAM7: [(usize, usize, usize, usize); 7] = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4),
+                                                   (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    return !crc;
+}
+
+fn read_u32(file: &mut dyn BufRead) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    file.read_exact(&mut bytes)?;
+    return Ok(u32::from_be_bytes(bytes));
+}
+
+/// Reads a PNG image of any bit depth and color type, interlaced or not.
+/// Transparency is ignored.
+pub fn read_png(file: &mut dyn BufRead) -> io::Result<Image> {
+    let mut signature = [0u8; 8];
+    file.read_exact(&mut signature)?;
+    if signature != PNG_SIGNATURE {
+        return Err(invalid_data("not a PNG file"));
+    }
+
+    let mut header: Option<(usize, usize, u8, u8, u8)> = None;
+    let mut palette: Vec<Color> = Vec::new();
+    let mut compressed = Vec::new();
+    loop {
+        let length = read_u32(file)? as usize;
+        if length > 0x7fff_ffff {
+            return Err(invalid_data("bad PNG chunk length"));
+        }
+        // The chunk type and data, which the checksum covers.
+        let chunk = read_bytes(file, length + 4)?;
+        if read_u32(file)? != crc32(&chunk) {
+            return Err(invalid_data("PNG chunk checksum mismatch"));
+        }
+        let data = &chunk[4..];
+        match &chunk[..4] {
+            b"IHDR" => {
+                if length != 13 || data[10] != 0 || data[11] != 0 || data[12] > 1 {
+                    return Err(invalid_data("unsupported PNG header"));
+                }
+                let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+                let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+                header = Some((width, height, data[8], data[9], data[12]));
+            }
+            b"PLTE" => {
+                if !length.is_multiple_of(3) || length > 256 * 3 {
+                    return Err(invalid_data("bad PNG palette"));
+                }
+                palette = data.chunks(3)
+                    .map(|c| Color::from_rgb(c[0], c[1], c[2]))
+                    .collect();
+            }
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            // Chunks with a lower case first letter may be safely ignored.
+            kind if kind[0] & 0x20 != 0 => {}
+            _ => return Err(invalid_data("unsupported PNG chunk")),
+        }
+    }
+
+    let (width, height, bit_depth, color_type, interlace) = match header {
+        Some(h) => h,
+        None => return Err(invalid_data("missing PNG header")),
+    };
+    let channels = match (color_type, bit_depth) {
+        (0, 1) | (0, 2) | (0, 4) | (0, 8) | (0, 16) => 1,
+        (3, 1) | (3, 2) | (3, 4) | (3, 8) => 1,
+        (2, 8) | (2, 16) => 3,
+        (4, 8) | (4, 16) => 2,
+        (6, 8) | (6, 16) => 4,
+        _ => return Err(invalid_data("bad PNG bit depth or color type")),
+    };
+    let bits_per_pixel = channels * bit_depth as usize;
+    let passes: &[(usize, usize, usize, usize)] = if interlace == 1 { &ADAM7 } else { &[(0, 0, 1, 1)] };
+    // Each row of each pass is a filter byte and then the packed pixels.
+    let mut expected = Some(0usize);
+    for &(x0, y0, dx, dy) in passes {
+        if width > x0 && height > y0 {
+            let pass_width = (width - x0).div_ceil(dx);
+            let pass_height = (height - y0).div_ceil(dy);
+            let row = pass_width.checked_mul(bits_per_pixel).map(|bits| 1 + bits.div_ceil(8));
+            expected = row.and_then(|row| row.checked_mul(pass_height)).and_then(|n| expected?.checked_add(n));
+        }
+    }
+    let expected = expected.ok_or_else(|| invalid_data("bad PNG size"))?;
+    let data = zlib_decompress(&compressed)?;
+    if data.len() != expected {
+        return Err(invalid_data("PNG image data is the wrong size"));
+    }
+
+    let mut image = Image::checked_new(width, height)?;
+    let mut offset = 0;
+    for &(x0, y0, dx, dy) in passes {
+        // Small images leave some passes empty.
+        if width <= x0 || height <= y0 {
+            continue;
+        }
+        let pass_width = (width - x0).div_ceil(dx);
+        let pass_height = (height - y0).div_ceil(dy);
+        let stride = (pass_width * bits_per_pixel).div_ceil(8);
+        let mut previous = vec![0u8; stride];
+        for row_index in 0..pass_height {
+            let filter = data[offset];
+            let mut row = data[offset + 1..offset + 1 + stride].to_vec();
+            offset += 1 + stride;
+            unfilter(filter, &mut row, &previous, bits_per_pixel.div_ceil(8))?;
+            for i in 0..pass_width {
+                let sample = |channel: usize| png_sample(&row, i * channels + channel, bit_depth);
+                let max = ((1u32 << bit_depth) - 1) as f64;
+                let c = match color_type {
+                    0 | 4 => {
+                        let grey = sample(0) as f64 / max;
+                        Color::new(grey, grey, grey)
+                    }
+                    3 => *palette.get(sample(0) as usize).ok_or_else(|| invalid_data("bad PNG palette index"))?,
+                    _ => Color::new(sample(0) as f64 / max, sample(1) as f64 / max, sample(2) as f64 / max),
+                };
+                image.set(x0 + i * dx, y0 + row_index * dy, c);
+            }
+            previous = row;
+        }
+    }
+    return Ok(image);
+}
+
+// The `index`th sample of a row, packed most significant bits first.
+fn png_sample(row: &[u8], index: usize, bit_depth: u8) -> u32 {
+    match bit_depth {
+        16 => return u16::from_be_bytes([row[2 * index], row[2 * index + 1]]) as u32,
+        8 => return row[index] as u32,
+        _ => {
+            let bit = index * bit_depth as usize;
+            let shift = 8 - bit_depth as usize - bit % 8;
+            return (row[bit / 8] >> shift) as u32 & ((1 << bit_depth) - 1);
+        }
+    }
+}
+
+// Undoes a PNG row filter, which predicts each byte from the ones to the
+// left (a whole pixel back, or one byte when pixels are smaller), above
+// and above left.
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], bytes_per_pixel: usize) -> io::Result<()> {
+    for i in 0..row.len() {
+        let left = if i >= bytes_per_pixel { row[i - bytes_per_pixel] } else { 0 };
+        let above = previous[i];
+        let above_left = if i >= bytes_per_pixel { previous[i - bytes_per_pixel] } else { 0 };
+        let prediction = match filter {
+            0 => 0,
+            1 => left,
+            2 => above,
+            3 => ((left as u16 + above as u16) / 2) as u8,
+            4 => paeth(left, above, above_left),
+            _ => return Err(invalid_data("bad PNG filter")),
+        };
+        row[i] = row[i].wrapping_add(prediction);
+    }
+    return Ok(());
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        return a;
+    } else if pb <= pc {
+        return b;
+    } else {
+        return c;
+    }
+}
+
+fn rgbe_to_color(rgbe: [u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return color::BLACK;
+    }
+    let f = 2.0f64.powi(rgbe[3] as i32 - 136);
+    return Color::new(rgbe[0] as f64 * f, rgbe[1] as f64 * f, rgbe[2] as f64 * f);
+}
+
+#[test]
+fn test_read_pfm() {
+    let mut data = b"PF\n2 1\n-1.0\n".to_vec();
+    for v in [1.0f32, 2.0, 3.0, 0.5, 0.25, 0.125].iter() {
+        data.extend_from_slice(&v.to_le_bytes());
+    }
+    let image = read_pfm(&mut &data[..]).unwrap();
+    assert_eq!((image.width, image.height), (2, 1));
+    assert_eq!(image.get(0, 0), Color::new(1.0, 2.0, 3.0));
+    assert_eq!(image.get(1, 0), Color::new(0.5, 0.25, 0.125));
+
+    // Greyscale, big endian, stored bottom row first.
+    let mut data = b"Pf\n1 2\n1.0\n".to_vec();
+    data.extend_from_slice(&1.0f32.to_be_bytes());
+    data.extend_from_slice(&4.0f32.to_be_bytes());
+    let image = read_pfm(&mut &data[..]).unwrap();
+    assert_eq!(image.get(0, 0), Color::new(4.0, 4.0, 4.0));
+    assert_eq!(image.get(0, 1), Color::new(1.0, 1.0, 1.0));
+}
+
+#[test]
+fn test_read_hdr() {
+    // One flat scanline of two pixels: 0.5 grey and black.
+    let mut data = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+    data.extend_from_slice(&[128, 128, 128, 128, 0, 0, 0, 0]);
+    let image = read_hdr(&mut &data[..]).unwrap();
+    assert_eq!(image.get(0, 0), Color::new(0.5, 0.5, 0.5));
+    assert_eq!(image.get(1, 0), color::BLACK);
+
+    // A run length encoded scanline of eight pixels: a run of four red
+    // bytes then four literals, and runs for the other components.
+    let mut data = b"#?RGBE\n\n-Y 1 +X 8\n".to_vec();
+    data.extend_from_slice(&[2, 2, 0, 8]);
+    data.extend_from_slice(&[128 + 4, 64, 4, 1, 2, 3, 4]);
+    data.extend_from_slice(&[128 + 8, 0]);
+    data.extend_from_slice(&[128 + 8, 0]);
+    data.extend_from_slice(&[128 + 8, 137]);
+    let image = read_hdr(&mut &data[..]).unwrap();
+    assert_eq!(image.get(0, 0), Color::new(128.0, 0.0, 0.0));
+    assert_eq!(image.get(3, 0), Color::new(128.0, 0.0, 0.0));
+    assert_eq!(image.get(7, 0), Color::new(8.0, 0.0, 0.0));
+
+    // Sizes too big to count, or bigger than the data, are errors.
+    assert!(read_hdr(&mut &b"#?RGBE\n\n-Y 18446744073709551615 +X 2\n"[..]).is_err());
+    assert!(read_hdr(&mut &b"#?RGBE\n\n-Y 100000 +X 100000\n\x01\x02\x03\x04"[..]).is_err());
+    assert!(read_hdr(&mut &b"#?RGBE\n\n-Y 1 +X 0\n"[..]).is_err());
+}
+
+#[test]
+fn test_read_ppm() {
+    let data = b"P3\n# A comment\n2 1 15\n15 0 0  0 5 15\n";
+    let image = read_ppm(&mut &data[..]).unwrap();
+    assert_eq!(image.get(0, 0), color::RED);
+    assert_eq!(image.get(1, 0), Color::new(0.0, 1.0 / 3.0, 1.0));
+
+    // What `write_pnm` writes reads back the same, channels in order.
+    let colors = vec![Color::new(1.0, 0.2, 0.0), Color::new(0.0, 0.6, 1.0)];
+    let mut data = Vec::new();
+    ::pnm::write_pnm(colors.clone().into_iter(), 1, 2, &mut data);
+    let image = read_ppm(&mut &data[..]).unwrap();
+    assert_eq!((image.width, image.height), (1, 2));
+    for (pixel, expected) in image.pixels.iter().zip(colors.iter()) {
+        assert!((*pixel - *expected).max_channel().abs() < 1.0 / 255.0);
+        assert!((*expected - *pixel).max_channel().abs() < 1.0 / 255.0);
+    }
+
+    // Sixteen bit samples are big endian.
+    let mut data = b"P6 1 1 65535\n".to_vec();
+    data.extend_from_slice(&[255, 255, 128, 0, 0, 0]);
+    assert_eq!(read_ppm(&mut &data[..]).unwrap().get(0, 0), Color::new(1.0, 32768.0 / 65535.0, 0.0));
+
+    // Sizes too big to count, or bigger than the data, are errors.
+    assert!(read_ppm(&mut &b"P6 18446744073709551615 2 255\n"[..]).is_err());
+    assert!(read_ppm(&mut &b"P6 100000 100000 255\n\x01\x02\x03"[..]).is_err());
+
+    let linear = Color::new(0.0, 0.2, 1.0);
+    assert!((linear.linear_to_srgb().srgb_to_linear() - linear).max_channel().abs() < 1.0e-12);
+    assert!((Color::new(0.5, 0.5, 0.5).srgb_to_linear().red - 0.214).abs() < 1.0e-3);
+}
+
+#[test]
+fn test_read_png() {
+    // 3 by 2 RGB, with the rows filtered by Sub and Paeth.
+    let data = [137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0,
+                0, 0, 18, 22, 241, 77, 0, 0, 0, 24, 73, 68, 65, 84, 120, 156, 99, 228, 18, 145, 131, 0, 22, 86,
+                86, 214, 5, 70, 71, 204, 103, 255, 7, 0, 25, 14, 4, 108, 119, 110, 199, 109, 0, 0, 0, 0, 73, 69,
+                78, 68, 174, 66, 96, 130];
+    let image = read_png(&mut &data[..]).unwrap();
+    assert_eq!((image.width, image.height), (3, 2));
+    assert_eq!(image.get(1, 0), Color::from_rgb(40, 50, 60));
+    assert_eq!(image.get(1, 1), Color::from_rgb(200, 100, 0));
+    assert_eq!(image.get(2, 1), color::WHITE);
+
+    // 2 bit palette indices 2, 1 and 0, with a text chunk to skip.
+    let data = [137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 3, 0, 0, 0, 1, 2, 3, 0,
+                0, 0, 102, 142, 252, 39, 0, 0, 0, 9, 80, 76, 84, 69, 255, 0, 0, 0, 255, 0, 0, 0, 255, 45, 74, 205,
+                138, 0, 0, 0, 3, 116, 69, 88, 116, 97, 0, 98, 220, 73, 162, 59, 0, 0, 0, 10, 73, 68, 65, 84, 120,
+                156, 99, 152, 0, 0, 0, 146, 0, 145, 18, 34, 251, 123, 0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96,
+                130];
+    let image = read_png(&mut &data[..]).unwrap();
+    assert_eq!(image.pixels, vec![Color::new(0.0, 0.0, 1.0), Color::new(0.0, 1.0, 0.0), color::RED]);
+
+    // 3 by 3 interlaced greyscale, each pixel 10 y + x.
+    let mut data = [137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 3, 0, 0, 0, 3, 8, 0,
+                    0, 0, 1, 4, 68, 218, 245, 0, 0, 0, 23, 73, 68, 65, 84, 120, 156, 99, 96, 96, 96, 98, 16, 17,
+                    99, 96, 100, 16, 101, 224, 226, 230, 1, 0, 2, 101, 0, 100, 100, 135, 229, 237, 0, 0, 0, 0, 73,
+                    69, 78, 68, 174, 66, 96, 130];
+    let image = read_png(&mut &data[..]).unwrap();
+    for y in 0..3 {
+        for x in 0..3 {
+            let grey = (10 * y + x) as u8;
+            assert_eq!(image.get(x, y), Color::from_rgb(grey, grey, grey));
+        }
+    }
+    data[50] ^= 1;
+    assert!(read_png(&mut &data[..]).is_err());
+
+    // A palette that is not whole colors, and a chunk longer than the file.
+    let chunk = |kind: &[u8], body: &[u8]| {
+        let mut chunk = (body.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(body);
+        let crc = crc32(&chunk[4..]);
+        chunk.extend_from_slice(&crc.to_be_bytes());
+        chunk
+    };
+    let mut data = PNG_SIGNATURE.to_vec();
+    data.extend(chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0]));
+    let mut bad_palette = data.clone();
+    bad_palette.extend(chunk(b"PLTE", &[255, 0, 0, 255]));
+    assert_eq!(read_png(&mut &bad_palette[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    let mut too_long = data.clone();
+    too_long.extend_from_slice(&[0x7f, 0xff, 0xff, 0xff]);
+    too_long.extend_from_slice(b"IDAT");
+    assert!(read_png(&mut &too_long[..]).is_err());
+
+    // Image data that is not exactly the size in the header, here empty,
+    // is an error however big the header claims the image is.
+    let empty = [120, 156, 3, 0, 0, 0, 0, 1];
+    for &size in [[0, 0, 0, 1], [0xff, 0xff, 0xff, 0xff]].iter() {
+        let mut data = PNG_SIGNATURE.to_vec();
+        let header: Vec<u8> = size.iter().chain(size.iter()).chain([8, 0, 0, 0, 0].iter()).cloned().collect();
+        data.extend(chunk(b"IHDR", &header));
+        data.extend(chunk(b"IDAT", &empty));
+        data.extend(chunk(b"IEND", &[]));
+        assert_eq!(read_png(&mut &data[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,280 @@
+use std::io;
+
+// Decompression of zlib streams (RFC 1950) holding DEFLATE data (RFC 1951),
+// as found in PNG files.
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+// Reads bits least significant first, as DEFLATE packs them.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, count: u32) -> io::Result<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            if self.position >= self.data.len() {
+                return Err(invalid_data("unexpected end of compressed data"));
+            }
+            value |= ((self.data[self.position] >> self.bit) as u32 & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.position += 1;
+            }
+        }
+        return Ok(value);
+    }
+
+    fn align_to_byte(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.position += 1;
+        }
+    }
+}
+
+const MAX_BITS: usize = 15;
+
+// A canonical Huffman code, stored as the number of codes of each length
+// and the symbols in code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        return Huffman { counts, symbols };
+    }
+
+    // Codes are read a bit at a time, most significant first, walking the
+    // ranges of codes of each length.
+    fn decode(&self, reader: &mut BitReader) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        return Err(invalid_data("bad Huffman code"));
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67,
+                                83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5,
+                                5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513,
+                                  769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10,
+                                  11, 11, 12, 12, 13, 13];
+// The order code length code lengths are stored in, in dynamic blocks.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Decompresses a zlib stream, checking its Adler-32 checksum.
+pub fn zlib_decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    if data.len() < 6 || data[0] & 0x0f != 8 || !((data[0] as u16) << 8 | data[1] as u16).is_multiple_of(31) {
+        return Err(invalid_data("bad zlib header"));
+    }
+    if data[1] & 0x20 != 0 {
+        return Err(invalid_data("zlib preset dictionaries are not supported"));
+    }
+    let mut reader = BitReader {
+        data: &data[2..],
+        position: 0,
+        bit: 0,
+    };
+    let output = inflate(&mut reader)?;
+    reader.align_to_byte();
+    let end = 2 + reader.position;
+    if data.len() < end + 4 {
+        return Err(invalid_data("missing zlib checksum"));
+    }
+    let expected = u32::from_be_bytes([data[end], data[end + 1], data[end + 2], data[end + 3]]);
+    if adler32(&output) != expected {
+        return Err(invalid_data("zlib checksum mismatch"));
+    }
+    return Ok(output);
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    return b << 16 | a;
+}
+
+/// Decompresses raw DEFLATE data.
+fn inflate(reader: &mut BitReader) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored_block(reader, &mut output)?,
+            1 => {
+                let (literals, distances) = fixed_codes();
+                compressed_block(reader, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(reader)?;
+                compressed_block(reader, &mut output, &literals, &distances)?;
+            }
+            _ => return Err(invalid_data("bad DEFLATE block type")),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn stored_block(reader: &mut BitReader, output: &mut Vec<u8>) -> io::Result<()> {
+    reader.align_to_byte();
+    let start = reader.position;
+    if reader.data.len() < start + 4 {
+        return Err(invalid_data("unexpected end of compressed data"));
+    }
+    let header = &reader.data[start..start + 4];
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    if length != !u16::from_le_bytes([header[2], header[3]]) {
+        return Err(invalid_data("bad stored block length"));
+    }
+    let (begin, end) = (start + 4, start + 4 + length as usize);
+    if reader.data.len() < end {
+        return Err(invalid_data("unexpected end of compressed data"));
+    }
+    output.extend_from_slice(&reader.data[begin..end]);
+    reader.position = end;
+    return Ok(());
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    return (Huffman::new(&lengths), Huffman::new(&[5; 30]));
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    let mut code_lengths = [0u8; 19];
+    for &i in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[i] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    // Literal and distance code lengths, run length encoded as one sequence.
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or_else(|| invalid_data("bad code length repeat"))?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(invalid_data("bad code length repeat"));
+    }
+    return Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])));
+}
+
+fn compressed_block(reader: &mut BitReader,
+                    output: &mut Vec<u8>,
+                    literals: &Huffman,
+                    distances: &Huffman)
+                    -> io::Result<()> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        if symbol < 256 {
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+        let index = symbol - 257;
+        if index >= LENGTH_BASE.len() {
+            return Err(invalid_data("bad DEFLATE length"));
+        }
+        let length = LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+        let index = distances.decode(reader)? as usize;
+        if index >= DISTANCE_BASE.len() {
+            return Err(invalid_data("bad DEFLATE distance"));
+        }
+        let distance = DISTANCE_BASE[index] as usize + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+        if distance > output.len() {
+            return Err(invalid_data("DEFLATE distance too far back"));
+        }
+        // Copies may overlap what they are copying, repeating it.
+        let start = output.len() - distance;
+        for i in 0..length {
+            let byte = output[start + i];
+            output.push(byte);
+        }
+    }
+}
+
+#[test]
+fn test_zlib_decompress() {
+    // Stored, fixed Huffman and dynamic Huffman blocks, from Python's zlib.
+    let stored = [120, 1, 1, 3, 0, 252, 255, 97, 98, 99, 2, 77, 1, 39];
+    assert_eq!(zlib_decompress(&stored).unwrap(), b"abc");
+    let fixed = [120, 218, 203, 72, 205, 201, 201, 87, 200, 64, 144, 0, 58, 46, 6, 125];
+    assert_eq!(zlib_decompress(&fixed).unwrap(), b"hello hello hello");
+    let dynamic = [120, 218, 237, 204, 73, 1, 192, 64, 8, 3, 64, 173, 64, 184, 2, 248,
+                  255, 86, 72, 119, 4, 140, 168, 19, 55, 43, 62, 97, 138, 220, 208, 59,
+                  203, 43, 135, 183, 20, 84, 209, 202, 140, 164, 49, 128, 32, 182, 171, 23,
+                  91, 17, 181, 46, 195, 145, 56, 86, 81, 82, 111, 207, 74, 135, 92, 107,
+                  136, 188, 250, 191, 245, 7, 83, 158, 241, 81];
+    let expected: Vec<u8> = (0..600u32).map(|i| ((i * i + i / 7) % 13 + 97) as u8).collect();
+    assert_eq!(zlib_decompress(&dynamic).unwrap(), expected);
+
+    let mut corrupt = fixed;
+    corrupt[15] ^= 1;
+    assert!(zlib_decompress(&corrupt).is_err());
+    assert!(zlib_decompress(&fixed[..10]).is_err());
+}
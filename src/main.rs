@@ -1,4 +1,5 @@
 #![allow(dead_code)]
+#![allow(clippy::needless_return)]
 mod vec3;
 mod color;
 mod shape;
@@ -6,14 +7,28 @@ mod trace;
 mod test;
 mod pnm;
 mod screen;
-use vec3::Vector3;
 
 mod cubic;
+mod complex;
+mod polynomial;
+mod transform;
+mod scene;
+mod sampling;
+mod material;
+mod path;
+mod microfacet;
+mod image;
+mod environment;
+mod texture;
+mod inflate;
+mod noise;
+mod medium;
+mod ray;
+mod animation;
+mod sequence;
+mod spectrum;
 
-pub type Direction = Vector3;
-pub type Origin = Vector3;
-
-pub type Ray = (Direction, Origin);
+pub use ray::Ray;
 
 fn main() {
     trace::simple_trace();
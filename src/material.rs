@@ -0,0 +1,414 @@
+use color;
+use color::Color;
+use vec3::Vector3;
+use sampling;
+use sampling::Rng;
+use microfacet;
+use spectrum;
+use spectrum::Dispersion;
+use std::f64::consts::PI;
+
+/// A direction picked by importance sampling a material.
+#[derive(Debug, Clone, Copy)]
+pub struct BsdfSample {
+    pub direction: Vector3,
+    /// The BSDF times the cosine term, divided by the pdf.
+    pub weight: Color,
+    /// Per steradian.
+    pub pdf: f64,
+    /// Whether it came from a lobe sharp enough to be taken for a mirror
+    /// reflection or a clean refraction.
+    pub specular: bool,
+}
+
+/// How light scatters off a surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bsdf {
+    /// Ideal diffuse reflection.
+    Lambert,
+    /// Cook-Torrance GGX reflection over a diffuse base. `metallic` blends
+    /// from a dielectric, with a white 4% specular highlight over the
+    /// material's colour, to a metal whose reflections take the colour.
+    Microfacet { roughness: f64, metallic: f64 },
+    /// Rough glass, reflecting and refracting through GGX microfacets.
+    /// Transmitted light is tinted by the material's colour.
+    Dielectric { roughness: f64, ior: f64 },
+}
+
+/// A material parameter that can be driven by a texture. Scalar
+/// parameters take the luminance of the texture's colour.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    Diffuse,
+    Ambient,
+    Emission,
+    Roughness,
+    Metallic,
+    Ior,
+}
+
+/// How a surface reflects and emits light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    /// Albedo, or base colour for `Bsdf::Microfacet`.
+    pub diffuse: Color,
+    pub ambient: Color,
+    /// Emitted radiance, the same in every direction off the front of the surface.
+    pub emission: Color,
+    /// Shadow rays per shading point when the surface is sampled as a light.
+    pub light_samples: u32,
+    pub bsdf: Bsdf,
+    /// How the refractive index of `Bsdf::Dielectric` varies with
+    /// wavelength when rendering spectrally. Without it the index is the
+    /// same for all wavelengths.
+    pub dispersion: Option<Dispersion>,
+}
+
+// Below this GGX width highlights become too sharp to sample reliably.
+const MIN_ALPHA: f64 = 1.0e-3;
+// Specular lobes up to this roughness count as near-mirror.
+const SPECULAR_ROUGHNESS: f64 = 0.1;
+
+fn alpha(roughness: f64) -> f64 {
+    return (roughness * roughness).max(MIN_ALPHA);
+}
+
+impl Material {
+    pub fn diffuse(color: Color) -> Material {
+        Material {
+            diffuse: color,
+            ambient: color,
+            emission: color::BLACK,
+            light_samples: 1,
+            bsdf: Bsdf::Lambert,
+            dispersion: None,
+        }
+    }
+
+    /// A black surface that only glows.
+    pub fn emissive(emission: Color, light_samples: u32) -> Material {
+        Material {
+            emission,
+            light_samples,
+            ..Material::diffuse(color::BLACK)
+        }
+    }
+
+    /// Roughness and metallic both run from 0 to 1, and are clamped to it.
+    pub fn microfacet(color: Color, roughness: f64, metallic: f64) -> Material {
+        Material {
+            bsdf: Bsdf::Microfacet {
+                roughness: roughness.clamp(0.0, 1.0),
+                metallic: metallic.clamp(0.0, 1.0),
+            },
+            ..Material::diffuse(color)
+        }
+    }
+
+    /// Glass with refractive index `ior` and a transmission tint. Roughness
+    /// is clamped to `[0, 1]`.
+    pub fn dielectric(tint: Color, roughness: f64, ior: f64) -> Material {
+        Material {
+            ambient: color::BLACK,
+            bsdf: Bsdf::Dielectric {
+                roughness: roughness.clamp(0.0, 1.0),
+                ior,
+            },
+            ..Material::diffuse(tint)
+        }
+    }
+
+    pub fn with_emission(self, emission: Color, light_samples: u32) -> Material {
+        Material {
+            emission,
+            light_samples,
+            ..self
+        }
+    }
+
+    pub fn with_dispersion(self, dispersion: Dispersion) -> Material {
+        Material { dispersion: Some(dispersion), ..self }
+    }
+
+    /// This material as seen at a single wavelength: its colours become the
+    /// greys of their spectra there, and glass takes its index there.
+    pub fn at_wavelength(self, wavelength: f64) -> Material {
+        let grey = |c: Color| color::WHITE * spectrum::value(c, wavelength);
+        let mut material = Material {
+            diffuse: grey(self.diffuse),
+            ambient: grey(self.ambient),
+            emission: grey(self.emission),
+            ..self
+        };
+        if let (Some(dispersion), &mut Bsdf::Dielectric { ref mut ior, .. }) =
+               (self.dispersion, &mut material.bsdf) {
+            *ior = dispersion.ior(wavelength);
+        }
+        return material;
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        return self.emission != color::BLACK;
+    }
+
+    /// This material with one parameter replaced. Parameters the BSDF does
+    /// not have, such as the roughness of `Bsdf::Lambert`, are ignored.
+    /// Roughness and metallic are clamped to `[0, 1]`.
+    pub fn with_channel(self, channel: Channel, value: Color) -> Material {
+        let scalar = value.luminance();
+        let unit = scalar.clamp(0.0, 1.0);
+        let mut material = self;
+        match (channel, &mut material.bsdf) {
+            (Channel::Diffuse, _) => material.diffuse = value,
+            (Channel::Ambient, _) => material.ambient = value,
+            (Channel::Emission, _) => material.emission = value,
+            (Channel::Roughness, &mut Bsdf::Microfacet { ref mut roughness, .. }) |
+            (Channel::Roughness, &mut Bsdf::Dielectric { ref mut roughness, .. }) => *roughness = unit,
+            (Channel::Metallic, &mut Bsdf::Microfacet { ref mut metallic, .. }) => *metallic = unit,
+            (Channel::Ior, &mut Bsdf::Dielectric { ref mut ior, .. }) => *ior = scalar,
+            _ => {}
+        }
+        return material;
+    }
+
+    // The BSDF methods below take unit directions pointing away from the
+    // surface: `wo` back towards the viewer and `wi` towards the incoming
+    // light. `normal` is the geometric normal, which faces out of closed
+    // shapes; which side `wo` is on tells glass whether light is entering.
+
+    /// The BSDF, the ratio of scattered radiance to incident irradiance.
+    pub fn eval(&self, wo: Vector3, wi: Vector3, normal: Vector3) -> Color {
+        let n = face(normal, wo);
+        let (cos_o, cos_i) = (n.dot(wo), n.dot(wi));
+        match self.bsdf {
+            Bsdf::Lambert => {
+                if cos_i <= 0.0 {
+                    return color::BLACK;
+                }
+                return self.diffuse * (1.0 / PI);
+            }
+            Bsdf::Microfacet { roughness, metallic } => {
+                if cos_i <= 0.0 || cos_o <= 0.0 {
+                    return color::BLACK;
+                }
+                let alpha = alpha(roughness);
+                let h = (wo + wi).normalize();
+                let fresnel = microfacet::schlick(self.specular_color(metallic), wi.dot(h));
+                let specular = microfacet::distribution(alpha, n, h) *
+                               microfacet::smith_g(alpha, n, wo, wi) /
+                               (4.0 * cos_o * cos_i);
+                let diffuse = self.diffuse * (color::WHITE - fresnel) * ((1.0 - metallic) / PI);
+                return fresnel * specular + diffuse;
+            }
+            Bsdf::Dielectric { roughness, ior } => {
+                if cos_i == 0.0 || cos_o <= 0.0 {
+                    return color::BLACK;
+                }
+                let alpha = alpha(roughness);
+                let eta = relative_ior(normal, wo, ior);
+                let g = microfacet::smith_g(alpha, n, wo, wi);
+                if cos_i > 0.0 {
+                    let h = (wo + wi).normalize();
+                    let fresnel = microfacet::fresnel_dielectric(wo.dot(h), eta);
+                    let d = microfacet::distribution(alpha, n, h);
+                    return color::WHITE * (fresnel * d * g / (4.0 * cos_o * cos_i));
+                }
+                let h = match transmission_half_vector(n, wo, wi, eta) {
+                    Some(h) => h,
+                    None => return color::BLACK,
+                };
+                let (o_h, i_h) = (wo.dot(h), wi.dot(h));
+                let fresnel = microfacet::fresnel_dielectric(o_h, eta);
+                let denominator = o_h + eta * i_h;
+                // Radiance is compressed by eta^2 on refraction, which cancels
+                // the eta^2 in the Jacobian of the half vector.
+                let f = (1.0 - fresnel) * microfacet::distribution(alpha, n, h) * g * (i_h * o_h).abs() /
+                        (cos_i.abs() * cos_o * denominator * denominator);
+                return self.diffuse * f;
+            }
+        }
+    }
+
+    /// The pdf with which `sample` picks `wi`.
+    pub fn pdf(&self, wo: Vector3, wi: Vector3, normal: Vector3) -> f64 {
+        let n = face(normal, wo);
+        let cos_i = n.dot(wi);
+        match self.bsdf {
+            Bsdf::Lambert => return (cos_i / PI).max(0.0),
+            Bsdf::Microfacet { roughness, metallic } => {
+                if cos_i <= 0.0 {
+                    return 0.0;
+                }
+                let h = (wo + wi).normalize();
+                let specular = microfacet::normal_pdf(alpha(roughness), n, h) / (4.0 * wo.dot(h).abs());
+                let p = specular_probability(metallic);
+                return p * specular + (1.0 - p) * cos_i / PI;
+            }
+            Bsdf::Dielectric { roughness, ior } => {
+                let alpha = alpha(roughness);
+                let eta = relative_ior(normal, wo, ior);
+                if cos_i > 0.0 {
+                    let h = (wo + wi).normalize();
+                    let fresnel = microfacet::fresnel_dielectric(wo.dot(h), eta);
+                    return fresnel * microfacet::normal_pdf(alpha, n, h) / (4.0 * wo.dot(h).abs());
+                }
+                let h = match transmission_half_vector(n, wo, wi, eta) {
+                    Some(h) => h,
+                    None => return 0.0,
+                };
+                let fresnel = microfacet::fresnel_dielectric(wo.dot(h), eta);
+                let denominator = wo.dot(h) + eta * wi.dot(h);
+                let jacobian = (eta * eta * wi.dot(h)).abs() / (denominator * denominator);
+                return (1.0 - fresnel) * microfacet::normal_pdf(alpha, n, h) * jacobian;
+            }
+        }
+    }
+
+    /// Picks an incoming direction in proportion to the cosine-weighted BSDF,
+    /// as near as can be.
+    pub fn sample(&self, wo: Vector3, normal: Vector3, rng: &mut Rng) -> Option<BsdfSample> {
+        let n = face(normal, wo);
+        let (u, v) = (rng.next_f64(), rng.next_f64());
+        let (direction, specular) = match self.bsdf {
+            Bsdf::Lambert => (sampling::cosine_hemisphere(n, u, v), false),
+            Bsdf::Microfacet { roughness, metallic } => {
+                if rng.next_f64() < specular_probability(metallic) {
+                    let h = microfacet::sample_normal(alpha(roughness), n, u, v);
+                    (microfacet::reflect(wo, h), roughness <= SPECULAR_ROUGHNESS)
+                } else {
+                    (sampling::cosine_hemisphere(n, u, v), false)
+                }
+            }
+            Bsdf::Dielectric { roughness, ior } => {
+                let h = microfacet::sample_normal(alpha(roughness), n, u, v);
+                if wo.dot(h) <= 0.0 {
+                    return None;
+                }
+                let eta = relative_ior(normal, wo, ior);
+                let fresnel = microfacet::fresnel_dielectric(wo.dot(h), eta);
+                let (direction, transmitted) = match microfacet::refract(wo, h, eta) {
+                    Some(refracted) if rng.next_f64() >= fresnel => (refracted, true),
+                    _ => (microfacet::reflect(wo, h), false),
+                };
+                // A steep microfacet can send light out on the wrong side.
+                if (direction.dot(n) < 0.0) != transmitted {
+                    return None;
+                }
+                (direction, roughness <= SPECULAR_ROUGHNESS)
+            }
+        };
+        let pdf = self.pdf(wo, direction, normal);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let cosine = direction.dot(n).abs();
+        return Some(BsdfSample {
+            direction,
+            weight: self.eval(wo, direction, normal) * (cosine / pdf),
+            pdf,
+            specular,
+        });
+    }
+
+    fn specular_color(&self, metallic: f64) -> Color {
+        return color::WHITE * (0.04 * (1.0 - metallic)) + self.diffuse * metallic;
+    }
+}
+
+fn face(normal: Vector3, v: Vector3) -> Vector3 {
+    if normal.dot(v) < 0.0 {
+        return -normal;
+    } else {
+        return normal;
+    }
+}
+
+// Chance of sampling the specular lobe rather than the diffuse one.
+fn specular_probability(metallic: f64) -> f64 {
+    return 0.5 * (1.0 + metallic);
+}
+
+/// Index on the far side of the surface from `wo` over that on its side.
+pub fn relative_ior(normal: Vector3, wo: Vector3, ior: f64) -> f64 {
+    if normal.dot(wo) >= 0.0 {
+        return ior;
+    } else {
+        return 1.0 / ior;
+    }
+}
+
+// The microfacet normal that refracts `wi` into `wo`, on the side of `n`.
+fn transmission_half_vector(n: Vector3, wo: Vector3, wi: Vector3, eta: f64) -> Option<Vector3> {
+    let h = (wo + wi * eta).normalize();
+    let h = if h.dot(n) < 0.0 { -h } else { h };
+    // Both directions must be on the correct sides of the microfacet.
+    if wo.dot(h) <= 0.0 || wi.dot(h) >= 0.0 {
+        return None;
+    }
+    return Some(h);
+}
+
+// Integrates f |cos| over the sphere both by importance sampling and by
+// uniform sampling, which agree only if `sample`, `eval` and `pdf` do.
+#[test]
+fn test_bsdf_sampling() {
+    let normal = Vector3::new(0.0, 0.0, 1.0);
+    let materials = [Material::diffuse(Color::new(0.5, 0.5, 0.5)),
+                     Material::microfacet(Color::new(0.8, 0.3, 0.2), 0.5, 0.0),
+                     Material::microfacet(Color::new(0.9, 0.6, 0.3), 0.3, 1.0),
+                     Material::dielectric(color::WHITE, 0.5, 1.5)];
+    let directions = [Vector3::new(0.0, 0.0, 1.0),
+                      Vector3::new(0.6, 0.0, 0.8),
+                      Vector3::new(0.0, 0.8, -0.6)];
+    let n = 200000;
+    for material in materials.iter() {
+        for &wo in directions.iter() {
+            if material.bsdf == Bsdf::Lambert && wo.z < 0.0 {
+                continue;
+            }
+            let mut rng = Rng::new(3);
+            let mut sampled = 0.0;
+            for _ in 0..n {
+                if let Some(s) = material.sample(wo, normal, &mut rng) {
+                    sampled += s.weight.red;
+                    let f = material.eval(wo, s.direction, normal).red;
+                    let pdf = material.pdf(wo, s.direction, normal);
+                    assert!((s.pdf - pdf).abs() <= 1.0e-9 * pdf && f >= 0.0);
+                }
+            }
+            let mut uniform = 0.0;
+            let mut total_pdf = 0.0;
+            for _ in 0..n {
+                let wi = sampling::uniform_cone(normal, -1.0, rng.next_f64(), rng.next_f64());
+                let f = material.eval(wo, wi, normal).red;
+                uniform += f * wi.dot(normal).abs() * 4.0 * PI;
+                total_pdf += material.pdf(wo, wi, normal) * 4.0 * PI;
+            }
+            let (sampled, uniform, total_pdf) = (sampled / n as f64, uniform / n as f64, total_pdf / n as f64);
+            assert!((sampled - uniform).abs() < 0.02, "{:?} {:?}: {} {}", material.bsdf, wo, sampled, uniform);
+            // Light leaving glass is concentrated into a narrower cone and
+            // so gains radiance; anything else must not create energy.
+            assert!(total_pdf <= 1.02 && (sampled <= 1.01 || wo.z < 0.0));
+        }
+    }
+}
+
+#[test]
+fn test_smooth_metal() {
+    // A nearly smooth metal reflects its own colour in the mirror direction.
+    let gold = Material::microfacet(Color::new(1.0, 0.8, 0.3), 0.0, 1.0);
+    let normal = Vector3::new(0.0, 0.0, 1.0);
+    let wo = Vector3::new(0.6, 0.0, 0.8);
+    let mut rng = Rng::new(1);
+    let mut mirrored = 0;
+    for _ in 0..1000 {
+        if let Some(s) = gold.sample(wo, normal, &mut rng) {
+            // GGX has long tails, even when very smooth.
+            if (s.direction - Vector3::new(-0.6, 0.0, 0.8)).len() < 0.01 {
+                mirrored += 1;
+                assert!((s.weight.green - 0.8).abs() < 0.01);
+            }
+        }
+    }
+    assert!(mirrored > 900);
+}
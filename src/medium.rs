@@ -0,0 +1,222 @@
+use std::f64::consts::PI;
+use std::rc::Rc;
+use vec3::Vector3;
+use color::Color;
+use Ray;
+
+/// Smoke, fog or anything else that absorbs and scatters light as it
+/// passes through.
+#[derive(Clone)]
+pub struct Medium {
+    /// The chance per unit distance of light being absorbed or scattered,
+    /// at density 1.
+    pub extinction: f64,
+    /// The fraction of the extinction that is scattering rather than
+    /// absorption, for each channel.
+    pub albedo: Color,
+    /// Henyey-Greenstein asymmetry, from -1 for scattering straight back
+    /// through 0 for scattering equally in all directions to 1 for
+    /// scattering straight on.
+    pub asymmetry: f64,
+    pub density: Density,
+    /// Points along each ray at which scattered light is gathered.
+    pub samples: u32,
+}
+
+/// How the density of a medium varies through space.
+#[derive(Clone)]
+pub enum Density {
+    /// Density 1 everywhere.
+    Uniform,
+    Grid(Rc<DensityGrid>),
+}
+
+impl Medium {
+    /// A medium of density 1 everywhere.
+    pub fn homogeneous(extinction: f64, albedo: Color) -> Medium {
+        Medium {
+            extinction,
+            albedo,
+            asymmetry: 0.0,
+            density: Density::Uniform,
+            samples: 8,
+        }
+    }
+
+    pub fn with_density(self, grid: DensityGrid) -> Medium {
+        Medium {
+            density: Density::Grid(Rc::new(grid)),
+            ..self
+        }
+    }
+
+    pub fn density(&self, point: Vector3) -> f64 {
+        match self.density {
+            Density::Uniform => return 1.0,
+            Density::Grid(ref grid) => return grid.density(point),
+        }
+    }
+
+    /// The fraction of light scattered towards the direction `cosine` away
+    /// from the direction it was travelling in, per steradian.
+    pub fn phase(&self, cosine: f64) -> f64 {
+        let g = self.asymmetry;
+        let denominator = 1.0 + g * g - 2.0 * g * cosine;
+        return (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt());
+    }
+}
+
+/// Densities on a regular grid filling an axis aligned box, interpolated
+/// between grid points and zero outside the box.
+pub struct DensityGrid {
+    /// Points along x, y and z; at least two each.
+    size: (usize, usize, usize),
+    min: Vector3,
+    max: Vector3,
+    /// Ordered by x, then y, then z.
+    values: Vec<f64>,
+}
+
+// The number of points in a grid of `size`, which needs at least two along
+// each axis to interpolate between.
+fn point_count(size: (usize, usize, usize)) -> Result<usize, &'static str> {
+    let (nx, ny, nz) = size;
+    if nx < 2 || ny < 2 || nz < 2 {
+        return Err("density grids need at least two points along each axis");
+    }
+    return nx.checked_mul(ny).and_then(|n| n.checked_mul(nz)).ok_or("density grid too big");
+}
+
+impl DensityGrid {
+    /// `values` ordered by x, then y, then z, one for each grid point.
+    pub fn new(size: (usize, usize, usize),
+               min: Vector3,
+               max: Vector3,
+               values: Vec<f64>)
+               -> Result<DensityGrid, &'static str> {
+        if values.len() != point_count(size)? {
+            return Err("density grid has the wrong number of values");
+        }
+        return Ok(DensityGrid { size, min, max, values });
+    }
+
+    /// Samples `density` at each grid point.
+    pub fn from_fn<F: Fn(Vector3) -> f64>(size: (usize, usize, usize),
+                                          min: Vector3,
+                                          max: Vector3,
+                                          density: F)
+                                          -> Result<DensityGrid, &'static str> {
+        let (nx, ny, nz) = size;
+        let mut values = Vec::with_capacity(point_count(size)?);
+        let step = |i: usize, n: usize| i as f64 / (n - 1) as f64;
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    let p = Vector3::new(min.x + (max.x - min.x) * step(x, nx),
+                                         min.y + (max.y - min.y) * step(y, ny),
+                                         min.z + (max.z - min.z) * step(z, nz));
+                    values.push(density(p));
+                }
+            }
+        }
+        return DensityGrid::new(size, min, max, values);
+    }
+
+    fn value(&self, x: usize, y: usize, z: usize) -> f64 {
+        let (nx, ny, _) = self.size;
+        return self.values[(z * ny + y) * nx + x];
+    }
+
+    /// Trilinear interpolation between the grid points around `point`.
+    pub fn density(&self, point: Vector3) -> f64 {
+        let (nx, ny, nz) = self.size;
+        // Position in grid cells, and the cell and offset within it.
+        let cell = |p: f64, min: f64, max: f64, n: usize| -> Option<(usize, f64)> {
+            let x = (p - min) / (max - min) * (n - 1) as f64;
+            if !(0.0..=(n - 1) as f64).contains(&x) {
+                return None;
+            }
+            let i = (x.floor() as usize).min(n - 2);
+            return Some((i, x - i as f64));
+        };
+        let (x, fx) = match cell(point.x, self.min.x, self.max.x, nx) {
+            Some(c) => c,
+            None => return 0.0,
+        };
+        let (y, fy) = match cell(point.y, self.min.y, self.max.y, ny) {
+            Some(c) => c,
+            None => return 0.0,
+        };
+        let (z, fz) = match cell(point.z, self.min.z, self.max.z, nz) {
+            Some(c) => c,
+            None => return 0.0,
+        };
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let plane = |z: usize| {
+            lerp(lerp(self.value(x, y, z), self.value(x + 1, y, z), fx),
+                 lerp(self.value(x, y + 1, z), self.value(x + 1, y + 1, z), fx),
+                 fy)
+        };
+        return lerp(plane(z), plane(z + 1), fz);
+    }
+
+    /// The part of `ray`'s interval that is inside the box, if any.
+    pub fn clip(&self, ray: Ray) -> Option<(f64, f64)> {
+        let (dir, origin, inv) = (ray.direction, ray.origin, ray.inv_direction());
+        let (mut near, mut far) = (ray.t_min, ray.t_max);
+        for &(d, inv_d, o, min, max) in [(dir.x, inv.x, origin.x, self.min.x, self.max.x),
+                                         (dir.y, inv.y, origin.y, self.min.y, self.max.y),
+                                         (dir.z, inv.z, origin.z, self.min.z, self.max.z)]
+            .iter() {
+            if d == 0.0 {
+                if o < min || o > max {
+                    return None;
+                }
+                continue;
+            }
+            let (a, b) = ((min - o) * inv_d, (max - o) * inv_d);
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        if near > far {
+            return None;
+        }
+        return Some((near, far));
+    }
+}
+
+#[test]
+fn test_density_grid() {
+    let grid = DensityGrid::from_fn((3, 2, 2),
+                                    Vector3::new(0.0, 0.0, 0.0),
+                                    Vector3::new(2.0, 1.0, 1.0),
+                                    |p| p.x + 2.0 * p.y + 4.0 * p.z)
+        .unwrap();
+    // Linear functions are interpolated exactly.
+    let p = Vector3::new(1.3, 0.25, 0.5);
+    assert!((grid.density(p) - (1.3 + 0.5 + 2.0)).abs() < 1.0e-12);
+    assert_eq!(grid.density(Vector3::new(2.0, 1.0, 1.0)), 8.0);
+    assert_eq!(grid.density(Vector3::new(2.1, 0.5, 0.5)), 0.0);
+    // Grids need two points along each axis, and a value for each point.
+    let (min, max) = (Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 1.0));
+    assert!(DensityGrid::from_fn((2, 1, 2), min, max, |_| 1.0).is_err());
+    assert!(DensityGrid::new((2, 2, 0), min, max, vec![]).is_err());
+    assert!(DensityGrid::new((2, 2, 2), min, max, vec![1.0; 7]).is_err());
+    assert!(DensityGrid::new((2, 2, 2), min, max, vec![1.0; 8]).is_ok());
+    assert!(DensityGrid::new((usize::MAX, 2, 2), min, max, vec![]).is_err());
+
+    let ray = Ray::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(-1.0, 0.5, 0.5));
+    assert_eq!(grid.clip(ray), Some((1.0, 3.0)));
+    assert_eq!(grid.clip(ray.with_interval(1.5, 2.0)), Some((1.5, 2.0)));
+    assert_eq!(grid.clip(ray.with_t_max(0.5)), None);
+    assert_eq!(grid.clip(Ray::new(ray.direction, Vector3::new(0.0, 2.0, 0.5))), None);
+
+    // The phase function integrates to one over the sphere.
+    for &g in [0.0, 0.5, -0.8].iter() {
+        let medium = Medium { asymmetry: g, ..Medium::homogeneous(1.0, Color::new(1.0, 1.0, 1.0)) };
+        let n = 100000;
+        let total: f64 = (0..n).map(|i| medium.phase(-1.0 + 2.0 * (i as f64 + 0.5) / n as f64)).sum::<f64>() *
+                         2.0 / n as f64 * 2.0 * PI;
+        assert!((total - 1.0).abs() < 1.0e-3);
+    }
+}
@@ -0,0 +1,90 @@
+// The GGX (Trowbridge-Reitz) microfacet distribution with Smith
+// shadowing-masking, and Fresnel terms, for `material`.
+//
+// Directions are unit vectors pointing away from the surface and `n` is the
+// macroscopic surface normal. `alpha` is the GGX width, the square of the
+// artist facing roughness.
+
+use std::f64::consts::PI;
+use vec3::Vector3;
+use color::Color;
+use sampling;
+
+/// The density of microfacets with normal `h`, per unit of projected area.
+pub fn distribution(alpha: f64, n: Vector3, h: Vector3) -> f64 {
+    let cosine = n.dot(h);
+    if cosine <= 0.0 {
+        return 0.0;
+    }
+    let a2 = alpha * alpha;
+    let d = cosine * cosine * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+/// The fraction of microfacets visible from `v`.
+pub fn smith_g1(alpha: f64, n: Vector3, v: Vector3) -> f64 {
+    let cosine = n.dot(v).abs();
+    let a2 = alpha * alpha;
+    return 2.0 * cosine / (cosine + (a2 + (1.0 - a2) * cosine * cosine).sqrt());
+}
+
+/// Separable Smith shadowing-masking for a pair of directions.
+pub fn smith_g(alpha: f64, n: Vector3, wo: Vector3, wi: Vector3) -> f64 {
+    return smith_g1(alpha, n, wo) * smith_g1(alpha, n, wi);
+}
+
+/// A microfacet normal picked with pdf `normal_pdf`.
+pub fn sample_normal(alpha: f64, n: Vector3, u: f64, v: f64) -> Vector3 {
+    let a2 = alpha * alpha;
+    let cos_theta = ((1.0 - u) / (1.0 + (a2 - 1.0) * u)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    let (t, b) = sampling::orthonormal_basis(n);
+    return t * (sin_theta * phi.cos()) + b * (sin_theta * phi.sin()) + n * cos_theta;
+}
+
+/// Pdf per steradian of `sample_normal` picking `h`: `D(h) cos(theta_h)`.
+pub fn normal_pdf(alpha: f64, n: Vector3, h: Vector3) -> f64 {
+    return distribution(alpha, n, h) * n.dot(h).max(0.0);
+}
+
+/// Schlick's approximation to the Fresnel reflectance, given the
+/// reflectance `f0` at normal incidence.
+pub fn schlick(f0: Color, cosine: f64) -> Color {
+    let w = (1.0 - cosine.clamp(0.0, 1.0)).powi(5);
+    return Color::new(f0.red + (1.0 - f0.red) * w,
+                      f0.green + (1.0 - f0.green) * w,
+                      f0.blue + (1.0 - f0.blue) * w);
+}
+
+/// Exact Fresnel reflectance of unpolarised light at a dielectric boundary,
+/// where `eta` is the ratio of the refractive index on the far side to
+/// that on the incident side. Total internal reflection gives 1.
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    return (rs * rs + rp * rp) / 2.0;
+}
+
+/// Mirror reflection of `v` about `h`.
+pub fn reflect(v: Vector3, h: Vector3) -> Vector3 {
+    return h * (2.0 * v.dot(h)) - v;
+}
+
+/// Refraction of `v` through a boundary with normal `h` on the same side
+/// as `v`, or `None` on total internal reflection.
+pub fn refract(v: Vector3, h: Vector3, eta: f64) -> Option<Vector3> {
+    let cos_i = v.dot(h);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    return Some(-v / eta + h * (cos_i / eta - cos_t));
+}
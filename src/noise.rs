@@ -0,0 +1,260 @@
+use vec3::Vector3;
+use sampling::Rng;
+
+/// Which gradient noise `Noise` is built from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Basis {
+    /// Perlin's improved noise, interpolated over a cubic lattice.
+    Perlin,
+    /// Perlin's simplex noise, summed over the corners of a tetrahedron.
+    /// Cheaper and with fewer axis aligned artefacts.
+    Simplex,
+}
+
+/// Smooth pseudo random gradient noise in three dimensions, between about
+/// -1 and 1, and its fractal sums.
+///
+/// The lattice is shuffled from `seed`, so the same seed always gives the
+/// same noise.
+#[derive(Debug, Clone)]
+pub struct Noise {
+    /// The permutation of 0 to 255, twice over to save wrapping indices.
+    permutation: Vec<u8>,
+    pub basis: Basis,
+}
+
+// Perlin's smootherstep, with zero first and second derivatives at 0 and 1.
+fn fade(t: f64) -> f64 {
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    return a + t * (b - a);
+}
+
+// The dot product of the offset with one of twelve gradients towards the
+// edges of a cube, picked by the hash.
+fn gradient(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    return if h & 1 == 0 { u } else { -u } + if h & 2 == 0 { v } else { -v };
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Noise {
+        let mut rng = Rng::new(seed);
+        let mut permutation: Vec<u8> = (0..=255).collect();
+        // Fisher-Yates shuffle.
+        for i in (1..256).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            permutation.swap(i, j);
+        }
+        let repeated = permutation.clone();
+        permutation.extend(repeated);
+        return Noise {
+            permutation,
+            basis: Basis::Perlin,
+        };
+    }
+
+    pub fn with_basis(self, basis: Basis) -> Noise {
+        Noise { basis, ..self }
+    }
+
+    fn hash(&self, x: i64, y: i64, z: i64) -> u8 {
+        let p = &self.permutation;
+        let index = |i: i64| (i & 255) as usize;
+        return p[p[p[index(x)] as usize + index(y)] as usize + index(z)];
+    }
+
+    /// Noise from the chosen basis.
+    pub fn noise(&self, p: Vector3) -> f64 {
+        match self.basis {
+            Basis::Perlin => return self.perlin(p),
+            Basis::Simplex => return self.simplex(p),
+        }
+    }
+
+    /// Perlin's improved noise, zero at every integer point.
+    pub fn perlin(&self, p: Vector3) -> f64 {
+        let (x0, y0, z0) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (ix, iy, iz) = (x0 as i64, y0 as i64, z0 as i64);
+        let (x, y, z) = (p.x - x0, p.y - y0, p.z - z0);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        let corner = |dx: i64, dy: i64, dz: i64| {
+            gradient(self.hash(ix + dx, iy + dy, iz + dz),
+                     x - dx as f64,
+                     y - dy as f64,
+                     z - dz as f64)
+        };
+        return lerp(w,
+                    lerp(v,
+                         lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                         lerp(u, corner(0, 1, 0), corner(1, 1, 0))),
+                    lerp(v,
+                         lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                         lerp(u, corner(0, 1, 1), corner(1, 1, 1))));
+    }
+
+    /// Simplex noise, after Gustavson's description of Perlin's design.
+    pub fn simplex(&self, p: Vector3) -> f64 {
+        const SKEW: f64 = 1.0 / 3.0;
+        const UNSKEW: f64 = 1.0 / 6.0;
+        // The cell of the skewed lattice, which is six tetrahedra.
+        let s = (p.x + p.y + p.z) * SKEW;
+        let (i, j, k) = ((p.x + s).floor(), (p.y + s).floor(), (p.z + s).floor());
+        let t = (i + j + k) * UNSKEW;
+        let first = Vector3::new(p.x - (i - t), p.y - (j - t), p.z - (k - t));
+
+        // The tetrahedron holding the point, as the two middle corners'
+        // offsets from the first.
+        let (second, third) = if first.x >= first.y {
+            if first.y >= first.z {
+                ((1, 0, 0), (1, 1, 0))
+            } else if first.x >= first.z {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if first.y < first.z {
+            ((0, 0, 1), (0, 1, 1))
+        } else if first.x < first.z {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
+
+        let (i, j, k) = (i as i64, j as i64, k as i64);
+        let corners = [(0, 0, 0), second, third, (1, 1, 1)];
+        let mut sum = 0.0;
+        for (n, &(di, dj, dk)) in corners.iter().enumerate() {
+            let offset = n as f64 * UNSKEW;
+            let x = first.x - di as f64 + offset;
+            let y = first.y - dj as f64 + offset;
+            let z = first.z - dk as f64 + offset;
+            let falloff = 0.6 - x * x - y * y - z * z;
+            if falloff > 0.0 {
+                let falloff = falloff * falloff;
+                sum += falloff * falloff * gradient(self.hash(i + di, j + dj, k + dk), x, y, z);
+            }
+        }
+        // Scales the result to about -1 to 1.
+        return 32.0 * sum;
+    }
+
+    /// Fractional Brownian motion: `octaves` layers of noise, each at twice
+    /// the frequency and half the amplitude of the last, normalised to about
+    /// -1 to 1.
+    pub fn fbm(&self, p: Vector3, octaves: u32) -> f64 {
+        return self.octaves(p, octaves, |n| n);
+    }
+
+    /// As `fbm` but summing the absolute value of each layer, which gives
+    /// creases where the noise crosses zero. Between 0 and about 1.
+    pub fn turbulence(&self, p: Vector3, octaves: u32) -> f64 {
+        return self.octaves(p, octaves, f64::abs);
+    }
+
+    fn octaves<F: Fn(f64) -> f64>(&self, p: Vector3, octaves: u32, layer: F) -> f64 {
+        let (mut sum, mut total, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, 1.0);
+        for _ in 0..octaves.max(1) {
+            sum += amplitude * layer(self.noise(p * frequency));
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        return sum / total;
+    }
+}
+
+/// Worley's cellular noise: the distances from a point to the nearest
+/// and second nearest of a scattering of feature points, one in each unit
+/// cube.
+#[derive(Debug, Clone, Copy)]
+pub struct Worley {
+    pub seed: u64,
+}
+
+impl Worley {
+    fn feature_point(&self, x: i64, y: i64, z: i64) -> Vector3 {
+        let cell = (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^
+                   (y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f) ^
+                   (z as u64).wrapping_mul(0x1656_67b1_9e37_79f9);
+        let mut rng = Rng::new(cell ^ self.seed);
+        return Vector3::new(x as f64 + rng.next_f64(),
+                            y as f64 + rng.next_f64(),
+                            z as f64 + rng.next_f64());
+    }
+
+    /// The distances to the nearest two feature points.
+    pub fn distances(&self, p: Vector3) -> (f64, f64) {
+        let (x, y, z) = (p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64);
+        let (mut nearest, mut second) = (1.0 / 0.0, 1.0 / 0.0);
+        // Only the neighbouring cells are searched. The nearest two points
+        // are almost always among them; when one is further out, the
+        // distances come out slightly too large.
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let distance = (self.feature_point(x + dx, y + dy, z + dz) - p).len();
+                    if distance < nearest {
+                        second = nearest;
+                        nearest = distance;
+                    } else if distance < second {
+                        second = distance;
+                    }
+                }
+            }
+        }
+        return (nearest, second);
+    }
+}
+
+#[test]
+fn test_noise() {
+    let noise = Noise::new(1);
+    let p = Vector3::new(1.3, -4.7, 2.1);
+    assert_eq!(noise.perlin(p), Noise::new(1).perlin(p));
+    assert!(noise.perlin(p) != Noise::new(2).perlin(p));
+    assert_eq!(noise.perlin(Vector3::new(3.0, -2.0, 7.0)), 0.0);
+
+    let simplex = Noise::new(1).with_basis(Basis::Simplex);
+    let mut rng = Rng::new(4);
+    let (mut low, mut high) = (0.0f64, 0.0f64);
+    for _ in 0..10000 {
+        let p = Vector3::new(rng.next_f64(), rng.next_f64(), rng.next_f64()) * 20.0;
+        for &n in [noise.perlin(p), simplex.simplex(p), noise.fbm(p, 5)].iter() {
+            assert!(n.abs() <= 1.1);
+            low = low.min(n);
+            high = high.max(n);
+        }
+        let t = noise.turbulence(p, 5);
+        assert!((0.0..=1.1).contains(&t));
+        // Continuous: nearby points have nearby values.
+        let q = p + Vector3::new(1.0e-6, 0.0, 0.0);
+        assert!((simplex.simplex(p) - simplex.simplex(q)).abs() < 1.0e-4);
+        assert!((noise.perlin(p) - noise.perlin(q)).abs() < 1.0e-4);
+    }
+    assert!(low < -0.5 && high > 0.5);
+}
+
+#[test]
+fn test_worley() {
+    let worley = Worley { seed: 9 };
+    let mut rng = Rng::new(2);
+    for _ in 0..1000 {
+        let p = Vector3::new(rng.next_f64(), rng.next_f64(), rng.next_f64()) * 10.0 - Vector3::new(5.0, 5.0, 5.0);
+        let (f1, f2) = worley.distances(p);
+        assert!(f1 <= f2 && f1 < 3.0f64.sqrt());
+        assert_eq!(worley.distances(p), Worley { seed: 9 }.distances(p));
+    }
+    let feature = worley.feature_point(2, -3, 0);
+    assert_eq!(worley.distances(feature).0, 0.0);
+}
@@ -0,0 +1,218 @@
+use vec3::Vector3;
+use color;
+use color::Color;
+use material::{self, Material, Bsdf};
+use sampling;
+use sampling::Rng;
+use trace::{World, SHADOW_EPSILON, material_of, emission_at};
+use shape::Light;
+use spectrum;
+use Ray;
+
+/// Unbiased Monte Carlo path tracing.
+///
+/// Paths bounce off surfaces by importance sampling their materials. At
+/// every bounce the lights and emissive shapes are also sampled directly
+/// (next event estimation), and the two ways of finding an emissive shape
+/// are combined with multiple importance sampling. Paths end when they
+/// leave the scene, at `max_depth` bounces, or by Russian roulette.
+///
+/// In spectral mode each path carries light of a single wavelength, so
+/// glass with `Material::dispersion` splits white light into colours.
+#[derive(Debug, Clone, Copy)]
+pub struct PathTracer {
+    /// Paths traced per camera ray.
+    pub samples: u32,
+    pub max_depth: u32,
+    /// Bounces before Russian roulette may end a path.
+    pub roulette_depth: u32,
+    pub spectral: bool,
+}
+
+impl PathTracer {
+    pub fn new(samples: u32) -> PathTracer {
+        PathTracer {
+            samples,
+            max_depth: 64,
+            roulette_depth: 3,
+            spectral: false,
+        }
+    }
+
+    pub fn with_spectral(self, spectral: bool) -> PathTracer {
+        PathTracer { spectral, ..self }
+    }
+
+    /// Average radiance along `ray` over `samples` paths.
+    pub fn trace(&self, world: &World, ray: Ray) -> Color {
+        let mut rng = Rng::for_ray(ray);
+        let mut total = color::BLACK;
+        for i in 0..self.samples {
+            if !self.spectral {
+                total = total + self.trace_path(world, ray, None, &mut rng);
+                continue;
+            }
+            // One wavelength in each of `samples` equal parts of the range.
+            let wavelength = spectrum::sample_wavelength((i as f64 + rng.next_f64()) / self.samples as f64);
+            let radiance = self.trace_path(world, ray, Some(wavelength), &mut rng);
+            total = total + spectrum::to_rgb(radiance.red, wavelength);
+        }
+        return total * (1.0 / self.samples as f64);
+    }
+
+    // Radiance along `ray`; at `wavelength`, if given, as a grey.
+    fn trace_path(&self, world: &World, mut ray: Ray, wavelength: Option<f64>, rng: &mut Rng) -> Color {
+        let mut radiance = color::BLACK;
+        let mut throughput = color::WHITE;
+        // Pdf of the material sample that produced `ray`; `None` for camera rays.
+        let mut bsdf_pdf: Option<f64> = None;
+
+        for depth in 0..self.max_depth {
+            let (dir, origin) = (ray.direction, ray.origin);
+            let (shape, hit) = match world.nearest_hit(ray) {
+                Some(hit) => hit,
+                None => {
+                    let background = spectrum::at_wavelength(escaped(world, origin, dir, bsdf_pdf), wavelength);
+                    radiance = radiance + throughput * background;
+                    break;
+                }
+            };
+            let (t, normal) = (hit.t, hit.normal);
+            let point = origin + dir * t;
+            let differentials = hit.differentials(shape, ray);
+            let footprint = differentials.map_or(0.0, |d| d.footprint());
+            let material = material_of(shape, point, hit.uv, footprint, ray.time);
+            let material = wavelength.map_or(material, |w| material.at_wavelength(w));
+
+            if material.is_emissive() && dir.dot(normal) < 0.0 {
+                let weight = match bsdf_pdf {
+                    None => 1.0,
+                    Some(pdf) => {
+                        let light_pdf = shape.surface_pdf(point, ray.time) * t * t / -dir.dot(normal);
+                        sampling::power_heuristic(pdf, light_pdf)
+                    }
+                };
+                radiance = radiance + throughput * material.emission * weight;
+            }
+
+            let wo = -dir;
+            let shading_normal = hit.shading_normal;
+            // The material is already grey at the wavelength, so the light
+            // can be turned into one after being scattered by it.
+            let direct = direct_light(world, &material, point, ray.time, wo, shading_normal, rng);
+            radiance = radiance + throughput * spectrum::at_wavelength(direct, wavelength);
+
+            let sample = match material.sample(wo, shading_normal, rng) {
+                Some(s) => s,
+                None => break,
+            };
+            throughput = throughput * sample.weight;
+            bsdf_pdf = Some(sample.pdf);
+            // Differentials follow near-mirror reflections and refractions,
+            // taken as if the surface were smooth; diffuse and rough bounces
+            // spread too widely for them to mean anything.
+            ray = match differentials {
+                Some(ref d) if sample.specular => {
+                    let eta = match material.bsdf {
+                        Bsdf::Dielectric { ior, .. } => material::relative_ior(normal, wo, ior),
+                        _ => 1.0,
+                    };
+                    ray.scattered(point, shading_normal, d, sample.direction, eta)
+                }
+                _ => ray.spawn(sample.direction, point),
+            };
+
+            if depth + 1 >= self.roulette_depth {
+                let survival = throughput.max_channel().min(0.95);
+                if rng.next_f64() >= survival {
+                    break;
+                }
+                throughput = throughput * (1.0 / survival);
+            }
+        }
+        return radiance;
+    }
+}
+
+// Light from the environment for a path leaving the scene, weighted
+// against the chance of `direct_light` sampling the same direction.
+fn escaped(world: &World, origin: Vector3, dir: Vector3, bsdf_pdf: Option<f64>) -> Color {
+    let mut radiance = color::BLACK;
+    for light in world.lights.iter() {
+        let background = light.background(dir);
+        if background == color::BLACK {
+            continue;
+        }
+        let weight = match bsdf_pdf {
+            None => 1.0,
+            Some(pdf) => sampling::power_heuristic(pdf, light_pdf(&**light, origin, dir)),
+        };
+        radiance = radiance + background * weight;
+    }
+    return radiance;
+}
+
+// Lights that are never sampled cannot be found by `direct_light`.
+fn light_pdf(light: &dyn Light, point: Vector3, direction: Vector3) -> f64 {
+    if light.sample_count() == 0 {
+        return 0.0;
+    }
+    return light.pdf(point, direction);
+}
+
+// One sample of each light and emissive shape, weighted against the chance
+// of the material sampling finding the same emissive shape.
+fn direct_light(world: &World,
+                material: &Material,
+                point: Vector3,
+                time: f64,
+                wo: Vector3,
+                normal: Vector3,
+                rng: &mut Rng)
+                -> Color {
+    let mut radiance = color::BLACK;
+    for light in world.lights.iter() {
+        if light.sample_count() == 0 {
+            continue;
+        }
+        if let Some(sample) = light.illuminate(point, (rng.next_f64(), rng.next_f64())) {
+            let f = material.eval(wo, sample.direction, normal);
+            let shadow_ray = Ray::new(sample.direction, point).at_time(time).with_t_max(sample.distance);
+            if f != color::BLACK && world.trace_collision(shadow_ray) {
+                // Only lights with a background can also be found by material sampling.
+                let pdf = light.pdf(point, sample.direction);
+                let weight = if pdf > 0.0 {
+                    sampling::power_heuristic(pdf, material.pdf(wo, sample.direction, normal))
+                } else {
+                    1.0
+                };
+                radiance = radiance + f * sample.color * (sample.direction.dot(normal).abs() * weight);
+            }
+        }
+    }
+    for shape in world.shapes.iter() {
+        match shape.material() {
+            Some(m) if m.is_emissive() => {}
+            _ => continue,
+        }
+        let on_light = match shape.sample_surface((rng.next_f64(), rng.next_f64()), time) {
+            Some(s) => s,
+            None => continue,
+        };
+        let emission = emission_at(*shape, on_light.point, time);
+        let ray_to_light = on_light.point - point;
+        let distance = ray_to_light.len();
+        let wi = ray_to_light / distance;
+        let cos_light = -wi.dot(on_light.normal);
+        let f = material.eval(wo, wi, normal);
+        let shadow_ray = Ray::new(wi, point).at_time(time).with_t_max(distance * (1.0 - SHADOW_EPSILON));
+        if cos_light <= 0.0 || f == color::BLACK || !world.trace_collision(shadow_ray) {
+            continue;
+        }
+        let light_pdf = on_light.pdf * distance * distance / cos_light;
+        let weight = sampling::power_heuristic(light_pdf, material.pdf(wo, wi, normal));
+        let cosine = wi.dot(normal).abs();
+        radiance = radiance + f * emission * (cosine * weight / light_pdf);
+    }
+    return radiance;
+}
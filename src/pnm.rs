@@ -9,20 +9,20 @@ pub fn write_console<I>(pixels: I, width: i64)
     let mut string = String::from("|");
     for color in pixels {
         if color.red > 0.0 {
-            string = string + "X";
+            string += "X";
         } else {
-            string = string + " ";
+            string += " ";
         }
         count += 1;
         if count == width {
-            string = string + "|\n|";
+            string += "|\n|";
             count = 0;
         }
     }
     print!("{}", string);
 }
 
-pub fn write_pnm<I>(pixels: I, width: i64, height: i64, file: &mut Write)
+pub fn write_pnm<I>(pixels: I, width: i64, height: i64, file: &mut dyn Write)
     where I: Iterator<Item = Color>
 {
     let rgb = pixels.map(|p| p.to_rgb());
@@ -0,0 +1,486 @@
+/// A polynomial in one variable, `coefficients[i]` multiplying `x^i`.
+///
+/// Note this is the reverse of the closed form solvers in `cubic`,
+/// which take the highest power first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polynomial {
+    pub coefficients: Vec<f64>,
+}
+
+// Leading coefficients this small next to the terms they were summed from,
+// or next to the other terms where the roots can be, are treated as zero.
+const LEADING_EPSILON: f64 = 1.0e-10;
+// Remainders this small next to the dividend are rounding noise.
+const REMAINDER_EPSILON: f64 = 1.0e-12;
+
+const MAX_BISECTIONS: usize = 200;
+const MAX_NEWTON_STEPS: usize = 100;
+
+impl Polynomial {
+    pub fn new(mut coefficients: Vec<f64>) -> Polynomial {
+        while coefficients.len() > 1 && coefficients[coefficients.len() - 1] == 0.0 {
+            coefficients.pop();
+        }
+        if coefficients.is_empty() {
+            coefficients.push(0.0);
+        }
+        return Polynomial { coefficients };
+    }
+
+    pub fn from_highest_first(coefficients: &[f64]) -> Polynomial {
+        return Polynomial::new(coefficients.iter().rev().cloned().collect());
+    }
+
+    pub fn constant(c: f64) -> Polynomial {
+        return Polynomial::new(vec![c]);
+    }
+
+    /// `a + b x`, e.g. one coordinate of a point moving along a ray.
+    pub fn linear(a: f64, b: f64) -> Polynomial {
+        return Polynomial::new(vec![a, b]);
+    }
+
+    pub fn from_roots(roots: &[f64]) -> Polynomial {
+        return roots.iter().fold(Polynomial::constant(1.0), |p, &r| p * Polynomial::linear(-r, 1.0));
+    }
+
+    pub fn degree(&self) -> usize {
+        return self.coefficients.len() - 1;
+    }
+
+    pub fn is_zero(&self) -> bool {
+        return self.coefficients.iter().all(|&c| c == 0.0);
+    }
+
+    pub fn leading(&self) -> f64 {
+        return self.coefficients[self.degree()];
+    }
+
+    pub fn evaluate(&self, x: f64) -> f64 {
+        return self.coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c);
+    }
+
+    /// Evaluates the polynomial with every coefficient made positive, which
+    /// bounds the rounding error of `evaluate` at `x`.
+    pub fn evaluate_abs(&self, x: f64) -> f64 {
+        return self.coefficients.iter().rev().fold(0.0, |acc, c| acc * x.abs() + c.abs());
+    }
+
+    pub fn derivative(&self) -> Polynomial {
+        if self.degree() == 0 {
+            return Polynomial::constant(0.0);
+        }
+        return Polynomial::new(self.coefficients
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, c)| c * i as f64)
+            .collect());
+    }
+
+    pub fn pow(&self, n: u32) -> Polynomial {
+        return (0..n).fold(Polynomial::constant(1.0), |p, _| p * self.clone());
+    }
+
+    /// `p(a + b t)` as a polynomial in `t`.
+    pub fn compose_linear(&self, a: f64, b: f64) -> Polynomial {
+        let inner = Polynomial::linear(a, b);
+        return self.coefficients
+            .iter()
+            .rev()
+            .fold(Polynomial::constant(0.0),
+                  |acc, &c| acc * inner.clone() + Polynomial::constant(c));
+    }
+
+    /// Polynomial long division, returning the quotient and remainder.
+    pub fn div_rem(&self, divisor: &Polynomial) -> (Polynomial, Polynomial) {
+        assert!(!divisor.is_zero());
+        let d = divisor.degree();
+        let mut r = self.coefficients.clone();
+        if r.len() <= d {
+            return (Polynomial::constant(0.0), self.clone());
+        }
+        let mut q = vec![0.0; r.len() - d];
+        for i in (0..q.len()).rev() {
+            let factor = r[i + d] / divisor.leading();
+            q[i] = factor;
+            for (j, c) in divisor.coefficients.iter().enumerate() {
+                r[i + j] -= factor * c;
+            }
+        }
+        r.truncate(d.max(1));
+        return (Polynomial::new(q), Polynomial::new(r));
+    }
+
+    /// Divides out the factor `(x - root)` by synthetic division, dropping the remainder.
+    pub fn deflate(&self, root: f64) -> Polynomial {
+        if self.degree() == 0 {
+            return Polynomial::constant(0.0);
+        }
+        let n = self.degree();
+        let mut q = vec![0.0; n];
+        let mut carry = 0.0;
+        for i in (0..n).rev() {
+            carry = self.coefficients[i + 1] + carry * root;
+            q[i] = carry;
+        }
+        return Polynomial::new(q);
+    }
+
+    /// Drops leading coefficients that are negligible next to the same ones of
+    /// `magnitudes`, the sums of the sizes of the terms that were added up to
+    /// make them. Terms that cancel, as when a ray runs parallel to an
+    /// asymptote of a surface, are dropped, but small ones are kept however
+    /// large the rest of the polynomial is.
+    pub fn trimmed(&self, magnitudes: &Polynomial) -> Polynomial {
+        let mut c = self.coefficients.clone();
+        while let Some(&leading) = c.last() {
+            let magnitude = magnitudes.coefficients.get(c.len() - 1).cloned().unwrap_or(0.0);
+            if leading.abs() > magnitude * LEADING_EPSILON {
+                break;
+            }
+            c.pop();
+        }
+        return Polynomial::new(c);
+    }
+
+    fn without_leading_below(&self, threshold: f64) -> Polynomial {
+        let mut c = self.coefficients.clone();
+        while !c.is_empty() && c[c.len() - 1].abs() <= threshold {
+            c.pop();
+        }
+        return Polynomial::new(c);
+    }
+
+    /// Drops leading coefficients too small to matter anywhere the rest of
+    /// the polynomial can have roots. They only add roots out near
+    /// infinity, and would swamp the others in rounding error. Unlike a
+    /// cutoff relative to the largest coefficient, this keeps a small
+    /// leading term when the others are large because the roots are.
+    fn without_negligible_leading(&self) -> Polynomial {
+        let mut p = self.clone();
+        while p.degree() > 0 {
+            let n = p.degree();
+            let rest = Polynomial::new(p.coefficients[..n].to_vec());
+            if rest.is_zero() {
+                break;
+            }
+            // Negligible next to some other term wherever the rest has roots.
+            let r = rest.root_bound();
+            let leading = p.leading().abs();
+            let negligible = rest.coefficients
+                .iter()
+                .enumerate()
+                .any(|(i, c)| leading * r.powi((n - i) as i32) <= c.abs() * LEADING_EPSILON);
+            if !negligible {
+                break;
+            }
+            p = rest;
+        }
+        return p;
+    }
+
+    fn max_coefficient(&self) -> f64 {
+        return self.coefficients.iter().fold(0.0, |m: f64, c| m.max(c.abs()));
+    }
+
+    /// Every real root lies in `[-bound, bound]` (Cauchy's bound).
+    fn root_bound(&self) -> f64 {
+        let leading = self.leading();
+        return 1.0 +
+               self.coefficients[..self.degree()].iter().fold(0.0, |m: f64, c| m.max((c / leading).abs()));
+    }
+
+    /// The sequence `p, p', -rem(p, p'), ...` whose sign changes at a point
+    /// count the distinct real roots above it.
+    pub fn sturm_sequence(&self) -> Vec<Polynomial> {
+        let mut sequence = vec![self.clone()];
+        let first_derivative = self.derivative();
+        if first_derivative.is_zero() {
+            return sequence;
+        }
+        sequence.push(first_derivative);
+        loop {
+            let n = sequence.len();
+            let (_, r) = sequence[n - 2].div_rem(&sequence[n - 1]);
+            let r = r.without_leading_below(sequence[n - 2].max_coefficient() * REMAINDER_EPSILON);
+            if r.is_zero() {
+                return sequence;
+            }
+            sequence.push(r * -1.0);
+        }
+    }
+
+    /// Divides out repeated factors, leaving a polynomial with the same roots
+    /// but all of them simple, which are much better conditioned.
+    /// Returns it with its Sturm sequence.
+    pub fn square_free(&self) -> (Polynomial, Vec<Polynomial>) {
+        let sequence = self.sturm_sequence();
+        // The last polynomial in the sequence is the gcd of p and p'.
+        let gcd = &sequence[sequence.len() - 1];
+        if gcd.degree() == 0 {
+            return (self.clone(), sequence);
+        }
+        let (q, _) = self.div_rem(gcd);
+        let q_sequence = q.sturm_sequence();
+        return (q, q_sequence);
+    }
+
+    /// The distinct real roots in `(lo, hi]`, in increasing order.
+    pub fn real_roots(&self, lo: f64, hi: f64) -> Vec<f64> {
+        let mut roots = Vec::new();
+        if let Some((p, sequence, lo, hi)) = self.prepare_roots(lo, hi) {
+            let count = count_roots(&sequence, lo, hi);
+            isolate_roots(&p, &sequence, lo, hi, count, 0, &mut roots);
+        }
+        return roots;
+    }
+
+    /// The smallest real root in `(lo, hi]`, without isolating the others.
+    pub fn smallest_root(&self, lo: f64, hi: f64) -> Option<f64> {
+        let (p, sequence, mut lo, mut hi) = self.prepare_roots(lo, hi)?;
+        let mut count = count_roots(&sequence, lo, hi);
+        if count == 0 {
+            return None;
+        }
+        for _ in 0..MAX_BISECTIONS {
+            if count == 1 {
+                return Some(refine_root(&p, &sequence, lo, hi));
+            }
+            let mid = (lo + hi) / 2.0;
+            if mid <= lo || mid >= hi {
+                break;
+            }
+            let left = count_roots(&sequence, lo, mid);
+            if left > 0 {
+                hi = mid;
+                count = left;
+            } else {
+                lo = mid;
+            }
+        }
+        return Some((lo + hi) / 2.0);
+    }
+
+    /// The square free part and its Sturm sequence, with the interval clipped
+    /// to where roots can be.
+    fn prepare_roots(&self, lo: f64, hi: f64) -> Option<(Polynomial, Vec<Polynomial>, f64, f64)> {
+        let p = self.without_negligible_leading();
+        if p.degree() == 0 {
+            return None;
+        }
+        let bound = p.root_bound();
+        let lo = lo.max(-bound);
+        let hi = hi.min(bound);
+        if lo >= hi {
+            return None;
+        }
+        let (p, sequence) = p.square_free();
+        return Some((p, sequence, lo, hi));
+    }
+}
+
+fn sign_changes(sequence: &[Polynomial], x: f64) -> usize {
+    let mut changes = 0;
+    let mut last = 0.0;
+    for p in sequence.iter() {
+        let value = p.evaluate(x);
+        if value != 0.0 {
+            if last * value < 0.0 {
+                changes += 1;
+            }
+            last = value;
+        }
+    }
+    return changes;
+}
+
+/// Number of distinct real roots in `(lo, hi]`.
+pub fn count_roots(sequence: &[Polynomial], lo: f64, hi: f64) -> usize {
+    return sign_changes(sequence, lo).saturating_sub(sign_changes(sequence, hi));
+}
+
+/// Polishes a root known to be the only one in `(lo, hi]`.
+fn refine_root(p: &Polynomial, sequence: &[Polynomial], mut lo: f64, mut hi: f64) -> f64 {
+    let dp = p.derivative();
+    let tolerance = |x: f64| 1.0e-15 * (1.0 + x.abs());
+    let f_lo = p.evaluate(lo);
+    if f_lo * p.evaluate(hi) < 0.0 {
+        // Newton steps, falling back to bisection whenever a step would leave the bracket.
+        let mut x = (lo + hi) / 2.0;
+        for _ in 0..MAX_NEWTON_STEPS {
+            let fx = p.evaluate(x);
+            if fx == 0.0 {
+                return x;
+            }
+            if fx * f_lo > 0.0 {
+                lo = x;
+            } else {
+                hi = x;
+            }
+            let step = fx / dp.evaluate(x);
+            let next = x - step;
+            if next.is_finite() && next > lo && next < hi {
+                x = next;
+                if step.abs() < tolerance(x) {
+                    return x;
+                }
+            } else {
+                x = (lo + hi) / 2.0;
+            }
+            if hi - lo < tolerance(x) {
+                return x;
+            }
+        }
+        return x;
+    } else {
+        // No sign change, e.g. a double root the square free part did not
+        // quite remove, but the Sturm sequence still tells us which half it is in.
+        for _ in 0..MAX_BISECTIONS {
+            let mid = (lo + hi) / 2.0;
+            if hi - lo < tolerance(mid) {
+                break;
+            }
+            if count_roots(sequence, lo, mid) > 0 {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        return (lo + hi) / 2.0;
+    }
+}
+
+fn isolate_roots(p: &Polynomial,
+                 sequence: &[Polynomial],
+                 lo: f64,
+                 hi: f64,
+                 count: usize,
+                 depth: usize,
+                 roots: &mut Vec<f64>) {
+    if count == 0 {
+        return;
+    }
+    if count == 1 {
+        roots.push(refine_root(p, sequence, lo, hi));
+        return;
+    }
+    let mid = (lo + hi) / 2.0;
+    if depth >= MAX_BISECTIONS || mid <= lo || mid >= hi {
+        // A cluster of roots closer together than we can resolve.
+        roots.push(mid);
+        return;
+    }
+    let left = count_roots(sequence, lo, mid);
+    isolate_roots(p, sequence, lo, mid, left, depth + 1, roots);
+    isolate_roots(p, sequence, mid, hi, count.saturating_sub(left), depth + 1, roots);
+}
+
+impl ::std::ops::Add for Polynomial {
+    type Output = Polynomial;
+
+    fn add(self, other: Polynomial) -> Polynomial {
+        let n = self.coefficients.len().max(other.coefficients.len());
+        let get = |p: &Polynomial, i: usize| *p.coefficients.get(i).unwrap_or(&0.0);
+        return Polynomial::new((0..n).map(|i| get(&self, i) + get(&other, i)).collect());
+    }
+}
+
+impl ::std::ops::Sub for Polynomial {
+    type Output = Polynomial;
+
+    fn sub(self, other: Polynomial) -> Polynomial {
+        return self + other * -1.0;
+    }
+}
+
+impl ::std::ops::Mul for Polynomial {
+    type Output = Polynomial;
+
+    fn mul(self, other: Polynomial) -> Polynomial {
+        let mut product = vec![0.0; self.coefficients.len() + other.coefficients.len() - 1];
+        for (i, x) in self.coefficients.iter().enumerate() {
+            for (j, y) in other.coefficients.iter().enumerate() {
+                product[i + j] += x * y;
+            }
+        }
+        return Polynomial::new(product);
+    }
+}
+
+impl ::std::ops::Mul<f64> for Polynomial {
+    type Output = Polynomial;
+
+    fn mul(self, num: f64) -> Polynomial {
+        return Polynomial::new(self.coefficients.iter().map(|c| c * num).collect());
+    }
+}
+
+#[test]
+fn test_polynomial_arithmetic() {
+    let p = Polynomial::from_roots(&[1.0, -2.0, 3.0]);
+    assert_eq!(p, Polynomial::new(vec![6.0, -5.0, -2.0, 1.0]));
+    assert_eq!(p.evaluate(3.0), 0.0);
+    assert_eq!(p.derivative(), Polynomial::new(vec![-5.0, -4.0, 3.0]));
+    assert_eq!(p.deflate(3.0), Polynomial::from_roots(&[1.0, -2.0]));
+
+    let (q, r) = p.div_rem(&Polynomial::linear(-1.0, 1.0));
+    assert_eq!(q, Polynomial::from_roots(&[-2.0, 3.0]));
+    assert!(r.is_zero());
+
+    // p(1 + 2t) has roots where 1 + 2t is a root of p.
+    let composed = p.compose_linear(1.0, 2.0);
+    assert_eq!(composed.degree(), 3);
+    for &t in [0.0, -1.5, 1.0].iter() {
+        assert!(composed.evaluate(t).abs() < 1.0e-12);
+    }
+    assert_eq!(Polynomial::linear(1.0, 1.0).pow(3), Polynomial::new(vec![1.0, 3.0, 3.0, 1.0]));
+
+    // Small leading terms are only trimmed if they cancelled out of larger ones.
+    let magnitudes = Polynomial::new(vec![1.0e10, 1.0, 1.0]);
+    assert_eq!(Polynomial::new(vec![1.0e10, 1.0, 1.0e-12]).trimmed(&magnitudes).degree(), 1);
+    assert_eq!(Polynomial::new(vec![1.0e10, 1.0, 1.0]).trimmed(&magnitudes).degree(), 2);
+}
+
+#[test]
+fn test_sturm_sequence() {
+    let close = |x: f64, y: f64| (x - y).abs() < 1.0e-10;
+
+    // (x - 1)(x - 2)(x - 3)(x^2 + 1) has three real roots.
+    let p = Polynomial::from_highest_first(&[1.0, -6.0, 12.0, -12.0, 11.0, -6.0]);
+    let sequence = p.sturm_sequence();
+    assert_eq!(count_roots(&sequence, 0.0, 10.0), 3);
+    assert_eq!(count_roots(&sequence, 1.5, 2.5), 1);
+    assert!(close(p.smallest_root(1.5, 10.0).unwrap(), 2.0));
+    let roots = p.real_roots(0.0, 10.0);
+    assert_eq!(roots.len(), 3);
+    assert!(close(roots[0], 1.0) && close(roots[1], 2.0) && close(roots[2], 3.0));
+
+    // (x - 1)^3 (x + 1) has a triple root, which is counted once.
+    let p = Polynomial::from_roots(&[1.0, 1.0, 1.0, -1.0]);
+    let (q, _) = p.square_free();
+    assert_eq!(q.degree(), 2);
+    assert_eq!(p.real_roots(-10.0, 10.0).len(), 2);
+}
+
+#[test]
+fn test_high_degree_roots() {
+    let expected = [-3.5, -1.0, -0.25, 0.5, 0.75, 2.0, 4.0, 10.0];
+    let p = Polynomial::from_roots(&expected) * Polynomial::new(vec![1.0, 0.0, 1.0]);
+    assert_eq!(p.degree(), 10);
+    let roots = p.real_roots(-1.0 / 0.0, 1.0 / 0.0);
+    assert_eq!(roots.len(), expected.len());
+    for (r, x) in roots.iter().zip(expected.iter()) {
+        assert!((r - x).abs() < 1.0e-9);
+    }
+    assert!((p.smallest_root(0.0, 1.0 / 0.0).unwrap() - 0.5).abs() < 1.0e-9);
+
+    // Leading terms too small to matter where the other roots are only add
+    // roots out near infinity, and are dropped; a large constant alone, as
+    // for a ray starting far away, does not make the leading term small.
+    let roots = Polynomial::from_highest_first(&[1.0e-20, 1.0, -3.0, 2.0]).real_roots(-1.0 / 0.0, 1.0 / 0.0);
+    assert_eq!(roots.len(), 2);
+    assert!((roots[0] - 1.0).abs() < 1.0e-9 && (roots[1] - 2.0).abs() < 1.0e-9);
+    let far = Polynomial::from_roots(&[1000.0, 1001.0, 1002.0, 1003.0]);
+    assert_eq!(far.real_roots(0.0, 1.0 / 0.0).len(), 4);
+}
@@ -0,0 +1,165 @@
+use vec3::Vector3;
+use shape::HitDifferentials;
+
+/// A ray from `origin` along `direction`, at an instant while the shutter
+/// is open. Only the part of it between `t_min` and `t_max` is looked at,
+/// so a shadow ray can stop at the light and a shape can ignore hits
+/// further away than one already found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub direction: Vector3,
+    pub origin: Vector3,
+    /// When the ray is travelling, so that moving shapes are hit where they
+    /// were at that moment.
+    pub time: f64,
+    pub t_min: f64,
+    pub t_max: f64,
+    /// How the ray changes from one pixel to the next, for camera rays and
+    /// their mirror reflections and refractions.
+    pub differential: Option<Differential>,
+    /// The reciprocal of each component of `direction`, for box tests. Kept
+    /// private so that it always matches `direction`.
+    inv_direction: Vector3,
+}
+
+/// The derivatives of a ray's origin and direction with respect to the
+/// pixel coordinates `x` and `y`, which give the footprint of a pixel
+/// wherever the ray lands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Differential {
+    pub dodx: Vector3,
+    pub dddx: Vector3,
+    pub dody: Vector3,
+    pub dddy: Vector3,
+}
+
+impl Ray {
+    /// A ray at time zero, from its origin to infinity.
+    pub fn new(direction: Vector3, origin: Vector3) -> Ray {
+        Ray {
+            direction,
+            origin,
+            time: 0.0,
+            t_min: 0.0,
+            t_max: 1.0 / 0.0,
+            differential: None,
+            inv_direction: Vector3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z),
+        }
+    }
+
+    pub fn at_time(self, time: f64) -> Ray {
+        Ray { time, ..self }
+    }
+
+    pub fn with_interval(self, t_min: f64, t_max: f64) -> Ray {
+        Ray {
+            t_min,
+            t_max,
+            ..self
+        }
+    }
+
+    pub fn with_t_max(self, t_max: f64) -> Ray {
+        Ray { t_max, ..self }
+    }
+
+    pub fn with_differential(self, differential: Differential) -> Ray {
+        Ray { differential: Some(differential), ..self }
+    }
+
+    /// A new ray at the same time as this one, such as a shadow ray or a
+    /// bounce from where this one hit. It has no differential.
+    pub fn spawn(&self, direction: Vector3, origin: Vector3) -> Ray {
+        Ray::new(direction, origin).at_time(self.time)
+    }
+
+    /// This ray in another frame of reference, where it has `direction`
+    /// and `origin`. The time and interval are kept, so `direction` should
+    /// be as long as it was.
+    pub fn in_frame(&self, direction: Vector3, origin: Vector3) -> Ray {
+        Ray {
+            time: self.time,
+            t_min: self.t_min,
+            t_max: self.t_max,
+            ..Ray::new(direction, origin)
+        }
+    }
+
+    pub fn inv_direction(&self) -> Vector3 {
+        return self.inv_direction;
+    }
+
+    /// The point `t` along the ray.
+    pub fn at(&self, t: f64) -> Vector3 {
+        return self.origin + self.direction * t;
+    }
+
+    /// Whether `t` is strictly inside the interval.
+    pub fn contains(&self, t: f64) -> bool {
+        return t > self.t_min && t < self.t_max;
+    }
+
+    /// How the point `t` along the ray moves from one pixel to the next,
+    /// staying on the plane through it with `normal`. `None` if the ray has
+    /// no differential or runs along the plane.
+    pub fn point_differentials(&self, t: f64, normal: Vector3) -> Option<(Vector3, Vector3)> {
+        let d = self.differential?;
+        let cosine = self.direction.dot(normal);
+        if cosine == 0.0 {
+            return None;
+        }
+        let transfer = |dodx: Vector3, dddx: Vector3| {
+            let dp = dodx + dddx * t;
+            dp - self.direction * (dp.dot(normal) / cosine)
+        };
+        return Some((transfer(d.dodx, d.dddx), transfer(d.dody, d.dddy)));
+    }
+
+    /// The ray leaving `point`, where this one hit a surface with `normal`,
+    /// in `direction`. Its differential is that of a mirror reflection if
+    /// `direction` is on this ray's side of the surface, and otherwise of
+    /// a refraction, where `eta` is the ratio of the refractive index on
+    /// the far side to that on this ray's side.
+    pub fn scattered(&self,
+                     point: Vector3,
+                     normal: Vector3,
+                     hit: &HitDifferentials,
+                     direction: Vector3,
+                     eta: f64)
+                     -> Ray {
+        let ray = self.spawn(direction, point);
+        let d = match self.differential {
+            Some(d) => d,
+            None => return ray,
+        };
+        let wo = -self.direction;
+        // Both the normal and how it turns, on the side of `wo`.
+        let (n, dndx, dndy) = if normal.dot(wo) < 0.0 {
+            (-normal, -hit.dndx, -hit.dndy)
+        } else {
+            (normal, hit.dndx, hit.dndy)
+        };
+        let cos_o = wo.dot(n);
+        let cos_t = -direction.dot(n);
+        let reflected = cos_t < 0.0;
+        if !reflected && cos_t < 1.0e-6 {
+            return ray;
+        }
+        let turn = |dddx: Vector3, dndx: Vector3| {
+            let dwo = -dddx;
+            let dcos = dwo.dot(n) + wo.dot(dndx);
+            if reflected {
+                return -dwo + (dndx * cos_o + n * dcos) * 2.0;
+            }
+            let mu = cos_o / eta - cos_t;
+            let dmu = dcos * (1.0 / eta - cos_o / (eta * eta * cos_t));
+            return -dwo / eta + dndx * mu + n * dmu;
+        };
+        return ray.with_differential(Differential {
+            dodx: hit.dpdx,
+            dddx: turn(d.dddx, dndx),
+            dody: hit.dpdy,
+            dddy: turn(d.dddy, dndy),
+        });
+    }
+}
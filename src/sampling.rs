@@ -0,0 +1,268 @@
+use std::f64::consts::PI;
+use vec3::Vector3;
+use Ray;
+
+/// A small xorshift64* pseudo random number generator.
+///
+/// Renders must be reproducible, so every generator is seeded explicitly.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+// SplitMix64 finaliser, used to spread out poor seeds.
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    return z ^ (z >> 31);
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        let state = mix(seed);
+        // Xorshift gets stuck on zero.
+        Rng { state: if state == 0 { 1 } else { state } }
+    }
+
+    /// A generator seeded from a ray, so the same ray always gets the same samples.
+    pub fn for_ray(ray: Ray) -> Rng {
+        let (dir, origin) = (ray.direction, ray.origin);
+        let seed = [dir.x, dir.y, dir.z, origin.x, origin.y, origin.z, ray.time]
+            .iter()
+            .fold(0, |h, x| mix(h ^ x.to_bits()));
+        return Rng::new(seed);
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        return self.state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        return (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+    }
+}
+
+/// `n` jittered points in the unit square, one in each cell of a grid of
+/// exactly `n` cells, as near square as `n` allows. A prime `n` gives
+/// strips.
+pub fn stratified(n: u32, rng: &mut Rng) -> Vec<(f64, f64)> {
+    let columns = (1..=(n as f64).sqrt() as u32).rev().find(|&c| n.is_multiple_of(c)).unwrap_or(1);
+    let rows = (n / columns).max(1);
+    return (0..n)
+        .map(|i| {
+            let (x, y) = (i % columns, i / columns);
+            ((x as f64 + rng.next_f64()) / columns as f64, (y as f64 + rng.next_f64()) / rows as f64)
+        })
+        .collect();
+}
+
+/// Two unit vectors perpendicular to `n` and to each other.
+pub fn orthonormal_basis(n: Vector3) -> (Vector3, Vector3) {
+    let helper = if n.x.abs() > 0.9 {
+        Vector3::new(0.0, 1.0, 0.0)
+    } else {
+        Vector3::new(1.0, 0.0, 0.0)
+    };
+    let t = n.cross(helper).normalize();
+    return (t, n.cross(t));
+}
+
+/// Maps the unit square onto the unit disk, keeping strata compact.
+pub fn concentric_disk(u: f64, v: f64) -> (f64, f64) {
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    return (r * theta.cos(), r * theta.sin());
+}
+
+/// A direction uniformly distributed within `acos(cos_max)` of `axis`.
+/// The pdf is `1 / (2 pi (1 - cos_max))` per steradian.
+pub fn uniform_cone(axis: Vector3, cos_max: f64, u: f64, v: f64) -> Vector3 {
+    let cos_theta = 1.0 - u * (1.0 - cos_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    let (t, b) = orthonormal_basis(axis);
+    return t * (sin_theta * phi.cos()) + b * (sin_theta * phi.sin()) + axis * cos_theta;
+}
+
+/// A direction in the hemisphere around `n` with density proportional to
+/// the cosine with `n`; the pdf is `cos / pi` per steradian.
+pub fn cosine_hemisphere(n: Vector3, u: f64, v: f64) -> Vector3 {
+    let (x, y) = concentric_disk(u, v);
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+    let (t, b) = orthonormal_basis(n);
+    return t * x + b * y + n * z;
+}
+
+/// Veach's power heuristic weight for a sample drawn with pdf `a` when
+/// another strategy could have drawn it with pdf `b`.
+pub fn power_heuristic(a: f64, b: f64) -> f64 {
+    if a == 0.0 {
+        return 0.0;
+    }
+    return a * a / (a * a + b * b);
+}
+
+/// A piecewise constant distribution over `[0, 1)`, for importance
+/// sampling tabulated functions.
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    function: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// `function` gives the relative density in each of its equal sized
+    /// pieces. Negative values count as zero; if all are zero the
+    /// distribution is uniform.
+    pub fn new(function: Vec<f64>) -> Distribution1D {
+        let n = function.len();
+        let mut function: Vec<f64> = function.into_iter().map(|f| f.max(0.0)).collect();
+        let mut integral: f64 = function.iter().sum::<f64>() / n as f64;
+        if integral <= 0.0 {
+            function = vec![1.0; n];
+            integral = 1.0;
+        }
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for i in 0..n {
+            let previous = cdf[i];
+            cdf.push(previous + function[i] / (integral * n as f64));
+        }
+        cdf[n] = 1.0;
+        return Distribution1D {
+            function,
+            cdf,
+            integral,
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.function.len();
+    }
+
+    /// Returns the sampled point, its pdf and the piece it is in.
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // The last piece whose cdf is at most u, skipping empty pieces.
+        let i = match self.cdf.binary_search_by(|c| c.partial_cmp(&u).unwrap()) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        let mut i = i.min(self.len() - 1);
+        while self.function[i] == 0.0 && i + 1 < self.len() {
+            i += 1;
+        }
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let offset = if width > 0.0 { ((u - self.cdf[i]) / width).clamp(0.0, 1.0) } else { 0.0 };
+        let x = ((i as f64 + offset) / self.len() as f64).min(1.0 - f64::EPSILON);
+        return (x, self.function[i] / self.integral, i);
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        let i = ((x * self.len() as f64) as usize).min(self.len() - 1);
+        return self.function[i] / self.integral;
+    }
+}
+
+/// A piecewise constant distribution over the unit square, sampled by
+/// picking a row from the marginal distribution and then a column within it.
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// `function` is `width * height` values, row by row.
+    pub fn new(function: &[f64], width: usize, height: usize) -> Distribution2D {
+        let rows = function.chunks(width).take(height);
+        // Not the rows' own integrals, which are made uniform when empty.
+        let marginal = Distribution1D::new(rows.clone().map(|row| row.iter().map(|f| f.max(0.0)).sum()).collect());
+        let rows = rows.map(|row| Distribution1D::new(row.to_vec())).collect();
+        return Distribution2D { rows, marginal };
+    }
+
+    /// Returns the point `(x, y)` and its pdf.
+    pub fn sample(&self, u: f64, v: f64) -> ((f64, f64), f64) {
+        let (y, pdf_y, row) = self.marginal.sample(v);
+        let (x, pdf_x, _) = self.rows[row].sample(u);
+        return ((x, y), pdf_x * pdf_y);
+    }
+
+    pub fn pdf(&self, x: f64, y: f64) -> f64 {
+        let row = ((y * self.rows.len() as f64) as usize).min(self.rows.len() - 1);
+        return self.marginal.pdf(y) * self.rows[row].pdf(x);
+    }
+}
+
+#[test]
+fn test_distribution() {
+    let d = Distribution1D::new(vec![0.0, 1.0, 3.0, 0.0]);
+    assert_eq!(d.pdf(0.1), 0.0);
+    assert_eq!(d.pdf(0.6), 3.0);
+    let (x, pdf, i) = d.sample(0.0);
+    assert!((0.25..0.5).contains(&x) && pdf == 1.0 && i == 1);
+    let (x, pdf, i) = d.sample(0.5);
+    assert!((0.5..0.75).contains(&x) && pdf == 3.0 && i == 2);
+    let (x, _, _) = d.sample(0.999999);
+    assert!(x < 0.75);
+
+    // The empty middle row is never picked.
+    let d = Distribution2D::new(&[1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 3.0], 3, 3);
+    let mut rng = Rng::new(5);
+    for _ in 0..100 {
+        let ((x, y), pdf) = d.sample(rng.next_f64(), rng.next_f64());
+        assert!((pdf - d.pdf(x, y)).abs() < 1.0e-12);
+        assert!((x < 1.0 / 3.0 && y < 1.0 / 3.0) || (x >= 2.0 / 3.0 && y >= 2.0 / 3.0));
+    }
+}
+
+#[test]
+fn test_stratified() {
+    let mut rng = Rng::new(7);
+    for &n in [1, 4, 6, 9, 16].iter() {
+        let points = stratified(n, &mut rng);
+        assert_eq!(points.len(), n as usize);
+        assert!(points.iter().all(|&(u, v)| (0.0..1.0).contains(&u) && (0.0..1.0).contains(&v)));
+    }
+    // A square count puts exactly one point in each cell.
+    let points = stratified(16, &mut rng);
+    for cell in 0..16 {
+        let (x, y) = ((cell % 4) as f64 / 4.0, (cell / 4) as f64 / 4.0);
+        let inside = points.iter().filter(|&&(u, v)| u >= x && u < x + 0.25 && v >= y && v < y + 0.25);
+        assert_eq!(inside.count(), 1);
+    }
+    // Other counts still cover the whole square: 6 points are a 2 by 3
+    // grid.
+    let points = stratified(6, &mut rng);
+    for cell in 0..6 {
+        let (x, y) = ((cell % 2) as f64 / 2.0, (cell / 2) as f64 / 3.0);
+        let inside = points.iter().filter(|&&(u, v)| u >= x && u < x + 0.5 && v >= y && v < y + 1.0 / 3.0);
+        assert_eq!(inside.count(), 1);
+    }
+    // A prime count gives one point in each of `n` strips.
+    for &n in [3, 5, 7].iter() {
+        let points = stratified(n, &mut rng);
+        for strip in 0..n {
+            let (y, height) = (strip as f64 / n as f64, 1.0 / n as f64);
+            assert_eq!(points.iter().filter(|&&(_, v)| v >= y && v < y + height).count(), 1);
+        }
+    }
+    let points = stratified(16, &mut rng);
+    for &(u, v) in points.iter() {
+        let (x, y) = concentric_disk(u, v);
+        assert!(x * x + y * y <= 1.0);
+    }
+}
@@ -0,0 +1,146 @@
+use shape::{Shape, Light, Transformed};
+use screen::Camera;
+use transform::{Transform, AnimatedTransform};
+use trace::World;
+
+/// Something placed in the scene by a `Node`, positioned by the node's transform.
+pub enum Content<'a> {
+    Shape(&'a dyn Shape),
+    Light(Box<dyn Light>),
+    Camera(Camera),
+}
+
+/// A named node in the scene graph.
+///
+/// A node's transform is relative to its parent, so moving a node moves
+/// everything below it as one unit.
+pub struct Node<'a> {
+    pub name: String,
+    pub transform: Transform,
+    /// Replaces `transform` when the scene is flattened at a moment in an
+    /// animation.
+    pub motion: Option<AnimatedTransform>,
+    pub contents: Vec<Content<'a>>,
+    pub children: Vec<Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    pub fn new(name: &str) -> Node<'a> {
+        Node {
+            name: String::from(name),
+            transform: Transform::identity(),
+            motion: None,
+            contents: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Node<'a> {
+        self.transform = transform;
+        return self;
+    }
+
+    pub fn with_motion(mut self, motion: AnimatedTransform) -> Node<'a> {
+        self.motion = Some(motion);
+        return self;
+    }
+
+    /// The node's transform relative to its parent at `time`.
+    pub fn transform_at(&self, time: f64) -> Transform {
+        return self.motion.as_ref().map_or(self.transform, |m| m.at(time));
+    }
+
+    pub fn with_shape(mut self, shape: &'a dyn Shape) -> Node<'a> {
+        self.contents.push(Content::Shape(shape));
+        return self;
+    }
+
+    pub fn with_light(mut self, light: Box<dyn Light>) -> Node<'a> {
+        self.contents.push(Content::Light(light));
+        return self;
+    }
+
+    pub fn with_camera(mut self, camera: Camera) -> Node<'a> {
+        self.contents.push(Content::Camera(camera));
+        return self;
+    }
+
+    pub fn with_child(mut self, child: Node<'a>) -> Node<'a> {
+        self.children.push(child);
+        return self;
+    }
+
+    /// Depth first search for the first node called `name`, including this one.
+    pub fn find(&self, name: &str) -> Option<&Node<'a>> {
+        if self.name == name {
+            return Some(self);
+        }
+        return self.children.iter().filter_map(|child| child.find(name)).next();
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Node<'a>> {
+        if self.name == name {
+            return Some(self);
+        }
+        return self.children.iter_mut().filter_map(|child| child.find_mut(name)).next();
+    }
+
+    /// Resolves every node's transform to world space and collects the contents
+    /// into flat lists the renderer can use directly.
+    pub fn flatten(&self) -> Flattened<'a> {
+        return self.flatten_at(0.0);
+    }
+
+    /// As `flatten`, with animated nodes frozen where they are at `time`.
+    pub fn flatten_at(&self, time: f64) -> Flattened<'a> {
+        let mut flattened = Flattened {
+            shapes: Vec::new(),
+            lights: Vec::new(),
+            cameras: Vec::new(),
+        };
+        self.flatten_into(Transform::identity(), time, &mut flattened);
+        return flattened;
+    }
+
+    fn flatten_into(&self, parent: Transform, time: f64, flattened: &mut Flattened<'a>) {
+        let transform = parent * self.transform_at(time);
+        for content in self.contents.iter() {
+            match *content {
+                Content::Shape(shape) => {
+                    flattened.shapes.push(Transformed::new(shape, transform));
+                }
+                Content::Light(ref light) => {
+                    flattened.lights.push(light.transformed(&transform));
+                }
+                Content::Camera(camera) => {
+                    flattened.cameras.push((self.name.clone(), camera.transformed(&transform)));
+                }
+            }
+        }
+        for child in self.children.iter() {
+            child.flatten_into(transform, time, flattened);
+        }
+    }
+}
+
+/// The world space contents of a scene graph.
+/// Cameras are tagged with the name of the node they were attached to.
+pub struct Flattened<'a> {
+    pub shapes: Vec<Transformed<&'a dyn Shape>>,
+    pub lights: Vec<Box<dyn Light>>,
+    pub cameras: Vec<(String, Camera)>,
+}
+
+impl<'a> Flattened<'a> {
+    pub fn world(&self) -> World<'_> {
+        World {
+            shapes: self.shapes.iter().map(|s| s as &dyn Shape).collect(),
+            lights: self.lights.iter().map(|l| &**l).collect(),
+            fog: None,
+        }
+    }
+
+    pub fn camera(&self, name: &str) -> Option<Camera> {
+        return self.cameras.iter().find(|&(n, _)| n == name).map(|&(_, camera)| camera);
+    }
+}
@@ -1,5 +1,8 @@
 use vec3::Vector3;
+use transform::Transform;
+use sampling::Rng;
 use super::Ray;
+use ray::Differential;
 
 pub const RES_W: i64 = 1920;
 pub const RES_H: i64 = 1080;
@@ -18,6 +21,8 @@ pub struct Screen {
     curr_w: i64,
     curr_h: i64,
     exausted: bool,
+    /// Rays are made at the moment the shutter opens.
+    pub shutter: Shutter,
 }
 
 impl Iterator for Screen {
@@ -28,7 +33,17 @@ impl Iterator for Screen {
         }
         let screen_point = self.top_left + self.increment_w * (self.curr_w as f64) +
                            self.increment_h * (self.curr_h as f64);
-        let res = Some(((screen_point - self.eye).normalize(), self.eye));
+        let to_screen = screen_point - self.eye;
+        let direction = to_screen.normalize();
+        // How the normalised direction turns as the point moves across the screen.
+        let turn = |step: Vector3| (step - direction * direction.dot(step)) / to_screen.len();
+        let differential = Differential {
+            dodx: Vector3::new(0.0, 0.0, 0.0),
+            dddx: turn(self.increment_w),
+            dody: Vector3::new(0.0, 0.0, 0.0),
+            dddy: turn(self.increment_h),
+        };
+        let res = Some(Ray::new(direction, self.eye).at_time(self.shutter.open).with_differential(differential));
         self.curr_w += 1;
         if self.curr_w == self.res_w {
             self.curr_h += 1;
@@ -41,16 +56,91 @@ impl Iterator for Screen {
     }
 }
 
-pub fn get_screen() -> Screen {
-    return Screen {
-        res_w: RES_W,
-        res_h: RES_H,
+/// When the camera's shutter is open. Each pixel averages rays at
+/// `samples` times spread over the interval, so shapes that move while it
+/// is open are blurred along their path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shutter {
+    pub open: f64,
+    pub close: f64,
+    pub samples: u32,
+}
+
+impl Shutter {
+    /// Open for an instant, which freezes all motion.
+    pub fn instant(time: f64) -> Shutter {
+        Shutter {
+            open: time,
+            close: time,
+            samples: 1,
+        }
+    }
+
+    /// One time in each of `samples` equal parts of the interval.
+    pub fn times(&self, rng: &mut Rng) -> Vec<f64> {
+        if self.open == self.close {
+            return vec![self.open];
+        }
+        let n = self.samples.max(1);
+        let length = self.close - self.open;
+        return (0..n).map(|i| self.open + length * (i as f64 + rng.next_f64()) / n as f64).collect();
+    }
+}
+
+/// A pinhole camera looking through a rectangular screen `screen_distance` in front of the eye.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    pub eye: Vector3,
+    pub forward: Vector3,
+    pub up: Vector3,
+    pub screen_distance: f64,
+    pub screen_w: f64,
+    pub screen_h: f64,
+    pub shutter: Shutter,
+}
+
+impl Camera {
+    pub fn screen(&self, res_w: i64, res_h: i64) -> Screen {
+        let forward = self.forward.normalize();
+        let right = self.up.cross(forward).normalize();
+        let up = forward.cross(right);
+        return Screen {
+            res_w,
+            res_h,
+            eye: self.eye,
+            top_left: self.eye + forward * self.screen_distance + right * (-self.screen_w / 2.0) +
+                      up * (self.screen_h / 2.0),
+            increment_w: right * (self.screen_w / res_w as f64),
+            increment_h: up * (-self.screen_h / res_h as f64),
+            curr_w: 0,
+            curr_h: 0,
+            exausted: false,
+            shutter: self.shutter,
+        };
+    }
+
+    pub fn transformed(&self, transform: &Transform) -> Camera {
+        Camera {
+            eye: transform.point(self.eye),
+            forward: transform.vector(self.forward),
+            up: transform.vector(self.up),
+            ..*self
+        }
+    }
+}
+
+pub fn default_camera() -> Camera {
+    Camera {
         eye: Vector3::new(0.0, 0.0, VIEW_Z),
-        top_left: Vector3::new(-SCREEN_W / 2.0, SCREEN_H / 2.0, SCREEN_Z),
-        increment_w: Vector3::new(SCREEN_W / RES_W as f64, 0.0, 0.0),
-        increment_h: Vector3::new(0.0, -SCREEN_H / RES_H as f64, 0.0),
-        curr_w: 0,
-        curr_h: 0,
-        exausted: false,
-    };
+        forward: Vector3::new(0.0, 0.0, 1.0),
+        up: Vector3::new(0.0, 1.0, 0.0),
+        screen_distance: SCREEN_Z - VIEW_Z,
+        screen_w: SCREEN_W,
+        screen_h: SCREEN_H,
+        shutter: Shutter::instant(0.0),
+    }
+}
+
+pub fn get_screen() -> Screen {
+    return default_camera().screen(RES_W, RES_H);
 }
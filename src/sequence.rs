@@ -0,0 +1,80 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use screen::Shutter;
+
+/// An animation rendered as a run of numbered image files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameSequence {
+    /// The time of the first frame.
+    pub start: f64,
+    pub frames: u32,
+    pub frames_per_second: f64,
+    /// The fraction of each frame the shutter is open for. Zero freezes
+    /// all motion.
+    pub exposure: f64,
+    /// Times sampled per pixel while the shutter is open.
+    pub shutter_samples: u32,
+    /// Files are named this, then the frame number padded to four digits,
+    /// then `.ppm`.
+    pub prefix: String,
+}
+
+impl FrameSequence {
+    pub fn new(frames: u32, frames_per_second: f64) -> FrameSequence {
+        FrameSequence {
+            start: 0.0,
+            frames,
+            frames_per_second,
+            exposure: 0.0,
+            shutter_samples: 1,
+            prefix: String::from("frame_"),
+        }
+    }
+
+    pub fn with_prefix(self, prefix: &str) -> FrameSequence {
+        FrameSequence { prefix: String::from(prefix), ..self }
+    }
+
+    pub fn with_exposure(self, exposure: f64, shutter_samples: u32) -> FrameSequence {
+        FrameSequence {
+            exposure,
+            shutter_samples,
+            ..self
+        }
+    }
+
+    pub fn time(&self, frame: u32) -> f64 {
+        return self.start + frame as f64 / self.frames_per_second;
+    }
+
+    /// The shutter opens at the frame's time.
+    pub fn shutter(&self, frame: u32) -> Shutter {
+        let open = self.time(frame);
+        return Shutter {
+            open,
+            close: open + self.exposure / self.frames_per_second,
+            samples: self.shutter_samples,
+        };
+    }
+
+    pub fn path(&self, frame: u32) -> PathBuf {
+        return PathBuf::from(format!("{}{:04}.ppm", self.prefix, frame));
+    }
+
+    /// Renders each frame in turn, handing `render` the frame's shutter and
+    /// the file to write its image to. Returns the files written.
+    pub fn render<F>(&self, mut render: F) -> io::Result<Vec<PathBuf>>
+        where F: FnMut(Shutter, &mut dyn Write)
+    {
+        let mut paths = Vec::with_capacity(self.frames as usize);
+        for frame in 0..self.frames {
+            let path = self.path(frame);
+            let mut file = BufWriter::new(File::create(&path)?);
+            render(self.shutter(frame), &mut file);
+            file.flush()?;
+            paths.push(path);
+        }
+        return Ok(paths);
+    }
+}
@@ -0,0 +1,220 @@
+use vec3::Vector3;
+use shape::{Shape, INTERSECT_EPSILON};
+use polynomial::Polynomial;
+use Ray;
+
+/// One term `coefficient * x^i * y^j * z^k` of a polynomial in x, y and z.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Term {
+    pub coefficient: f64,
+    pub powers: (u32, u32, u32),
+}
+
+pub fn term(coefficient: f64, i: u32, j: u32, k: u32) -> Term {
+    Term {
+        coefficient,
+        powers: (i, j, k),
+    }
+}
+
+impl Term {
+    pub fn degree(&self) -> u32 {
+        let (i, j, k) = self.powers;
+        return i + j + k;
+    }
+
+    pub fn scaled(self, factor: f64) -> Term {
+        Term {
+            coefficient: self.coefficient * factor,
+            ..self
+        }
+    }
+}
+
+/// Product of two polynomials given as lists of terms, for building up surfaces
+/// from factors.
+pub fn multiply(a: &[Term], b: &[Term]) -> Vec<Term> {
+    let mut product = Vec::with_capacity(a.len() * b.len());
+    for s in a.iter() {
+        for t in b.iter() {
+            let (i, j, k) = s.powers;
+            let (l, m, n) = t.powers;
+            product.push(term(s.coefficient * t.coefficient, i + l, j + m, k + n));
+        }
+    }
+    return collect_terms(product);
+}
+
+/// Merges terms with the same powers and drops those that cancel.
+pub fn collect_terms(terms: Vec<Term>) -> Vec<Term> {
+    let mut collected: Vec<Term> = Vec::new();
+    for t in terms {
+        if let Some(existing) = collected.iter_mut().find(|c| c.powers == t.powers) {
+            existing.coefficient += t.coefficient;
+            continue;
+        }
+        collected.push(t);
+    }
+    collected.retain(|t| t.coefficient != 0.0);
+    return collected;
+}
+
+/// The surface `f(x, y, z) = 0` for a polynomial `f` of any degree.
+///
+/// Along a ray `f` becomes a polynomial in the distance `t`, whose real
+/// roots are isolated with Sturm sequences. Only intersections within
+/// `bounding_radius` of the origin are reported, which clips surfaces that
+/// extend to infinity.
+pub struct AlgebraicSurface {
+    pub terms: Vec<Term>,
+    pub bounding_radius: f64,
+}
+
+impl AlgebraicSurface {
+    pub fn new(terms: Vec<Term>) -> AlgebraicSurface {
+        return AlgebraicSurface {
+            terms: collect_terms(terms),
+            bounding_radius: 1.0 / 0.0,
+        };
+    }
+
+    pub fn with_bounding_radius(mut self, radius: f64) -> AlgebraicSurface {
+        self.bounding_radius = radius;
+        return self;
+    }
+
+    /// Steiner's Roman surface, `x^2 y^2 + y^2 z^2 + z^2 x^2 + xyz = 0`.
+    pub fn steiner() -> AlgebraicSurface {
+        AlgebraicSurface::new(vec![term(1.0, 2, 2, 0),
+                                   term(1.0, 0, 2, 2),
+                                   term(1.0, 2, 0, 2),
+                                   term(1.0, 1, 1, 1)])
+            .with_bounding_radius(1.0)
+    }
+
+    /// Goursat's family `x^4 + y^4 + z^4 + a (x^2 + y^2 + z^2)^2 + b (x^2 + y^2 + z^2) + c = 0`.
+    /// `goursat(0.0, -5.0, 11.8)` is the tanglecube.
+    pub fn goursat(a: f64, b: f64, c: f64) -> AlgebraicSurface {
+        let r2 = [term(1.0, 2, 0, 0), term(1.0, 0, 2, 0), term(1.0, 0, 0, 2)];
+        let mut terms = vec![term(1.0, 4, 0, 0), term(1.0, 0, 4, 0), term(1.0, 0, 0, 4), term(c, 0, 0, 0)];
+        terms.extend(multiply(&r2, &r2).into_iter().map(|t| t.scaled(a)));
+        terms.extend(r2.iter().map(|t| t.scaled(b)));
+        return AlgebraicSurface::new(terms);
+    }
+
+    /// Kummer's quartic with parameter `mu`; `mu * mu = 1.3` gives the surface
+    /// with sixteen real double points.
+    pub fn kummer(mu: f64) -> AlgebraicSurface {
+        let mu2 = mu * mu;
+        let lambda = (3.0 * mu2 - 1.0) / (3.0 - mu2);
+        let root2 = (2.0f64).sqrt();
+        let sphere = [term(1.0, 2, 0, 0), term(1.0, 0, 2, 0), term(1.0, 0, 0, 2), term(-mu2, 0, 0, 0)];
+        // Four planes bounding a tetrahedron.
+        let p = [term(1.0, 0, 0, 0), term(-1.0, 0, 0, 1), term(-root2, 1, 0, 0)];
+        let q = [term(1.0, 0, 0, 0), term(-1.0, 0, 0, 1), term(root2, 1, 0, 0)];
+        let r = [term(1.0, 0, 0, 0), term(1.0, 0, 0, 1), term(root2, 0, 1, 0)];
+        let s = [term(1.0, 0, 0, 0), term(1.0, 0, 0, 1), term(-root2, 0, 1, 0)];
+        let planes = multiply(&multiply(&p, &q), &multiply(&r, &s));
+        let mut terms = multiply(&sphere, &sphere);
+        terms.extend(planes.into_iter().map(|t| t.scaled(-lambda)));
+        return AlgebraicSurface::new(terms).with_bounding_radius(3.0);
+    }
+
+    /// Barth's sextic, with 65 double points.
+    pub fn barth_sextic() -> AlgebraicSurface {
+        let phi = (1.0 + (5.0f64).sqrt()) / 2.0;
+        let phi2 = phi * phi;
+        // 4 (phi^2 x^2 - y^2)(phi^2 y^2 - z^2)(phi^2 z^2 - x^2) - (1 + 2 phi)(x^2 + y^2 + z^2 - 1)^2
+        let a = [term(4.0 * phi2, 2, 0, 0), term(-4.0, 0, 2, 0)];
+        let b = [term(phi2, 0, 2, 0), term(-1.0, 0, 0, 2)];
+        let c = [term(phi2, 0, 0, 2), term(-1.0, 2, 0, 0)];
+        let sphere = [term(1.0, 2, 0, 0), term(1.0, 0, 2, 0), term(1.0, 0, 0, 2), term(-1.0, 0, 0, 0)];
+        let mut terms = multiply(&multiply(&a, &b), &c);
+        terms.extend(multiply(&sphere, &sphere).into_iter().map(|t| t.scaled(-(1.0 + 2.0 * phi))));
+        return AlgebraicSurface::new(terms).with_bounding_radius(2.0);
+    }
+
+    pub fn degree(&self) -> u32 {
+        return self.terms.iter().map(|t| t.degree()).max().unwrap_or(0);
+    }
+
+    pub fn value(&self, p: Vector3) -> f64 {
+        return self.terms
+            .iter()
+            .map(|t| {
+                let (i, j, k) = t.powers;
+                t.coefficient * p.x.powi(i as i32) * p.y.powi(j as i32) * p.z.powi(k as i32)
+            })
+            .sum();
+    }
+
+    pub fn gradient(&self, p: Vector3) -> Vector3 {
+        let mut gradient = Vector3::new(0.0, 0.0, 0.0);
+        for t in self.terms.iter() {
+            let (i, j, k) = t.powers;
+            let (x, y, z) = (p.x.powi(i as i32), p.y.powi(j as i32), p.z.powi(k as i32));
+            if i > 0 {
+                gradient.x += t.coefficient * i as f64 * p.x.powi(i as i32 - 1) * y * z;
+            }
+            if j > 0 {
+                gradient.y += t.coefficient * j as f64 * x * p.y.powi(j as i32 - 1) * z;
+            }
+            if k > 0 {
+                gradient.z += t.coefficient * k as f64 * x * y * p.z.powi(k as i32 - 1);
+            }
+        }
+        return gradient;
+    }
+
+    /// `f(origin + t * dir)` as a polynomial in `t`, without leading terms
+    /// that cancel out.
+    pub fn ray_polynomial(&self, ray: Ray) -> Polynomial {
+        let (dir, origin) = (ray.direction, ray.origin);
+        let abs = |v: Vector3| Vector3::new(v.x.abs(), v.y.abs(), v.z.abs());
+        // The same sum with every part positive, so nothing cancels.
+        let magnitudes = self.along(abs(origin), abs(dir), f64::abs);
+        return self.along(origin, dir, |c| c).trimmed(&magnitudes);
+    }
+
+    fn along<F: Fn(f64) -> f64>(&self, origin: Vector3, dir: Vector3, coefficient: F) -> Polynomial {
+        let x = Polynomial::linear(origin.x, dir.x);
+        let y = Polynomial::linear(origin.y, dir.y);
+        let z = Polynomial::linear(origin.z, dir.z);
+        return self.terms.iter().fold(Polynomial::constant(0.0), |sum, t| {
+            let (i, j, k) = t.powers;
+            sum + x.pow(i) * y.pow(j) * z.pow(k) * coefficient(t.coefficient)
+        });
+    }
+
+    /// All real intersections along the ray, nearest first.
+    fn intersections(&self, ray: Ray) -> Vec<f64> {
+        let (dir, origin) = (ray.direction, ray.origin);
+        let mut roots = self.ray_polynomial(ray).real_roots(ray.t_min.max(INTERSECT_EPSILON), ray.t_max);
+        roots.retain(|&t| ray.contains(t) && (origin + dir * t).len() <= self.bounding_radius);
+        return roots;
+    }
+}
+
+impl Shape for AlgebraicSurface {
+    fn intersect(&self, ray: Ray) -> Option<f64> {
+        return self.intersections(ray).first().cloned();
+    }
+
+    fn normal(&self, point: Vector3, _time: f64) -> Vector3 {
+        return self.gradient(point).normalize();
+    }
+
+    // These surfaces need not enclose anything, so the normal faces whichever
+    // side the ray came from.
+    fn intersect_with_normal(&self, ray: Ray) -> Option<(f64, Vector3)> {
+        let (dir, origin) = (ray.direction, ray.origin);
+        return self.intersect(ray).map(|s| {
+            let normal = self.normal(origin + dir * s, ray.time);
+            if normal.dot(dir) > 0.0 {
+                (s, -normal)
+            } else {
+                (s, normal)
+            }
+        });
+    }
+}
@@ -0,0 +1,94 @@
+use vec3::Vector3;
+use shape::{Shape, SurfaceSample, Hit, Transformed};
+use material::Material;
+use medium::Medium;
+use transform::AnimatedTransform;
+use color::Color;
+use Ray;
+
+/// A shape moving through the world, placed by a transform that changes
+/// over time. Each ray sees it where it was at the ray's time, so rays
+/// spread over the shutter interval blur it.
+///
+/// Questions about points on it, such as its texture or where to sample
+/// it as a light, are answered for where it is at the time asked.
+pub struct Animated<S: Shape> {
+    pub shape: S,
+    pub motion: AnimatedTransform,
+}
+
+impl<S: Shape> Animated<S> {
+    pub fn new(shape: S, motion: AnimatedTransform) -> Animated<S> {
+        Animated {
+            shape,
+            motion,
+        }
+    }
+
+    /// The shape as it is at `time`.
+    pub fn at(&self, time: f64) -> Transformed<&S> {
+        return Transformed::new(&self.shape, self.motion.at(time));
+    }
+}
+
+impl<S: Shape> Shape for Animated<S> {
+    fn intersect(&self, ray: Ray) -> Option<f64> {
+        return self.at(ray.time).intersect(ray);
+    }
+
+    fn normal(&self, point: Vector3, time: f64) -> Vector3 {
+        return self.at(time).normal(point, time);
+    }
+
+    fn intersect_with_normal(&self, ray: Ray) -> Option<(f64, Vector3)> {
+        return self.at(ray.time).intersect_with_normal(ray);
+    }
+
+    fn hit(&self, ray: Ray) -> Option<Hit> {
+        return self.at(ray.time).hit(ray);
+    }
+
+    fn uv(&self, point: Vector3, time: f64) -> (f64, f64) {
+        return self.at(time).uv(point, time);
+    }
+
+    fn tangents(&self, point: Vector3, time: f64) -> (Vector3, Vector3) {
+        return self.at(time).tangents(point, time);
+    }
+
+    fn color_diffuse(&self) -> Color {
+        return self.shape.color_diffuse();
+    }
+
+    fn color_ambient(&self) -> Color {
+        return self.shape.color_ambient();
+    }
+
+    fn material(&self) -> Option<&Material> {
+        return self.shape.material();
+    }
+
+    fn material_at(&self, point: Vector3, uv: (f64, f64), footprint: f64, time: f64) -> Option<Material> {
+        return self.at(time).material_at(point, uv, footprint, time);
+    }
+
+    fn object_point(&self, point: Vector3, time: f64) -> Vector3 {
+        return self.at(time).object_point(point, time);
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        return self.shape.medium();
+    }
+
+    fn density(&self, point: Vector3, time: f64) -> f64 {
+        return self.at(time).density(point, time);
+    }
+
+    fn sample_surface(&self, u: (f64, f64), time: f64) -> Option<SurfaceSample> {
+        return self.at(time).sample_surface(u, time);
+    }
+
+    fn surface_pdf(&self, point: Vector3, time: f64) -> f64 {
+        return self.at(time).surface_pdf(point, time);
+    }
+}
@@ -1,8 +1,337 @@
 use vec3::Vector3;
+use color;
 use color::Color;
+use transform::Transform;
+use sampling;
+use std::f64::consts::PI;
 
-#[derive(Debug)]
-pub struct Light {
+/// Light reaching a point from a light source.
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    /// Unit vector from the lit point towards the light.
+    pub direction: Vector3,
+    /// Distance to the light, infinite for directional lights.
+    /// Shadow rays only need to look this far.
+    pub distance: f64,
+    /// Irradiance on a surface facing the light, before the cosine term.
+    pub color: Color,
+}
+
+pub trait Light {
+    /// The light arriving at `point` from the part of the light picked by
+    /// `u`, a point in the unit square, or `None` if none can.
+    ///
+    /// Area lights return an estimate of the light from their whole surface
+    /// based on that one point, so averaging over many `u` converges on the
+    /// true value. Point-like lights ignore `u`.
+    fn illuminate(&self, point: Vector3, u: (f64, f64)) -> Option<LightSample>;
+
+    /// How many shadow rays to spend on the light at each shading point.
+    fn sample_count(&self) -> u32 {
+        1
+    }
+
+    /// Radiance arriving along rays that leave the scene in `direction`.
+    /// Only lights at infinity, like environments, have any.
+    fn background(&self, _direction: Vector3) -> Color {
+        return color::BLACK;
+    }
+
+    /// The pdf per steradian with which `illuminate` picks `direction`, for
+    /// weighting it against other ways of finding the light. Only needed for
+    /// lights that rays can run into, i.e. those with a `background`.
+    fn pdf(&self, _point: Vector3, _direction: Vector3) -> f64 {
+        return 0.0;
+    }
+
+    /// A copy of the light moved by `transform`, for placing lights with the scene graph.
+    fn transformed(&self, transform: &Transform) -> Box<dyn Light>;
+}
+
+/// How a light's intensity drops off with distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Falloff {
+    /// Physically correct `1 / d^2`.
+    InverseSquare,
+    /// `1 / d^2`, smoothly windowed to reach zero at `radius` so that
+    /// distant surfaces can skip the light entirely.
+    Windowed { radius: f64 },
+    /// `1 / d`; not physical, but a softer look.
+    Linear,
+    /// Constant with distance.
+    None,
+}
+
+impl Falloff {
+    pub fn attenuation(&self, distance: f64) -> f64 {
+        match *self {
+            Falloff::InverseSquare => return 1.0 / (distance * distance),
+            Falloff::Windowed { radius } => {
+                let x = distance / radius;
+                let window = (1.0 - x * x * x * x).max(0.0);
+                return window * window / (distance * distance);
+            }
+            Falloff::Linear => return 1.0 / distance,
+            Falloff::None => return 1.0,
+        }
+    }
+}
+
+/// Light radiating equally in all directions from a point.
+#[derive(Debug, Clone, Copy)]
+pub struct PointLight {
+    pub position: Vector3,
+    pub color: Color,
+    /// Radiant intensity in watts per steradian.
+    pub intensity: f64,
+    pub falloff: Falloff,
+}
+
+impl Light for PointLight {
+    fn illuminate(&self, point: Vector3, _: (f64, f64)) -> Option<LightSample> {
+        let ray_to_light = self.position - point;
+        let distance = ray_to_light.len();
+        let attenuation = self.falloff.attenuation(distance);
+        if attenuation <= 0.0 {
+            return None;
+        }
+        return Some(LightSample {
+            direction: ray_to_light / distance,
+            distance,
+            color: self.color * (self.intensity * attenuation),
+        });
+    }
+
+    fn transformed(&self, transform: &Transform) -> Box<dyn Light> {
+        Box::new(PointLight {
+            position: transform.point(self.position),
+            ..*self
+        })
+    }
+}
+
+/// Light from a very distant source such as the sun, arriving everywhere
+/// from the same direction.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    /// The direction the light travels in.
+    pub direction: Vector3,
+    pub color: Color,
+    /// Irradiance in watts per square metre.
+    pub irradiance: f64,
+}
+
+impl Light for DirectionalLight {
+    fn illuminate(&self, _: Vector3, _: (f64, f64)) -> Option<LightSample> {
+        return Some(LightSample {
+            direction: -self.direction.normalize(),
+            distance: 1.0 / 0.0,
+            color: self.color * self.irradiance,
+        });
+    }
+
+    fn transformed(&self, transform: &Transform) -> Box<dyn Light> {
+        Box::new(DirectionalLight {
+            direction: transform.vector(self.direction),
+            ..*self
+        })
+    }
+}
+
+/// A point light that only shines within a cone.
+#[derive(Debug, Clone, Copy)]
+pub struct SpotLight {
+    pub position: Vector3,
+    /// The axis of the cone.
+    pub direction: Vector3,
+    pub color: Color,
+    /// Radiant intensity along the axis, in watts per steradian.
+    pub intensity: f64,
+    pub falloff: Falloff,
+    /// Angle in radians between the axis and the edge of the cone.
+    pub cone_angle: f64,
+    /// Width in radians of the soft edge, just inside the cone, over which
+    /// the light fades out.
+    pub penumbra_angle: f64,
+}
+
+impl Light for SpotLight {
+    fn illuminate(&self, point: Vector3, _: (f64, f64)) -> Option<LightSample> {
+        let ray_to_light = self.position - point;
+        let distance = ray_to_light.len();
+        let direction = ray_to_light / distance;
+        let cosine = -direction.dot(self.direction.normalize());
+        let outer = self.cone_angle.cos();
+        if cosine <= outer {
+            return None;
+        }
+        let inner = (self.cone_angle - self.penumbra_angle).max(0.0).cos();
+        let edge = if cosine >= inner {
+            1.0
+        } else {
+            // Smoothstep across the penumbra.
+            let x = (cosine - outer) / (inner - outer);
+            x * x * (3.0 - 2.0 * x)
+        };
+        let attenuation = self.falloff.attenuation(distance) * edge;
+        if attenuation <= 0.0 {
+            return None;
+        }
+        return Some(LightSample {
+            direction,
+            distance,
+            color: self.color * (self.intensity * attenuation),
+        });
+    }
+
+    fn transformed(&self, transform: &Transform) -> Box<dyn Light> {
+        Box::new(SpotLight {
+            position: transform.point(self.position),
+            direction: transform.vector(self.direction),
+            ..*self
+        })
+    }
+}
+
+/// One-sided rectangular light spanned by `edge_u` and `edge_v` from
+/// `corner`. It shines towards `edge_u x edge_v`.
+#[derive(Debug, Clone, Copy)]
+pub struct RectLight {
+    pub corner: Vector3,
+    pub edge_u: Vector3,
+    pub edge_v: Vector3,
+    pub color: Color,
+    /// Emitted radiance in watts per steradian per square metre.
+    pub radiance: f64,
+    pub samples: u32,
+}
+
+// Irradiance estimate for a light point sampled uniformly by area.
+fn area_sample(point: Vector3,
+               on_light: Vector3,
+               light_normal: Vector3,
+               area: f64,
+               color: Color,
+               radiance: f64)
+               -> Option<LightSample> {
+    let ray_to_light = on_light - point;
+    let distance = ray_to_light.len();
+    let direction = ray_to_light / distance;
+    let cosine = -direction.dot(light_normal);
+    if cosine <= 0.0 {
+        return None;
+    }
+    return Some(LightSample {
+        direction,
+        distance,
+        color: color * (radiance * cosine * area / (distance * distance)),
+    });
+}
+
+impl Light for RectLight {
+    fn illuminate(&self, point: Vector3, (u, v): (f64, f64)) -> Option<LightSample> {
+        let cross = self.edge_u.cross(self.edge_v);
+        let on_light = self.corner + self.edge_u * u + self.edge_v * v;
+        return area_sample(point, on_light, cross.normalize(), cross.len(), self.color, self.radiance);
+    }
+
+    fn sample_count(&self) -> u32 {
+        self.samples
+    }
+
+    fn transformed(&self, transform: &Transform) -> Box<dyn Light> {
+        Box::new(RectLight {
+            corner: transform.point(self.corner),
+            edge_u: transform.vector(self.edge_u),
+            edge_v: transform.vector(self.edge_v),
+            ..*self
+        })
+    }
+}
+
+/// One-sided disk light facing along `normal`.
+#[derive(Debug, Clone, Copy)]
+pub struct DiskLight {
     pub position: Vector3,
+    pub normal: Vector3,
+    pub radius: f64,
     pub color: Color,
+    /// Emitted radiance in watts per steradian per square metre.
+    pub radiance: f64,
+    pub samples: u32,
+}
+
+impl Light for DiskLight {
+    fn illuminate(&self, point: Vector3, (u, v): (f64, f64)) -> Option<LightSample> {
+        let normal = self.normal.normalize();
+        let (t, b) = sampling::orthonormal_basis(normal);
+        let (x, y) = sampling::concentric_disk(u, v);
+        let on_light = self.position + (t * x + b * y) * self.radius;
+        let area = PI * self.radius * self.radius;
+        return area_sample(point, on_light, normal, area, self.color, self.radiance);
+    }
+
+    fn sample_count(&self) -> u32 {
+        self.samples
+    }
+
+    // Assumes the transform scales uniformly.
+    fn transformed(&self, transform: &Transform) -> Box<dyn Light> {
+        let (t, _) = sampling::orthonormal_basis(self.normal.normalize());
+        Box::new(DiskLight {
+            position: transform.point(self.position),
+            normal: transform.normal(self.normal),
+            radius: self.radius * transform.vector(t).len(),
+            ..*self
+        })
+    }
+}
+
+/// Spherical light, glowing equally in every direction from its surface.
+#[derive(Debug, Clone, Copy)]
+pub struct SphereLight {
+    pub position: Vector3,
+    pub radius: f64,
+    pub color: Color,
+    /// Emitted radiance in watts per steradian per square metre.
+    pub radiance: f64,
+    pub samples: u32,
+}
+
+impl Light for SphereLight {
+    // Samples directions uniformly within the cone the sphere subtends,
+    // which wastes no samples on the far side of the sphere.
+    fn illuminate(&self, point: Vector3, (u, v): (f64, f64)) -> Option<LightSample> {
+        let to_centre = self.position - point;
+        let d2 = to_centre.dot(to_centre);
+        let r2 = self.radius * self.radius;
+        if d2 <= r2 {
+            return None;
+        }
+        let axis = to_centre.normalize();
+        let cos_max = (1.0 - r2 / d2).sqrt();
+        let direction = sampling::uniform_cone(axis, cos_max, u, v);
+        // Nearest intersection of the sample direction with the sphere.
+        let b = direction.dot(to_centre);
+        let distance = b - (b * b - d2 + r2).max(0.0).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_max);
+        return Some(LightSample {
+            direction,
+            distance,
+            color: self.color * (self.radiance * solid_angle),
+        });
+    }
+
+    fn sample_count(&self) -> u32 {
+        self.samples
+    }
+
+    // Assumes the transform scales uniformly.
+    fn transformed(&self, transform: &Transform) -> Box<dyn Light> {
+        Box::new(SphereLight {
+            position: transform.point(self.position),
+            radius: self.radius * transform.vector(Vector3::new(1.0, 0.0, 0.0)).len(),
+            ..*self
+        })
+    }
 }
@@ -1,10 +1,14 @@
 use vec3::Vector3;
 use color;
 use color::Color;
+use material::Material;
+use medium::Medium;
+use sampling;
 use Ray;
 
 pub mod light;
-pub use shape::light::Light;
+pub use shape::light::{Light, LightSample, Falloff, PointLight, DirectionalLight, SpotLight, RectLight,
+                       DiskLight, SphereLight};
 pub mod plane;
 pub use shape::plane::Plane;
 
@@ -14,25 +18,275 @@ pub use shape::sphere::Sphere;
 pub mod torus;
 pub use shape::torus::Torus;
 
+pub mod algebraic;
+pub use shape::algebraic::AlgebraicSurface;
+
+pub mod sdf;
+pub use shape::sdf::{Sdf, SdfShape};
+
+pub mod transformed;
+pub use shape::transformed::Transformed;
+
+pub mod animated;
+pub use shape::animated::Animated;
+
+pub mod triangle;
+pub use shape::triangle::Triangle;
+
+pub mod surface;
+pub use shape::surface::Surface;
+
+pub mod volume;
+pub use shape::volume::Volume;
+
+/// Where a ray meets a shape.
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    /// Distance along the ray.
+    pub t: f64,
+    /// The geometric normal.
+    pub normal: Vector3,
+    /// The normal used for shading, on the same side as `normal` but
+    /// perturbed by any bump or normal map.
+    pub shading_normal: Vector3,
+    /// Surface coordinates of the point, for looking up textures.
+    pub uv: (f64, f64),
+    /// How the point moves as `u` and `v` change; together with the normal
+    /// these give the tangent frame at the point.
+    pub dpdu: Vector3,
+    pub dpdv: Vector3,
+}
+
+/// How a hit moves from one pixel to the next, found from the
+/// differential of the ray that made it.
+#[derive(Debug, Clone, Copy)]
+pub struct HitDifferentials {
+    pub dpdx: Vector3,
+    pub dpdy: Vector3,
+    /// How the geometric normal turns.
+    pub dndx: Vector3,
+    pub dndy: Vector3,
+    /// How the surface coordinates change.
+    pub dudx: f64,
+    pub dvdx: f64,
+    pub dudy: f64,
+    pub dvdy: f64,
+}
+
+impl HitDifferentials {
+    /// The width, in surface coordinates, of the part of the surface seen
+    /// through one pixel, for picking a mip map level.
+    pub fn footprint(&self) -> f64 {
+        let x = (self.dudx * self.dudx + self.dvdx * self.dvdx).sqrt();
+        let y = (self.dudy * self.dudy + self.dvdy * self.dvdy).sqrt();
+        return x.max(y);
+    }
+}
+
+impl Hit {
+    /// How this hit of `shape` by `ray` moves from one pixel to the next, or
+    /// `None` if the ray has no differential.
+    pub fn differentials(&self, shape: &dyn Shape, ray: Ray) -> Option<HitDifferentials> {
+        let (dpdx, dpdy) = ray.point_differentials(self.t, self.normal)?;
+        let point = ray.at(self.t);
+        // Some shapes turn their normal towards the ray.
+        let n = shape.normal(point, ray.time);
+        let side = if n.dot(self.normal) < 0.0 { -1.0 } else { 1.0 };
+        let dndx = (shape.normal(point + dpdx, ray.time) - n) * side;
+        let dndy = (shape.normal(point + dpdy, ray.time) - n) * side;
+        // Least squares fit of each point differential to the tangents.
+        let (uu, uv, vv) = (self.dpdu.dot(self.dpdu), self.dpdu.dot(self.dpdv), self.dpdv.dot(self.dpdv));
+        let det = uu * vv - uv * uv;
+        let solve = |dp: Vector3| {
+            if det.abs() < 1.0e-12 {
+                return (0.0, 0.0);
+            }
+            let (a, b) = (dp.dot(self.dpdu), dp.dot(self.dpdv));
+            return ((vv * a - uv * b) / det, (uu * b - uv * a) / det);
+        };
+        let ((dudx, dvdx), (dudy, dvdy)) = (solve(dpdx), solve(dpdy));
+        return Some(HitDifferentials {
+            dpdx,
+            dpdy,
+            dndx,
+            dndy,
+            dudx,
+            dvdx,
+            dudy,
+            dvdy,
+        });
+    }
+}
+
+/// A point picked on the surface of a shape, for sampling shapes as lights.
+#[derive(Debug, Clone, Copy)]
+pub struct SurfaceSample {
+    pub point: Vector3,
+    pub normal: Vector3,
+    /// Probability density of picking this point, per unit area.
+    pub pdf: f64,
+}
+
 pub trait Shape {
     // Intersection point of ray with shape.
     // the return value, (R, N), is such that
     // Ray origin + R * Ray direction = intersect point.
+    // Only hits strictly inside the ray's interval count.
     fn intersect(&self, ray: Ray) -> Option<f64>;
 
-    fn normal(&self, point: Vector3) -> Vector3;
+    /// The normal at `point` at `time`. This and the other queries about
+    /// points take the time they are asked at, so that a moving shape
+    /// answers for where it was then.
+    fn normal(&self, point: Vector3, time: f64) -> Vector3;
 
     fn intersect_with_normal(&self, ray: Ray) -> Option<(f64, Vector3)> {
-        let (dir, origin): Ray = ray;
-        return self.intersect(ray).map(|s| (s, self.normal(origin + (dir * s))));
+        let (dir, origin) = (ray.direction, ray.origin);
+        return self.intersect(ray).map(|s| (s, self.normal(origin + (dir * s), ray.time)));
+    }
+
+    fn hit(&self, ray: Ray) -> Option<Hit> {
+        let (dir, origin) = (ray.direction, ray.origin);
+        return self.intersect_with_normal(ray).map(|(t, normal)| {
+            let point = origin + dir * t;
+            let (dpdu, dpdv) = self.tangents(point, ray.time);
+            Hit {
+                t,
+                normal,
+                shading_normal: normal,
+                uv: self.uv(point, ray.time),
+                dpdu,
+                dpdv,
+            }
+        });
+    }
+
+    /// Surface coordinates of `point`, which must be on the surface. Shapes
+    /// without a parameterisation put everything at `(0, 0)`.
+    fn uv(&self, _point: Vector3, _time: f64) -> (f64, f64) {
+        return (0.0, 0.0);
+    }
+
+    /// The derivatives of `point` with respect to `u` and `v`. Shapes
+    /// without a parameterisation give any two unit vectors perpendicular
+    /// to the normal.
+    fn tangents(&self, point: Vector3, time: f64) -> (Vector3, Vector3) {
+        return sampling::orthonormal_basis(self.normal(point, time));
     }
 
     fn color_diffuse(&self) -> Color {
-        return color::RED;
+        return self.material().map_or(color::RED, |m| m.diffuse);
     }
 
     fn color_ambient(&self) -> Color {
-        return color::RED;
+        return self.material().map_or(color::RED, |m| m.ambient);
+    }
+
+    fn material(&self) -> Option<&Material> {
+        return None;
+    }
+
+    /// The material at `point`, with any textures applied. `point` is in
+    /// the space of the shape's caller; textures are looked up at its
+    /// `object_point`, so solid textures move with the shape.
+    /// `footprint` is the width in surface coordinates of the area seen,
+    /// or zero for a single point.
+    fn material_at(&self, _point: Vector3, _uv: (f64, f64), _footprint: f64, _time: f64) -> Option<Material> {
+        return self.material().cloned();
+    }
+
+    /// `point`, given in the space of the shape's caller, in the space of
+    /// the innermost shape, where solid textures are looked up. Only
+    /// shapes that move what they wrap change it.
+    fn object_point(&self, point: Vector3, _time: f64) -> Vector3 {
+        return point;
+    }
+
+    /// The medium filling a closed shape, if any, whose surface is then
+    /// only the invisible boundary of the medium.
+    fn medium(&self) -> Option<&Medium> {
+        return None;
+    }
+
+    /// The density of the medium at `point`, which is in the space of the
+    /// shape's caller.
+    fn density(&self, point: Vector3, _time: f64) -> f64 {
+        return self.medium().map_or(0.0, |m| m.density(point));
+    }
+
+    /// Picks a point on the surface from `u`, a point in the unit square.
+    /// Only finite shapes that can do this illuminate the scene when emissive;
+    /// others are still visible, but only light what they are seen in.
+    fn sample_surface(&self, _u: (f64, f64), _time: f64) -> Option<SurfaceSample> {
+        return None;
+    }
+
+    /// The pdf per unit area with which `sample_surface` picks `point`,
+    /// which must be on the surface; zero for shapes that cannot be sampled.
+    fn surface_pdf(&self, _point: Vector3, _time: f64) -> f64 {
+        return 0.0;
+    }
+}
+
+// Lets a single shape be shared between several wrappers, e.g. instanced by many `Transformed`s.
+impl<S: Shape + ?Sized> Shape for &S {
+    fn intersect(&self, ray: Ray) -> Option<f64> {
+        return (**self).intersect(ray);
+    }
+
+    fn normal(&self, point: Vector3, time: f64) -> Vector3 {
+        return (**self).normal(point, time);
+    }
+
+    fn intersect_with_normal(&self, ray: Ray) -> Option<(f64, Vector3)> {
+        return (**self).intersect_with_normal(ray);
+    }
+
+    fn hit(&self, ray: Ray) -> Option<Hit> {
+        return (**self).hit(ray);
+    }
+
+    fn uv(&self, point: Vector3, time: f64) -> (f64, f64) {
+        return (**self).uv(point, time);
+    }
+
+    fn tangents(&self, point: Vector3, time: f64) -> (Vector3, Vector3) {
+        return (**self).tangents(point, time);
+    }
+
+    fn color_diffuse(&self) -> Color {
+        return (**self).color_diffuse();
+    }
+
+    fn color_ambient(&self) -> Color {
+        return (**self).color_ambient();
+    }
+
+    fn material(&self) -> Option<&Material> {
+        return (**self).material();
+    }
+
+    fn material_at(&self, point: Vector3, uv: (f64, f64), footprint: f64, time: f64) -> Option<Material> {
+        return (**self).material_at(point, uv, footprint, time);
+    }
+
+    fn object_point(&self, point: Vector3, time: f64) -> Vector3 {
+        return (**self).object_point(point, time);
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        return (**self).medium();
+    }
+
+    fn density(&self, point: Vector3, time: f64) -> f64 {
+        return (**self).density(point, time);
+    }
+
+    fn sample_surface(&self, u: (f64, f64), time: f64) -> Option<SurfaceSample> {
+        return (**self).sample_surface(u, time);
+    }
+
+    fn surface_pdf(&self, point: Vector3, time: f64) -> f64 {
+        return (**self).surface_pdf(point, time);
     }
 }
 
@@ -1,6 +1,7 @@
 use vec3::Vector3;
 use super::Shape;
 use Ray;
+use sampling;
 use shape::{ANGLE_EPSILON, INTERSECT_EPSILON};
 
 pub struct Plane {
@@ -10,7 +11,8 @@ pub struct Plane {
 }
 
 impl Shape for Plane {
-    fn intersect(&self, (ray_dir, ray_origin): Ray) -> Option<f64> {
+    fn intersect(&self, ray: Ray) -> Option<f64> {
+        let (ray_dir, ray_origin) = (ray.direction, ray.origin);
         let intersect_cosine: f64 = ray_dir.dot(self.normal);
 
         if intersect_cosine < ANGLE_EPSILON && intersect_cosine > -ANGLE_EPSILON {
@@ -21,15 +23,38 @@ impl Shape for Plane {
         let s: f64 = self.origin_distance - ray_origin.dot(self.normal);
         let t: f64 = s / intersect_cosine;
 
-        if t < INTERSECT_EPSILON {
-            // Ray starts after plane.
+        if t < INTERSECT_EPSILON || !ray.contains(t) {
+            // Ray starts after plane, or stops before it.
             return None;
         } else {
             return Some(t);
         }
     }
 
-    fn normal(&self, _: Vector3) -> Vector3 {
+    fn normal(&self, _: Vector3, _time: f64) -> Vector3 {
         return self.normal;
     }
+
+    // Distances along two fixed directions in the plane, so textures tile
+    // once per unit.
+    fn uv(&self, point: Vector3, _time: f64) -> (f64, f64) {
+        let (u, v) = sampling::orthonormal_basis(self.normal);
+        return (point.dot(u), point.dot(v));
+    }
+
+    fn tangents(&self, _: Vector3, _time: f64) -> (Vector3, Vector3) {
+        return sampling::orthonormal_basis(self.normal);
+    }
+
+    // The plane is two sided, so the normal faces whichever side the ray came from.
+    fn intersect_with_normal(&self, ray: Ray) -> Option<(f64, Vector3)> {
+        let dir = ray.direction;
+        return self.intersect(ray).map(|s| {
+            if dir.dot(self.normal) > 0.0 {
+                (s, -self.normal)
+            } else {
+                (s, self.normal)
+            }
+        });
+    }
 }
@@ -0,0 +1,243 @@
+use vec3::Vector3;
+use shape::Shape;
+use Ray;
+
+/// A signed distance function: negative inside the surface, positive outside,
+/// and never larger than the true distance to the surface.
+pub trait Sdf {
+    fn distance(&self, point: Vector3) -> f64;
+}
+
+impl<F: Fn(Vector3) -> f64> Sdf for F {
+    fn distance(&self, point: Vector3) -> f64 {
+        return self(point);
+    }
+}
+
+/// Renders a distance function by sphere tracing: step along the ray by the
+/// distance to the nearest surface until within `epsilon` of it.
+pub struct SdfShape<D: Sdf> {
+    pub sdf: D,
+    pub max_steps: u32,
+    pub max_distance: f64,
+    pub epsilon: f64,
+    // Fraction of the distance to step by; below 1.0 for operators like
+    // `Twist` that can overestimate the distance.
+    pub step_scale: f64,
+}
+
+impl<D: Sdf> SdfShape<D> {
+    pub fn new(sdf: D) -> SdfShape<D> {
+        SdfShape {
+            sdf,
+            max_steps: 256,
+            max_distance: 100.0,
+            epsilon: 1.0e-5,
+            step_scale: 1.0,
+        }
+    }
+}
+
+impl<D: Sdf> Shape for SdfShape<D> {
+    fn intersect(&self, ray: Ray) -> Option<f64> {
+        let (dir, origin) = (ray.direction, ray.origin);
+        let mut t = ray.t_min;
+        let mut steps = 0;
+        // Rays leaving a surface start within `epsilon` of it; get clear
+        // before marching so they do not hit the surface they start on.
+        while self.sdf.distance(origin + dir * t).abs() < self.epsilon {
+            t += self.epsilon;
+            steps += 1;
+            if steps >= self.max_steps {
+                return None;
+            }
+        }
+        while steps < self.max_steps && t < self.max_distance.min(ray.t_max) {
+            let d = self.sdf.distance(origin + dir * t).abs();
+            if d < self.epsilon {
+                return Some(t).filter(|&t| ray.contains(t));
+            }
+            t += d * self.step_scale;
+            steps += 1;
+        }
+        return None;
+    }
+
+    // Gradient of the distance field by central differences.
+    fn normal(&self, point: Vector3, _time: f64) -> Vector3 {
+        let h = self.epsilon;
+        let dx = Vector3::new(h, 0.0, 0.0);
+        let dy = Vector3::new(0.0, h, 0.0);
+        let dz = Vector3::new(0.0, 0.0, h);
+        let d = |p| self.sdf.distance(p);
+        return Vector3::new(d(point + dx) - d(point - dx),
+                            d(point + dy) - d(point - dy),
+                            d(point + dz) - d(point - dz))
+            .normalize();
+    }
+}
+
+// Primitives, all centred on the origin.
+
+pub struct Sphere {
+    pub radius: f64,
+}
+
+impl Sdf for Sphere {
+    fn distance(&self, p: Vector3) -> f64 {
+        return p.len() - self.radius;
+    }
+}
+
+/// Box with the given half extents along each axis, with edges rounded by `radius`.
+pub struct RoundBox {
+    pub half_extents: Vector3,
+    pub radius: f64,
+}
+
+impl Sdf for RoundBox {
+    fn distance(&self, p: Vector3) -> f64 {
+        let q = Vector3::new(p.x.abs() - self.half_extents.x,
+                             p.y.abs() - self.half_extents.y,
+                             p.z.abs() - self.half_extents.z);
+        let outside = Vector3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).len();
+        let inside = q.x.max(q.y).max(q.z).min(0.0);
+        return outside + inside - self.radius;
+    }
+}
+
+/// Torus around the z axis, like `shape::Torus`.
+pub struct Torus {
+    pub radius: f64,
+    pub tube_radius: f64,
+}
+
+impl Sdf for Torus {
+    fn distance(&self, p: Vector3) -> f64 {
+        let ring = (p.x * p.x + p.y * p.y).sqrt() - self.radius;
+        return (ring * ring + p.z * p.z).sqrt() - self.tube_radius;
+    }
+}
+
+/// Line segment from `a` to `b` thickened by `radius`.
+pub struct Capsule {
+    pub a: Vector3,
+    pub b: Vector3,
+    pub radius: f64,
+}
+
+impl Sdf for Capsule {
+    fn distance(&self, p: Vector3) -> f64 {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+        return (pa - ba * h).len() - self.radius;
+    }
+}
+
+/// Distance estimator for the power-n Mandelbulb fractal.
+pub struct Mandelbulb {
+    pub power: f64,
+    pub iterations: u32,
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: Vector3) -> f64 {
+        let mut z = p;
+        let mut dr = 1.0;
+        let mut r = z.len();
+        for _ in 0..self.iterations {
+            if r > 2.0 {
+                break;
+            }
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            z = Vector3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()) *
+                r.powf(self.power) + p;
+            r = z.len();
+        }
+        return 0.5 * r.ln() * r / dr;
+    }
+}
+
+// Operators.
+
+pub struct Union<A: Sdf, B: Sdf>(pub A, pub B);
+
+impl<A: Sdf, B: Sdf> Sdf for Union<A, B> {
+    fn distance(&self, p: Vector3) -> f64 {
+        return self.0.distance(p).min(self.1.distance(p));
+    }
+}
+
+pub struct Intersection<A: Sdf, B: Sdf>(pub A, pub B);
+
+impl<A: Sdf, B: Sdf> Sdf for Intersection<A, B> {
+    fn distance(&self, p: Vector3) -> f64 {
+        return self.0.distance(p).max(self.1.distance(p));
+    }
+}
+
+/// The first shape with the second cut out of it.
+pub struct Subtraction<A: Sdf, B: Sdf>(pub A, pub B);
+
+impl<A: Sdf, B: Sdf> Sdf for Subtraction<A, B> {
+    fn distance(&self, p: Vector3) -> f64 {
+        return self.0.distance(p).max(-self.1.distance(p));
+    }
+}
+
+/// Union that blends the two shapes together over a distance of about `k`.
+pub struct SmoothUnion<A: Sdf, B: Sdf> {
+    pub a: A,
+    pub b: B,
+    pub k: f64,
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(&self, p: Vector3) -> f64 {
+        let d1 = self.a.distance(p);
+        let d2 = self.b.distance(p);
+        // Polynomial smooth minimum.
+        let h = (0.5 + 0.5 * (d2 - d1) / self.k).clamp(0.0, 1.0);
+        return d2 + (d1 - d2) * h - self.k * h * (1.0 - h);
+    }
+}
+
+/// Infinite copies of a shape, one in every cell of a grid with the given period.
+/// The shape should fit inside one cell. A zero period leaves that axis unrepeated.
+pub struct Repeat<A: Sdf> {
+    pub sdf: A,
+    pub period: Vector3,
+}
+
+impl<A: Sdf> Sdf for Repeat<A> {
+    fn distance(&self, p: Vector3) -> f64 {
+        let wrap = |x: f64, period: f64| {
+            if period == 0.0 {
+                x
+            } else {
+                x - period * (x / period).round()
+            }
+        };
+        return self.sdf.distance(Vector3::new(wrap(p.x, self.period.x),
+                                              wrap(p.y, self.period.y),
+                                              wrap(p.z, self.period.z)));
+    }
+}
+
+/// Twists a shape about the y axis by `rate` radians per unit height.
+/// This stretches the field, so use a `step_scale` below 1.0 with it.
+pub struct Twist<A: Sdf> {
+    pub sdf: A,
+    pub rate: f64,
+}
+
+impl<A: Sdf> Sdf for Twist<A> {
+    fn distance(&self, p: Vector3) -> f64 {
+        let angle = self.rate * p.y;
+        let (s, c) = angle.sin_cos();
+        return self.sdf.distance(Vector3::new(c * p.x - s * p.z, p.y, s * p.x + c * p.z));
+    }
+}
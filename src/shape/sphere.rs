@@ -1,7 +1,10 @@
 use vec3::Vector3;
-use super::Shape;
+use super::{Shape, SurfaceSample};
+use std::f64::consts::PI;
 use Ray;
 use shape::INTERSECT_EPSILON;
+use environment::direction_to_equirect;
+use sampling;
 
 pub struct Sphere {
     pub centre: Vector3,
@@ -15,7 +18,8 @@ impl Sphere {
 }
 
 impl Shape for Sphere {
-    fn intersect(&self, (ray_dir, ray_origin): Ray) -> Option<f64> {
+    fn intersect(&self, ray: Ray) -> Option<f64> {
+        let (ray_dir, ray_origin) = (ray.direction, ray.origin);
         let c = ray_origin.dot(ray_origin) + self.centre.dot(self.centre) -
                 2.0 * ray_origin.dot(self.centre) - self.radius * self.radius;
         let b = ray_dir.dot(ray_origin - self.centre);
@@ -25,25 +29,48 @@ impl Shape for Sphere {
             return None::<f64>;
         }
         let d = dsq.sqrt();
-        let s1 = -b + d;
-        let s2 = -b - d;
-        if s1 < s2 {
-            if s1 > INTERSECT_EPSILON {
-                return Some(s1);
-            }
-        }
-        if s2 > INTERSECT_EPSILON {
-            return Some(s2);
-        } else {
-            if s1 > INTERSECT_EPSILON {
-                return Some(s1);
-            } else {
-                return None::<f64>;
-            }
-        }
+        // Nearest first.
+        return [-b - d, -b + d].iter().cloned().find(|&s| s > INTERSECT_EPSILON && ray.contains(s));
     }
 
-    fn normal(&self, point: Vector3) -> Vector3 {
+    fn normal(&self, point: Vector3, _time: f64) -> Vector3 {
         return (point - self.centre).normalize();
     }
+
+    // Longitude and latitude, laid out as on an equirectangular map with
+    // the poles on the y axis.
+    fn uv(&self, point: Vector3, time: f64) -> (f64, f64) {
+        return direction_to_equirect(Shape::normal(self, point, time));
+    }
+
+    fn tangents(&self, point: Vector3, _time: f64) -> (Vector3, Vector3) {
+        let d = point - self.centre;
+        let (sin_theta, cos_theta) = ((d.x * d.x + d.z * d.z).sqrt() / self.radius, d.y / self.radius);
+        // At the poles longitude is undefined and dpdu vanishes; any pair
+        // of tangents will do.
+        if sin_theta < 1.0e-9 {
+            return sampling::orthonormal_basis(d / self.radius);
+        }
+        let phi = d.z.atan2(d.x);
+        let dpdu = Vector3::new(-d.z, 0.0, d.x) * (2.0 * PI);
+        let dpdv = Vector3::new(cos_theta * phi.cos(), -sin_theta, cos_theta * phi.sin()) * (PI * self.radius);
+        return (dpdu, dpdv);
+    }
+
+    // Uniform over the whole sphere.
+    fn sample_surface(&self, (u, v): (f64, f64), _time: f64) -> Option<SurfaceSample> {
+        let z = 1.0 - 2.0 * u;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        let normal = Vector3::new(r * phi.cos(), r * phi.sin(), z);
+        return Some(SurfaceSample {
+            point: self.centre + normal * self.radius,
+            normal,
+            pdf: 1.0 / (4.0 * PI * self.radius * self.radius),
+        });
+    }
+
+    fn surface_pdf(&self, _: Vector3, _time: f64) -> f64 {
+        return 1.0 / (4.0 * PI * self.radius * self.radius);
+    }
 }
@@ -0,0 +1,103 @@
+use vec3::Vector3;
+use std::rc::Rc;
+use shape::{Shape, SurfaceSample, Hit};
+use material::{Material, Channel};
+use texture::{Texture, Bump};
+use Ray;
+
+/// A shape made of a particular material, optionally with textures
+/// varying some of its parameters over the surface.
+pub struct Surface<S: Shape> {
+    pub shape: S,
+    pub material: Material,
+    pub textures: Vec<(Channel, Rc<dyn Texture>)>,
+    pub bump: Option<Bump>,
+}
+
+impl<S: Shape> Surface<S> {
+    pub fn new(shape: S, material: Material) -> Surface<S> {
+        Surface {
+            shape,
+            material,
+            textures: Vec::new(),
+            bump: None,
+        }
+    }
+
+    pub fn with_bump(mut self, bump: Bump) -> Surface<S> {
+        self.bump = Some(bump);
+        return self;
+    }
+
+    /// Drives `channel` of the material with `texture`, replacing its
+    /// value in the material.
+    pub fn with_texture(mut self, channel: Channel, texture: Rc<dyn Texture>) -> Surface<S> {
+        self.textures.push((channel, texture));
+        return self;
+    }
+}
+
+impl<S: Shape> Shape for Surface<S> {
+    fn intersect(&self, ray: Ray) -> Option<f64> {
+        return self.shape.intersect(ray);
+    }
+
+    fn normal(&self, point: Vector3, time: f64) -> Vector3 {
+        return self.shape.normal(point, time);
+    }
+
+    fn intersect_with_normal(&self, ray: Ray) -> Option<(f64, Vector3)> {
+        return self.shape.intersect_with_normal(ray);
+    }
+
+    fn hit(&self, ray: Ray) -> Option<Hit> {
+        let (dir, origin) = (ray.direction, ray.origin);
+        return self.shape.hit(ray).map(|hit| {
+            match self.bump {
+                Some(ref bump) => {
+                    Hit {
+                        shading_normal: bump.shading_normal(&hit,
+                                                            origin + dir * hit.t,
+                                                            &|p| self.shape.object_point(p, ray.time)),
+                        ..hit
+                    }
+                }
+                None => hit,
+            }
+        });
+    }
+
+    fn uv(&self, point: Vector3, time: f64) -> (f64, f64) {
+        return self.shape.uv(point, time);
+    }
+
+    fn tangents(&self, point: Vector3, time: f64) -> (Vector3, Vector3) {
+        return self.shape.tangents(point, time);
+    }
+
+    fn material(&self) -> Option<&Material> {
+        return Some(&self.material);
+    }
+
+    // Textures are looked up in the space of the shape this wraps, so solid
+    // textures move with it however it is placed.
+    fn material_at(&self, point: Vector3, uv: (f64, f64), footprint: f64, time: f64) -> Option<Material> {
+        let point = self.shape.object_point(point, time);
+        let material = self.textures.iter().fold(self.material, |material, &(channel, ref texture)| {
+            material.with_channel(channel, texture.filtered(uv, point, footprint))
+        });
+        return Some(material);
+    }
+
+    fn object_point(&self, point: Vector3, time: f64) -> Vector3 {
+        return self.shape.object_point(point, time);
+    }
+
+    fn sample_surface(&self, u: (f64, f64), time: f64) -> Option<SurfaceSample> {
+        return self.shape.sample_surface(u, time);
+    }
+
+    fn surface_pdf(&self, point: Vector3, time: f64) -> f64 {
+        return self.shape.surface_pdf(point, time);
+    }
+}
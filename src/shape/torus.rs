@@ -1,5 +1,6 @@
 use vec3::Vector3;
-use shape::{Shape, INTERSECT_EPSILON};
+use shape::{Shape, SurfaceSample, INTERSECT_EPSILON};
+use std::f64::consts::PI;
 use Ray;
 use cubic::solve_quartic_smallest_positive_real;
 
@@ -15,7 +16,8 @@ pub struct Torus {
 // tube_radius < raduis.
 
 impl Torus {
-    fn intersect_origin(&self, (b, a): Ray) -> Option<(f64)> {
+    fn intersect_origin(&self, ray: Ray) -> Option<f64> {
+        let (b, a) = (ray.direction, ray.origin);
         let a_dot_a = a.dot(a);
         let a_dot_b = a.dot(b);
 
@@ -27,9 +29,9 @@ impl Torus {
         let t3 = 4.0 * (k * a_dot_b + 2.0 * radius2 * a.z * b.z);
         let t4 = k * k + 4.0 * radius2 * (a.z * a.z - minor_radius2);
 
-        let s = solve_quartic_smallest_positive_real(1.0, t1, t2, t3, t4, INTERSECT_EPSILON);
+        let s = solve_quartic_smallest_positive_real(1.0, t1, t2, t3, t4, ray.t_min.max(INTERSECT_EPSILON));
 
-        return s;
+        return s.filter(|&s| ray.contains(s));
     }
 
     fn normal_origin(&self, point: Vector3) -> Vector3 {
@@ -39,23 +41,70 @@ impl Torus {
 }
 
 impl Shape for Torus {
-    fn intersect(&self, (b, a): Ray) -> Option<(f64)> {
-        return self.intersect_origin((b.rotate_inv(self.rotx, self.roty),
-                                      (a - self.center).rotate_inv(self.rotx, self.roty)));
+    fn intersect(&self, ray: Ray) -> Option<f64> {
+        return self.intersect_origin(ray.in_frame(ray.direction.rotate_inv(self.rotx, self.roty),
+                                                  (ray.origin - self.center).rotate_inv(self.rotx, self.roty)));
     }
 
-    fn normal(&self, _: Vector3) -> Vector3 {
-        unimplemented!();
+    fn normal(&self, point: Vector3, _time: f64) -> Vector3 {
+        let local_point = (point - self.center).rotate_inv(self.rotx, self.roty);
+        return self.normal_origin(local_point).rotate(self.rotx, self.roty);
     }
 
     fn intersect_with_normal(&self, ray: Ray) -> Option<(f64, Vector3)> {
-        let (dir, origin): Ray = ray;
+        let (dir, origin) = (ray.direction, ray.origin);
         let moved_dir = dir.rotate_inv(self.rotx, self.roty);
         let moved_origin = (origin - self.center).rotate_inv(self.rotx, self.roty);
 
-        return self.intersect_origin((moved_dir, moved_origin))
+        return self.intersect_origin(ray.in_frame(moved_dir, moved_origin))
             .map(|s| {
                 (s, self.normal_origin(moved_origin + (moved_dir * s)).rotate(self.rotx, self.roty))
             });
     }
+
+    // The angle around the ring and the angle around the tube, matching
+    // `sample_surface`.
+    fn uv(&self, point: Vector3, _time: f64) -> (f64, f64) {
+        let local_point = (point - self.center).rotate_inv(self.rotx, self.roty);
+        let ring_distance = (local_point.x * local_point.x + local_point.y * local_point.y).sqrt();
+        let turns = |angle: f64| (angle / (2.0 * PI)).rem_euclid(1.0);
+        return (turns(local_point.y.atan2(local_point.x)),
+                turns(local_point.z.atan2(ring_distance - self.radius)));
+    }
+
+    fn tangents(&self, point: Vector3, _time: f64) -> (Vector3, Vector3) {
+        let local_point = (point - self.center).rotate_inv(self.rotx, self.roty);
+        let ring_distance = (local_point.x * local_point.x + local_point.y * local_point.y).sqrt();
+        let theta = local_point.y.atan2(local_point.x);
+        let phi = local_point.z.atan2(ring_distance - self.radius);
+        let ring = Vector3::new(theta.cos(), theta.sin(), 0.0);
+        let around_ring = Vector3::new(-theta.sin(), theta.cos(), 0.0) * (2.0 * PI * ring_distance);
+        let around_tube = (ring * -phi.sin() + Vector3::new(0.0, 0.0, phi.cos())) *
+                          (2.0 * PI * self.tube_radius);
+        return (around_ring.rotate(self.rotx, self.roty), around_tube.rotate(self.rotx, self.roty));
+    }
+
+    // Uniform in the two angles, which is not quite uniform by area: the
+    // outside of the ring has more area than the inside.
+    fn sample_surface(&self, (u, v): (f64, f64), _time: f64) -> Option<SurfaceSample> {
+        let (sin_theta, cos_theta) = (2.0 * PI * u).sin_cos();
+        let (sin_phi, cos_phi) = (2.0 * PI * v).sin_cos();
+        let ring = Vector3::new(cos_theta, sin_theta, 0.0);
+        let normal = ring * cos_phi + Vector3::new(0.0, 0.0, sin_phi);
+        let point = ring * self.radius + normal * self.tube_radius;
+        let area_element = self.tube_radius * (self.radius + self.tube_radius * cos_phi);
+        return Some(SurfaceSample {
+            point: point.rotate(self.rotx, self.roty) + self.center,
+            normal: normal.rotate(self.rotx, self.roty),
+            pdf: 1.0 / (4.0 * PI * PI * area_element),
+        });
+    }
+
+    fn surface_pdf(&self, point: Vector3, _time: f64) -> f64 {
+        let local_point = (point - self.center).rotate_inv(self.rotx, self.roty);
+        let cos_phi = ((local_point.x * local_point.x + local_point.y * local_point.y).sqrt() -
+                       self.radius) / self.tube_radius;
+        let area_element = self.tube_radius * (self.radius + self.tube_radius * cos_phi);
+        return 1.0 / (4.0 * PI * PI * area_element);
+    }
 }
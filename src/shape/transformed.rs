@@ -0,0 +1,114 @@
+use vec3::Vector3;
+use shape::{Shape, SurfaceSample, Hit};
+use material::Material;
+use medium::Medium;
+use transform::Transform;
+use color::Color;
+use Ray;
+
+/// Places a shape in the world with an arbitrary affine transform.
+///
+/// The wrapped shape can be a reference, so one shape can be instanced
+/// many times with different transforms without being copied.
+pub struct Transformed<S: Shape> {
+    pub shape: S,
+    pub transform: Transform,
+}
+
+impl<S: Shape> Transformed<S> {
+    pub fn new(shape: S, transform: Transform) -> Transformed<S> {
+        Transformed {
+            shape,
+            transform,
+        }
+    }
+}
+
+impl<S: Shape> Shape for Transformed<S> {
+    fn intersect(&self, ray: Ray) -> Option<f64> {
+        let (local_ray, scale) = self.transform.ray_to_object(ray);
+        return self.shape.intersect(local_ray).map(|s| s * scale);
+    }
+
+    fn normal(&self, point: Vector3, time: f64) -> Vector3 {
+        let local_point = self.transform.inverse.transform_point(point);
+        return self.transform.normal(self.shape.normal(local_point, time));
+    }
+
+    fn intersect_with_normal(&self, ray: Ray) -> Option<(f64, Vector3)> {
+        let (local_ray, scale) = self.transform.ray_to_object(ray);
+        return self.shape
+            .intersect_with_normal(local_ray)
+            .map(|(s, normal)| (s * scale, self.transform.normal(normal)));
+    }
+
+    fn hit(&self, ray: Ray) -> Option<Hit> {
+        let (local_ray, scale) = self.transform.ray_to_object(ray);
+        return self.shape.hit(local_ray).map(|hit| {
+            Hit {
+                t: hit.t * scale,
+                normal: self.transform.normal(hit.normal),
+                shading_normal: self.transform.normal(hit.shading_normal),
+                dpdu: self.transform.vector(hit.dpdu),
+                dpdv: self.transform.vector(hit.dpdv),
+                ..hit
+            }
+        });
+    }
+
+    fn uv(&self, point: Vector3, time: f64) -> (f64, f64) {
+        return self.shape.uv(self.transform.inverse.transform_point(point), time);
+    }
+
+    fn tangents(&self, point: Vector3, time: f64) -> (Vector3, Vector3) {
+        let (dpdu, dpdv) = self.shape.tangents(self.transform.inverse.transform_point(point), time);
+        return (self.transform.vector(dpdu), self.transform.vector(dpdv));
+    }
+
+    fn color_diffuse(&self) -> Color {
+        return self.shape.color_diffuse();
+    }
+
+    fn color_ambient(&self) -> Color {
+        return self.shape.color_ambient();
+    }
+
+    fn material(&self) -> Option<&Material> {
+        return self.shape.material();
+    }
+
+    fn material_at(&self, point: Vector3, uv: (f64, f64), footprint: f64, time: f64) -> Option<Material> {
+        return self.shape.material_at(self.transform.inverse.transform_point(point), uv, footprint, time);
+    }
+
+    fn object_point(&self, point: Vector3, time: f64) -> Vector3 {
+        return self.shape.object_point(self.transform.inverse.transform_point(point), time);
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        return self.shape.medium();
+    }
+
+    fn density(&self, point: Vector3, time: f64) -> f64 {
+        return self.shape.density(self.transform.inverse.transform_point(point), time);
+    }
+
+    fn sample_surface(&self, u: (f64, f64), time: f64) -> Option<SurfaceSample> {
+        return self.shape.sample_surface(u, time).map(|s| {
+            SurfaceSample {
+                point: self.transform.point(s.point),
+                normal: self.transform.normal(s.normal),
+                pdf: s.pdf / self.transform.area_scale(s.normal),
+            }
+        });
+    }
+
+    fn surface_pdf(&self, point: Vector3, time: f64) -> f64 {
+        let local_point = self.transform.inverse.transform_point(point);
+        let pdf = self.shape.surface_pdf(local_point, time);
+        if pdf == 0.0 {
+            return 0.0;
+        }
+        return pdf / self.transform.area_scale(self.shape.normal(local_point, time));
+    }
+}
@@ -0,0 +1,135 @@
+use vec3::Vector3;
+use shape::{Shape, SurfaceSample, Hit, INTERSECT_EPSILON};
+use Ray;
+use sampling;
+
+/// A single triangle. The front faces the side from which the vertices
+/// run anticlockwise.
+pub struct Triangle {
+    pub vertices: [Vector3; 3],
+    /// Surface coordinates at each vertex, interpolated across the face.
+    pub uvs: [(f64, f64); 3],
+}
+
+impl Triangle {
+    pub fn new(a: Vector3, b: Vector3, c: Vector3) -> Triangle {
+        Triangle {
+            vertices: [a, b, c],
+            uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+        }
+    }
+
+    pub fn with_uvs(self, uvs: [(f64, f64); 3]) -> Triangle {
+        Triangle { uvs, ..self }
+    }
+
+    fn area(&self) -> f64 {
+        let [a, b, c] = self.vertices;
+        return (b - a).cross(c - a).len() / 2.0;
+    }
+
+    // Möller and Trumbore's test, giving the distance and the weights of
+    // the second and third vertices.
+    fn intersect_barycentric(&self, ray: Ray) -> Option<(f64, f64, f64)> {
+        let (dir, origin) = (ray.direction, ray.origin);
+        let [a, b, c] = self.vertices;
+        let (edge1, edge2) = (b - a, c - a);
+        let p = dir.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < 1.0e-12 {
+            // Ray is parallel to the triangle.
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+        let offset = origin - a;
+        let u = offset.dot(p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = offset.cross(edge1);
+        let v = dir.dot(q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) * inverse;
+        if t < INTERSECT_EPSILON || !ray.contains(t) {
+            return None;
+        }
+        return Some((t, u, v));
+    }
+
+    fn interpolate_uv(&self, u: f64, v: f64) -> (f64, f64) {
+        let w = 1.0 - u - v;
+        let [a, b, c] = self.uvs;
+        return (a.0 * w + b.0 * u + c.0 * v, a.1 * w + b.1 * u + c.1 * v);
+    }
+}
+
+impl Shape for Triangle {
+    fn intersect(&self, ray: Ray) -> Option<f64> {
+        return self.intersect_barycentric(ray).map(|(t, _, _)| t);
+    }
+
+    fn normal(&self, _: Vector3, _time: f64) -> Vector3 {
+        let [a, b, c] = self.vertices;
+        return (b - a).cross(c - a).normalize();
+    }
+
+    fn hit(&self, ray: Ray) -> Option<Hit> {
+        return self.intersect_barycentric(ray).map(|(t, u, v)| {
+            let normal = self.normal(self.vertices[0], ray.time);
+            let (dpdu, dpdv) = self.tangents(self.vertices[0], ray.time);
+            Hit {
+                t,
+                normal,
+                shading_normal: normal,
+                uv: self.interpolate_uv(u, v),
+                dpdu,
+                dpdv,
+            }
+        });
+    }
+
+    // Barycentric coordinates from the areas of the sub-triangles opposite
+    // each vertex.
+    fn uv(&self, point: Vector3, _time: f64) -> (f64, f64) {
+        let [a, b, c] = self.vertices;
+        let n = (b - a).cross(c - a);
+        let scale = 1.0 / n.dot(n);
+        let u = (point - a).cross(c - a).dot(n) * scale;
+        let v = (b - a).cross(point - a).dot(n) * scale;
+        return self.interpolate_uv(u, v);
+    }
+
+    // The edges expressed in terms of the change in surface coordinates
+    // along them.
+    fn tangents(&self, _: Vector3, _time: f64) -> (Vector3, Vector3) {
+        let [a, b, c] = self.vertices;
+        let [uv_a, uv_b, uv_c] = self.uvs;
+        let (du1, dv1) = (uv_b.0 - uv_a.0, uv_b.1 - uv_a.1);
+        let (du2, dv2) = (uv_c.0 - uv_a.0, uv_c.1 - uv_a.1);
+        let determinant = du1 * dv2 - dv1 * du2;
+        if determinant.abs() < 1.0e-12 {
+            return sampling::orthonormal_basis(self.normal(a, 0.0));
+        }
+        let (edge1, edge2) = (b - a, c - a);
+        let inverse = 1.0 / determinant;
+        return ((edge1 * dv2 - edge2 * dv1) * inverse, (edge2 * du1 - edge1 * du2) * inverse);
+    }
+
+    // Uniform by area.
+    fn sample_surface(&self, (u, v): (f64, f64), time: f64) -> Option<SurfaceSample> {
+        let [a, b, c] = self.vertices;
+        let root = u.sqrt();
+        let (wb, wc) = (root * (1.0 - v), root * v);
+        return Some(SurfaceSample {
+            point: a + (b - a) * wb + (c - a) * wc,
+            normal: self.normal(a, time),
+            pdf: 1.0 / self.area(),
+        });
+    }
+
+    fn surface_pdf(&self, _: Vector3, _time: f64) -> f64 {
+        return 1.0 / self.area();
+    }
+}
@@ -0,0 +1,47 @@
+use vec3::Vector3;
+use shape::{Shape, Hit};
+use medium::Medium;
+use Ray;
+
+/// A closed shape filled with a participating medium. The shape's surface
+/// is invisible and only bounds the medium. Density grids are in the
+/// volume's own space, so move the whole volume with `Transformed` to
+/// move them with it.
+///
+/// Media are rendered by `World::trace`; the path tracer sees straight
+/// through them.
+pub struct Volume<S: Shape> {
+    pub shape: S,
+    pub medium: Medium,
+}
+
+impl<S: Shape> Volume<S> {
+    pub fn new(shape: S, medium: Medium) -> Volume<S> {
+        Volume {
+            shape,
+            medium,
+        }
+    }
+}
+
+impl<S: Shape> Shape for Volume<S> {
+    fn intersect(&self, ray: Ray) -> Option<f64> {
+        return self.shape.intersect(ray);
+    }
+
+    fn normal(&self, point: Vector3, time: f64) -> Vector3 {
+        return self.shape.normal(point, time);
+    }
+
+    fn intersect_with_normal(&self, ray: Ray) -> Option<(f64, Vector3)> {
+        return self.shape.intersect_with_normal(ray);
+    }
+
+    fn hit(&self, ray: Ray) -> Option<Hit> {
+        return self.shape.hit(ray);
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        return Some(&self.medium);
+    }
+}
@@ -0,0 +1,152 @@
+// Light at single wavelengths, for spectral rendering: the CIE colour
+// matching functions, RGB colours as spectra, and refractive indices that
+// vary with wavelength. Wavelengths are in nanometres.
+
+use color;
+use color::Color;
+
+/// The visible range that wavelengths are sampled from.
+pub const WAVELENGTH_MIN: f64 = 380.0;
+pub const WAVELENGTH_MAX: f64 = 780.0;
+
+// The integral of the y colour matching function over the visible range.
+const Y_INTEGRAL: f64 = 106.9197;
+// The sRGB colour of a flat spectrum, which is balanced to white.
+const FLAT_WHITE: Color = Color {
+    red: 1.20055,
+    green: 0.94977,
+    blue: 0.90769,
+};
+// Half the width of the blend between neighbouring bands in `value`.
+const BAND_BLEND: f64 = 20.0;
+
+/// A wavelength from `u` in `[0, 1)`, uniformly over the visible range.
+pub fn sample_wavelength(u: f64) -> f64 {
+    return WAVELENGTH_MIN + u * (WAVELENGTH_MAX - WAVELENGTH_MIN);
+}
+
+// A Gaussian with different widths either side of its peak.
+fn lobe(x: f64, mean: f64, below: f64, above: f64) -> f64 {
+    let t = (x - mean) / if x < mean { below } else { above };
+    return (-0.5 * t * t).exp();
+}
+
+/// The CIE 1931 2° colour matching functions, as fitted by Wyman, Sloan
+/// and Shirley.
+pub fn cie_xyz(wavelength: f64) -> (f64, f64, f64) {
+    let l = wavelength;
+    let x = 1.056 * lobe(l, 599.8, 37.9, 31.0) + 0.362 * lobe(l, 442.0, 16.0, 26.7) -
+            0.065 * lobe(l, 501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(l, 568.8, 46.9, 40.5) + 0.286 * lobe(l, 530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(l, 437.0, 11.8, 36.0) + 0.681 * lobe(l, 459.0, 26.0, 13.8);
+    return (x, y, z);
+}
+
+/// The spectrum standing in for a linear RGB colour, at `wavelength`.
+///
+/// The visible range is split into smooth blue, green and red bands that
+/// sum to one everywhere, so grey is flat and colours are never negative.
+pub fn value(color: Color, wavelength: f64) -> f64 {
+    let rise = |edge: f64| {
+        let t = ((wavelength - edge + BAND_BLEND) / (2.0 * BAND_BLEND)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    };
+    let red = rise(590.0);
+    let blue = 1.0 - rise(490.0);
+    let green = 1.0 - red - blue;
+    return color.red * red + color.green * green + color.blue * blue;
+}
+
+/// The linear sRGB colour of radiance `value` at a wavelength picked by
+/// `sample_wavelength`. Averaged over wavelengths it estimates the colour
+/// of the whole spectrum, white balanced so a flat one is white.
+pub fn to_rgb(value: f64, wavelength: f64) -> Color {
+    let (x, y, z) = cie_xyz(wavelength);
+    let scale = value * (WAVELENGTH_MAX - WAVELENGTH_MIN) / Y_INTEGRAL;
+    let c = Color::from_xyz(x * scale, y * scale, z * scale);
+    return Color::new(c.red / FLAT_WHITE.red, c.green / FLAT_WHITE.green, c.blue / FLAT_WHITE.blue);
+}
+
+/// `color` at `wavelength` as a grey, for carrying spectral radiance in a
+/// `Color`; unchanged if there is no wavelength.
+pub fn at_wavelength(color: Color, wavelength: Option<f64>) -> Color {
+    return match wavelength {
+        Some(w) => color::WHITE * value(color, w),
+        None => color,
+    };
+}
+
+/// How a material's refractive index varies with wavelength, which splits
+/// white light into colours.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dispersion {
+    /// Cauchy's equation, `n = a + b / l^2` with `l` in micrometres.
+    Cauchy { a: f64, b: f64 },
+    /// The Sellmeier equation, `n^2 = 1 + sum(b l^2 / (l^2 - c))` with `l`
+    /// in micrometres.
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    /// Schott N-BK7, a common optical crown glass.
+    pub fn bk7() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    /// Schott SF10, a dense flint glass, which disperses much more than
+    /// crown glass.
+    pub fn flint() -> Dispersion {
+        Dispersion::Cauchy { a: 1.7280, b: 0.01342 }
+    }
+
+    pub fn ior(&self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.0) * (wavelength / 1000.0);
+        match *self {
+            Dispersion::Cauchy { a, b } => return a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                return (1.0 + sum).sqrt();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_spectrum() {
+    // The constants match the colour matching functions.
+    let n = 4000;
+    let step = (WAVELENGTH_MAX - WAVELENGTH_MIN) / n as f64;
+    let mut white = color::BLACK;
+    let mut y_integral = 0.0;
+    for i in 0..n {
+        let wavelength = WAVELENGTH_MIN + (i as f64 + 0.5) * step;
+        y_integral += cie_xyz(wavelength).1 * step;
+        white = white + to_rgb(value(color::WHITE, wavelength), wavelength) * (1.0 / n as f64);
+    }
+    assert!((y_integral - Y_INTEGRAL).abs() < 1.0e-3);
+    for &channel in [white.red, white.green, white.blue].iter() {
+        assert!((channel - 1.0).abs() < 1.0e-4);
+    }
+    // Spectra stay positive and primaries keep their hue.
+    for &c in [Color::new(1.0, 0.0, 0.0), Color::new(0.0, 1.0, 0.0), Color::new(0.0, 0.0, 1.0)].iter() {
+        let mut seen = color::BLACK;
+        for i in 0..n {
+            let wavelength = WAVELENGTH_MIN + (i as f64 + 0.5) * step;
+            assert!(value(c, wavelength) >= 0.0);
+            seen = seen + to_rgb(value(c, wavelength), wavelength);
+        }
+        let channels = [seen.red, seen.green, seen.blue];
+        let strongest = (0..3).max_by(|&a, &b| channels[a].partial_cmp(&channels[b]).unwrap()).unwrap();
+        assert_eq!([c.red, c.green, c.blue][strongest], 1.0);
+    }
+
+    // N-BK7 at the helium d line, and glass bends blue more than red.
+    assert!((Dispersion::bk7().ior(587.56) - 1.5168).abs() < 1.0e-4);
+    for glass in [Dispersion::bk7(), Dispersion::flint()].iter() {
+        assert!(glass.ior(450.0) > glass.ior(650.0));
+    }
+    assert_eq!(at_wavelength(Color::new(0.2, 0.4, 0.6), None), Color::new(0.2, 0.4, 0.6));
+}
@@ -1,11 +1,33 @@
 #![allow(unused_imports)]
 
-use super::vec3::Vector3;
-use super::shape::{Sphere, Shape, Plane, ORIGIN};
-use super::{trace, Ray};
+use super::vec3::{Vector3, Quaternion};
+use super::shape::{Sphere, Shape, Plane, Torus, Triangle, Transformed, Sdf, SdfShape, AlgebraicSurface, ORIGIN};
+use super::shape::sdf;
+use super::shape::{Light, PointLight, DirectionalLight, SpotLight, Falloff, RectLight, DiskLight, SphereLight};
+use super::sampling::{self, Rng};
+use super::shape::{Surface, Volume, Animated};
+use super::medium::{Medium, DensityGrid};
+use super::material::{Material, Channel, Bsdf};
+use super::spectrum::{self, Dispersion};
+use super::texture::{Texture, UvTexture, Checker, Bump, ImageTexture, Filter, Wrap};
+use super::path::PathTracer;
+use super::environment::{self, Environment, EnvironmentLight, EnvironmentMap, Sky};
+use super::image::Image;
+use std::rc::Rc;
+use super::color::Color;
+use super::color;
+use super::transform::{Transform, Keyframe, AnimatedTransform};
+use super::scene::Node;
+use super::screen::Shutter;
+use super::animation::{Track, Key, Curve};
+use super::sequence::FrameSequence;
+use super::ray::Differential;
+use super::microfacet;
+use super::{trace, screen, Ray};
 
 
 #[test]
+#[allow(clippy::assertions_on_constants)]
 fn testy() {
     assert!(true);
 }
@@ -26,7 +48,7 @@ fn test_plane() {
         normal: v1,
         origin_distance: 1.0,
     };
-    let (t, n) = plane.intersect_with_normal((v1, ORIGIN)).unwrap();
+    let (t, n) = plane.intersect_with_normal(Ray::new(v1, ORIGIN)).unwrap();
     assert_eq!(t, 1.0);
     assert_eq!(n, v2);
 }
@@ -38,7 +60,7 @@ fn test_trace_nearest() {
         normal: v,
         origin_distance: 1.0,
     };
-    trace::trace_nearest((v, ORIGIN), &vec![&p as &Shape]).unwrap();
+    trace::trace_nearest(Ray::new(v, ORIGIN), &[&p as &dyn Shape]).unwrap();
 }
 
 #[test]
@@ -49,7 +71,1362 @@ fn test_sphere() {
         centre: ORIGIN,
         radius: 1.0,
     };
-    let r: Ray = (v2, v1);
+    let r = Ray::new(v2, v1);
     let d = s.intersect(r).unwrap();
     assert_eq!(d, 2.0);
 }
+
+#[test]
+fn test_matrix_inverse() {
+    let t = Transform::translate(Vector3::new(1.0, 2.0, 3.0)) *
+            Transform::rotate(Vector3::new(1.0, 1.0, 0.0), 0.7) *
+            Transform::scale(Vector3::new(2.0, 0.5, 3.0));
+    let inverse = t.matrix.inverse().unwrap();
+    let p = Vector3::new(0.3, -1.2, 4.0);
+    assert!((inverse.transform_point(t.point(p)) - p).len() < 1.0e-12);
+    assert!((t.inverse.transform_point(t.point(p)) - p).len() < 1.0e-12);
+}
+
+#[test]
+fn test_transformed_sphere() {
+    let s = Sphere {
+        centre: ORIGIN,
+        radius: 1.0,
+    };
+    // Stretched to an ellipsoid with semi-axis 2 along x, then moved to x = 5.
+    let t = Transformed::new(&s,
+                             Transform::translate(Vector3::new(5.0, 0.0, 0.0)) *
+                             Transform::scale(Vector3::new(2.0, 1.0, 1.0)));
+    let (d, n) = t.intersect_with_normal(Ray::new(Vector3::new(1.0, 0.0, 0.0), ORIGIN)).unwrap();
+    assert!((d - 3.0).abs() < 1.0e-12);
+    assert!((n - Vector3::new(-1.0, 0.0, 0.0)).len() < 1.0e-12);
+    // The normal of a scaled sphere is not radial.
+    let (_, n) = t.intersect_with_normal(Ray::new(Vector3::new(0.0, 0.0, 1.0), Vector3::new(6.0, 0.0, -5.0)))
+        .unwrap();
+    let expected = Vector3::new(1.0 / 4.0, 0.0, -(0.75f64).sqrt()).normalize();
+    assert!((n - expected).len() < 1.0e-12);
+}
+
+#[test]
+fn test_ray_interval() {
+    use super::shape::algebraic::term;
+
+    let unit = Sphere {
+        centre: ORIGIN,
+        radius: 1.0,
+    };
+    let ray = Ray::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(-3.0, 0.0, 0.0));
+    assert_eq!(ray.inv_direction(), Vector3::new(1.0, 1.0 / 0.0, 1.0 / 0.0));
+    // Starting past the near side finds the far side; stopping short finds nothing.
+    assert_eq!(unit.intersect(ray.with_interval(2.5, 10.0)), Some(4.0));
+    assert_eq!(unit.intersect(ray.with_t_max(1.5)), None);
+    assert_eq!(unit.intersect(ray.with_interval(4.5, 10.0)), None);
+
+    // The interval is measured along the world ray through a scaled shape.
+    let stretched = Transformed::new(&unit, Transform::scale(Vector3::new(2.0, 1.0, 1.0)));
+    assert!((stretched.intersect(ray).unwrap() - 1.0).abs() < 1.0e-12);
+    assert!((stretched.intersect(ray.with_interval(1.5, 10.0)).unwrap() - 5.0).abs() < 1.0e-12);
+    assert_eq!(stretched.intersect(ray.with_t_max(0.5)), None);
+
+    let ball = SdfShape::new(sdf::Sphere { radius: 1.0 });
+    assert_eq!(ball.intersect(ray.with_t_max(1.5)), None);
+    let plane = Plane {
+        normal: Vector3::new(1.0, 0.0, 0.0),
+        origin_distance: 0.0,
+    };
+    assert_eq!(plane.intersect(ray.with_t_max(3.0)), None);
+    let triangle = Triangle::new(Vector3::new(0.0, -1.0, -1.0),
+                                 Vector3::new(0.0, 1.0, -1.0),
+                                 Vector3::new(0.0, 0.0, 1.0));
+    assert_eq!(triangle.intersect(ray.with_t_max(3.0)), None);
+    assert!(triangle.intersect(ray.with_t_max(3.5)).is_some());
+
+    // Quartics and general polynomial surfaces take only the roots inside
+    // the interval, even just either side of a bound.
+    let close = |t: Option<f64>, expected: f64| t.is_some_and(|t| (t - expected).abs() < 1.0e-9);
+    let torus = Torus {
+        center: ORIGIN,
+        radius: 1.0,
+        tube_radius: 0.25,
+        rotx: 0.0,
+        roty: 0.0,
+    };
+    assert!(close(torus.intersect(ray), 1.75));
+    assert!(close(torus.intersect(ray.with_t_max(1.76)), 1.75));
+    assert_eq!(torus.intersect(ray.with_t_max(1.74)), None);
+    assert!(close(torus.intersect(ray.with_interval(1.74, 10.0)), 1.75));
+    assert!(close(torus.intersect(ray.with_interval(1.76, 10.0)), 2.25));
+    assert!(close(torus.intersect(ray.with_interval(2.26, 10.0)), 3.75));
+    assert_eq!(torus.intersect(ray.with_interval(2.26, 3.74)), None);
+    let algebraic = AlgebraicSurface::new(vec![term(1.0, 2, 0, 0),
+                                               term(1.0, 0, 2, 0),
+                                               term(1.0, 0, 0, 2),
+                                               term(-1.0, 0, 0, 0)]);
+    assert!(close(algebraic.intersect(ray.with_t_max(2.01)), 2.0));
+    assert_eq!(algebraic.intersect(ray.with_t_max(1.99)), None);
+    assert!(close(algebraic.intersect(ray.with_interval(1.99, 10.0)), 2.0));
+    assert!(close(algebraic.intersect(ray.with_interval(2.01, 10.0)), 4.0));
+    assert!(close(algebraic.intersect(ray.with_interval(2.01, 4.01)), 4.0));
+    assert_eq!(algebraic.intersect(ray.with_interval(2.01, 3.99)), None);
+    assert_eq!(algebraic.intersect(ray.with_interval(4.01, 10.0)), None);
+
+    // A shadow ray stops at the light, so shapes behind it do not block it.
+    let world = trace::World {
+        shapes: vec![&unit],
+        lights: vec![],
+        fog: None,
+    };
+    assert!(world.trace_collision(ray.with_t_max(1.5)));
+    assert!(!world.trace_collision(ray));
+    let (_, hit) = world.nearest_hit(ray.with_interval(3.0, 10.0)).unwrap();
+    assert_eq!(hit.t, 4.0);
+}
+
+#[test]
+fn test_quaternion() {
+    let axis = Vector3::new(1.0, -2.0, 0.5);
+    let q = Quaternion::from_axis_angle(axis, 1.1);
+    let m = super::transform::Matrix4::rotation(axis, 1.1);
+    let v = Vector3::new(0.2, 3.0, -1.0);
+    assert!((q.rotate(v) - m.transform_vector(v)).len() < 1.0e-12);
+    assert!((q.to_matrix().transform_vector(v) - m.transform_vector(v)).len() < 1.0e-12);
+
+    let back = Quaternion::from_matrix(&m);
+    assert!((back.dot(q).abs() - 1.0).abs() < 1.0e-12);
+
+    // Composition applies the right hand rotation first.
+    let z = Quaternion::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), ::std::f64::consts::FRAC_PI_2);
+    let x = Quaternion::from_axis_angle(Vector3::new(1.0, 0.0, 0.0), ::std::f64::consts::FRAC_PI_2);
+    let r = (x * z).rotate(Vector3::new(1.0, 0.0, 0.0));
+    assert!((r - Vector3::new(0.0, 0.0, 1.0)).len() < 1.0e-12);
+}
+
+#[test]
+fn test_slerp() {
+    let a = Quaternion::identity();
+    let b = Quaternion::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), 2.0);
+    let (axis, angle) = a.slerp(b, 0.25).to_axis_angle();
+    assert!((angle - 0.5).abs() < 1.0e-12);
+    assert!((axis - Vector3::new(0.0, 1.0, 0.0)).len() < 1.0e-12);
+    assert_eq!(a.slerp(b, 0.0), a);
+    // Takes the short way round even if the end point has the opposite sign.
+    let (_, angle) = a.slerp(b * -1.0, 0.5).to_axis_angle();
+    assert!((angle - 1.0).abs() < 1.0e-12);
+}
+
+#[test]
+fn test_scene_graph() {
+    let s = Sphere {
+        centre: ORIGIN,
+        radius: 1.0,
+    };
+    let wheel = |name| Node::new(name).with_shape(&s);
+    let mut car = Node::new("car")
+        .with_child(wheel("front").with_transform(Transform::translate(Vector3::new(0.0, 0.0, 2.0))))
+        .with_child(wheel("back").with_transform(Transform::translate(Vector3::new(0.0, 0.0, -2.0))))
+        .with_camera(screen::default_camera());
+    car.find_mut("car").unwrap().transform = Transform::translate(Vector3::new(10.0, 0.0, 0.0));
+    assert!(car.find("back").is_some());
+    assert!(car.find("boot").is_none());
+
+    let flat = car.flatten();
+    assert_eq!(flat.shapes.len(), 2);
+    assert_eq!(flat.camera("car").unwrap().eye, Vector3::new(10.0, 0.0, screen::VIEW_Z));
+    let world = flat.world();
+    let (_, d, _) = world.trace_nearest(Ray::new(Vector3::new(0.0, 0.0, 1.0), Vector3::new(10.0, 0.0, 0.0)))
+        .unwrap();
+    assert!((d - 1.0).abs() < 1.0e-12);
+    assert!(world.trace_nearest(Ray::new(Vector3::new(0.0, 0.0, 1.0), ORIGIN)).is_none());
+}
+
+#[test]
+fn test_sdf_shape() {
+    let ball = SdfShape::new(sdf::Sphere { radius: 1.0 });
+    let ray = Ray::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(-3.0, 0.0, 0.0));
+    let (d, n) = ball.intersect_with_normal(ray).unwrap();
+    assert!((d - 2.0).abs() < 1.0e-4);
+    assert!((n - Vector3::new(-1.0, 0.0, 0.0)).len() < 1.0e-4);
+    // A ray leaving the surface does not hit it again.
+    assert!(ball.intersect(Ray::new(Vector3::new(-1.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0))).is_none());
+
+    // Two blobs blend together where plain union leaves a crease.
+    let blob = |x| move |p: Vector3| (p - Vector3::new(x, 0.0, 0.0)).len() - 1.0;
+    let smooth = sdf::SmoothUnion {
+        a: blob(-1.0),
+        b: blob(1.0),
+        k: 0.5,
+    };
+    let plain = sdf::Union(blob(-1.0), blob(1.0));
+    assert!(smooth.distance(Vector3::new(0.0, 0.5, 0.0)) < plain.distance(Vector3::new(0.0, 0.5, 0.0)));
+    assert_eq!(smooth.distance(Vector3::new(3.0, 0.0, 0.0)), 1.0);
+
+    let holed = SdfShape::new(sdf::Subtraction(sdf::Sphere { radius: 1.0 }, sdf::Sphere { radius: 0.5 }));
+    let d = holed.intersect(Ray::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0))).unwrap();
+    assert!((d - 0.5).abs() < 1.0e-4);
+}
+
+#[test]
+fn test_algebraic_surface() {
+    use super::shape::algebraic::{term, multiply};
+
+    let unit_sphere = AlgebraicSurface::new(vec![term(1.0, 2, 0, 0),
+                                                 term(1.0, 0, 2, 0),
+                                                 term(1.0, 0, 0, 2),
+                                                 term(-1.0, 0, 0, 0)]);
+    let ray = Ray::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(-3.0, 0.0, 0.0));
+    let (d, n) = unit_sphere.intersect_with_normal(ray).unwrap();
+    assert!((d - 2.0).abs() < 1.0e-9);
+    assert!((n - Vector3::new(-1.0, 0.0, 0.0)).len() < 1.0e-9);
+
+    // (x^2 + y^2 + z^2 + R^2 - r^2)^2 - 4 R^2 (x^2 + y^2) matches the hand derived torus.
+    let (radius, tube_radius) = (0.8, 0.25);
+    let s = [term(1.0, 2, 0, 0),
+             term(1.0, 0, 2, 0),
+             term(1.0, 0, 0, 2),
+             term(radius * radius - tube_radius * tube_radius, 0, 0, 0)];
+    let mut terms = multiply(&s, &s);
+    terms.push(term(-4.0 * radius * radius, 2, 0, 0));
+    terms.push(term(-4.0 * radius * radius, 0, 2, 0));
+    let algebraic = AlgebraicSurface::new(terms);
+    assert_eq!(algebraic.degree(), 4);
+    let torus = Torus {
+        center: ORIGIN,
+        radius,
+        tube_radius,
+        rotx: 0.0,
+        roty: 0.0,
+    };
+    let ray = Ray::new(Vector3::new(0.05, 0.0, 1.0).normalize(), Vector3::new(0.5, 0.3, -3.0));
+    let (d1, n1) = algebraic.intersect_with_normal(ray).unwrap();
+    let (d2, n2) = torus.intersect_with_normal(ray).unwrap();
+    assert!((d1 - d2).abs() < 1.0e-6);
+    assert!((n1 - n2).len() < 1.0e-6);
+    // From far away the constant term dwarfs the others, but it is still a quartic.
+    let far = Ray::new(ray.direction, ray.origin - ray.direction * 1000.0);
+    assert_eq!(algebraic.ray_polynomial(far).degree(), 4);
+    assert!((algebraic.intersect(far).unwrap() - 1000.0 - d2).abs() < 1.0e-3);
+
+    let tanglecube = AlgebraicSurface::goursat(0.0, -5.0, 11.8);
+    let p = Vector3::new(0.3, -0.2, 0.7);
+    let h = 1.0e-6;
+    let numeric = (tanglecube.value(p + Vector3::new(h, 0.0, 0.0)) -
+                   tanglecube.value(p - Vector3::new(h, 0.0, 0.0))) / (2.0 * h);
+    assert!((tanglecube.gradient(p).x - numeric).abs() < 1.0e-6);
+
+    // Higher degrees go through the same root isolation.
+    let barth = AlgebraicSurface::barth_sextic();
+    assert_eq!(barth.degree(), 6);
+    let ray = Ray::new(Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.1, 0.2, -3.0));
+    let d = barth.intersect(ray).unwrap();
+    assert!(barth.value(Vector3::new(0.1, 0.2, -3.0 + d)).abs() < 1.0e-9);
+}
+
+#[test]
+fn test_lights() {
+    let point = PointLight {
+        position: Vector3::new(0.0, 2.0, 0.0),
+        color: color::WHITE,
+        intensity: 4.0,
+        falloff: Falloff::InverseSquare,
+    };
+    let sample = point.illuminate(ORIGIN, (0.5, 0.5)).unwrap();
+    assert_eq!(sample.direction, Vector3::new(0.0, 1.0, 0.0));
+    assert_eq!(sample.distance, 2.0);
+    assert_eq!(sample.color, color::WHITE);
+    let windowed = PointLight { falloff: Falloff::Windowed { radius: 1.5 }, ..point };
+    assert!(windowed.illuminate(ORIGIN, (0.5, 0.5)).is_none());
+
+    let sun = DirectionalLight {
+        direction: Vector3::new(0.0, -2.0, 0.0),
+        color: color::WHITE,
+        irradiance: 1.0,
+    };
+    let sample = sun.illuminate(Vector3::new(5.0, -3.0, 1.0), (0.5, 0.5)).unwrap();
+    assert_eq!(sample.direction, Vector3::new(0.0, 1.0, 0.0));
+    assert!(sample.distance.is_infinite());
+
+    let spot = SpotLight {
+        position: Vector3::new(0.0, 1.0, 0.0),
+        direction: Vector3::new(0.0, -1.0, 0.0),
+        color: color::WHITE,
+        intensity: 1.0,
+        falloff: Falloff::None,
+        cone_angle: ::std::f64::consts::FRAC_PI_4,
+        penumbra_angle: 0.2,
+    };
+    // Full intensity on the axis, nothing outside the cone, partial in the penumbra.
+    assert_eq!(spot.illuminate(ORIGIN, (0.5, 0.5)).unwrap().color, color::WHITE);
+    assert!(spot.illuminate(Vector3::new(1.5, 0.0, 0.0), (0.5, 0.5)).is_none());
+    let edge = spot.illuminate(Vector3::new(0.9, 0.0, 0.0), (0.5, 0.5)).unwrap().color.red;
+    assert!(edge > 0.0 && edge < 1.0);
+
+    let moved = spot.transformed(&Transform::translate(Vector3::new(3.0, 0.0, 0.0)));
+    assert!(moved.illuminate(ORIGIN, (0.5, 0.5)).is_none());
+    assert!(moved.illuminate(Vector3::new(3.0, 0.0, 0.0), (0.5, 0.5)).is_some());
+}
+
+// Average irradiance from a light over many stratified samples.
+fn irradiance(light: &dyn Light, point: Vector3, normal: Vector3, n: u32) -> f64 {
+    let mut rng = Rng::new(1);
+    let total: f64 = sampling::stratified(n, &mut rng)
+        .into_iter()
+        .filter_map(|u| light.illuminate(point, u))
+        .map(|sample| sample.color.red * sample.direction.dot(normal).max(0.0))
+        .sum();
+    return total / n as f64;
+}
+
+#[test]
+fn test_area_lights() {
+    let up = Vector3::new(0.0, 1.0, 0.0);
+    let pi = ::std::f64::consts::PI;
+    // Disk of radius 1 at height 2 facing down: E = pi L R^2 / (h^2 + R^2).
+    let disk = DiskLight {
+        position: Vector3::new(0.0, 2.0, 0.0),
+        normal: -up,
+        radius: 1.0,
+        color: color::WHITE,
+        radiance: 1.0,
+        samples: 16,
+    };
+    assert!((irradiance(&disk, ORIGIN, up, 4096) - pi / 5.0).abs() < 1.0e-3);
+    // The back of the disk is dark.
+    assert!(disk.illuminate(Vector3::new(0.0, 3.0, 0.0), (0.5, 0.5)).is_none());
+
+    // Sphere of radius 1 at distance 3: E = pi L (R / d)^2.
+    let sphere = SphereLight {
+        position: Vector3::new(0.0, 3.0, 0.0),
+        radius: 1.0,
+        color: color::WHITE,
+        radiance: 1.0,
+        samples: 16,
+    };
+    assert!((irradiance(&sphere, ORIGIN, up, 4096) - pi / 9.0).abs() < 1.0e-3);
+    let sample = sphere.illuminate(ORIGIN, (0.0, 0.0)).unwrap();
+    assert!((sample.distance - 2.0).abs() < 1.0e-9);
+
+    // A small rectangle looks like a point light of intensity L * A.
+    let rect = RectLight {
+        corner: Vector3::new(-0.005, 10.0, -0.005),
+        edge_u: Vector3::new(0.01, 0.0, 0.0),
+        edge_v: Vector3::new(0.0, 0.0, 0.01),
+        color: color::WHITE,
+        radiance: 1.0e4,
+        samples: 4,
+    };
+    assert!((irradiance(&rect, ORIGIN, up, 16) - 1.0 / 100.0).abs() < 1.0e-6);
+
+    // Half of a big rectangle is hidden from the origin by the edge of a
+    // blocker, giving a penumbra rather than a hard shadow.
+    let big = RectLight {
+        corner: Vector3::new(-1.0, 2.0, -1.0),
+        edge_u: Vector3::new(2.0, 0.0, 0.0),
+        edge_v: Vector3::new(0.0, 0.0, 2.0),
+        radiance: 1.0,
+        samples: 64,
+        ..rect
+    };
+    let floor = Sphere {
+        centre: Vector3::new(0.0, -100.0, 0.0),
+        radius: 100.0,
+    };
+    let blocker = Sphere {
+        centre: Vector3::new(100.0, 1.0, 0.0),
+        radius: 100.0,
+    };
+    let ray = Ray::new(Vector3::new(1.0, -1.0, 0.0).normalize(), Vector3::new(-1.0, 1.0, 0.0));
+    let lit = trace::World {
+        shapes: vec![&floor as &dyn Shape],
+        lights: vec![&big as &dyn Light],
+        fog: None,
+    };
+    let shaded = trace::World {
+        shapes: vec![&floor as &dyn Shape, &blocker],
+        lights: vec![&big as &dyn Light],
+        fog: None,
+    };
+    let ambient = trace::K_AMBIENT;
+    let fraction = (shaded.trace(ray).red - ambient) / (lit.trace(ray).red - ambient);
+    assert!(fraction > 0.4 && fraction < 0.6);
+}
+
+#[test]
+fn test_emissive_shapes() {
+    let glow = Material::emissive(color::WHITE, 1024);
+    // A sphere of radius 1 with radiance 1, at distance 3 from the origin,
+    // and the same sphere made by scaling a smaller one.
+    let lamp = Surface::new(Sphere {
+                                centre: Vector3::new(0.0, 3.0, 0.0),
+                                radius: 1.0,
+                            },
+                            glow);
+    let scaled_lamp = Transformed::new(Surface::new(Sphere { centre: ORIGIN, radius: 0.5 }, glow),
+                                       Transform::translate(Vector3::new(0.0, 3.0, 0.0)) *
+                                       Transform::uniform_scale(2.0));
+    // Lying flat, with its hole above the origin.
+    let torus_lamp = Transformed::new(Surface::new(Torus {
+                                                       center: ORIGIN,
+                                                       radius: 1.0,
+                                                       tube_radius: 0.25,
+                                                       rotx: 0.0,
+                                                       roty: 0.0,
+                                                   },
+                                                   glow),
+                                      Transform::translate(Vector3::new(0.0, 3.0, 0.0)) *
+                                      Transform::rotate(Vector3::new(1.0, 0.0, 0.0),
+                                                        ::std::f64::consts::FRAC_PI_2));
+    let floor = Sphere {
+        centre: Vector3::new(0.0, -100.0, 0.0),
+        radius: 100.0,
+    };
+    let down = Ray::new(Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+
+    // The lamp is visible to camera rays.
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape, &lamp],
+        lights: vec![],
+        fog: None,
+    };
+    let up = Ray::new(Vector3::new(0.0, 1.0, 0.0), ORIGIN);
+    assert_eq!(world.trace(up), color::WHITE);
+
+    // It lights the red floor with irradiance pi (R / d)^2, so the floor's
+    // radiance is (R / d)^2 on top of the ambient term.
+    for lamp in [&lamp as &dyn Shape, &scaled_lamp].iter() {
+        let world = trace::World {
+            shapes: vec![&floor as &dyn Shape, *lamp],
+            lights: vec![],
+            fog: None,
+        };
+        let lit = world.trace(down).red - trace::K_AMBIENT;
+        assert!((lit - 1.0 / 9.0).abs() < 2.0e-3);
+    }
+
+    // A torus lamp lights the floor too, and the floor sees through its hole.
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape, &torus_lamp],
+        lights: vec![],
+        fog: None,
+    };
+    assert!(world.trace(down).red > trace::K_AMBIENT);
+    assert_eq!(world.trace(up).red, 0.0);
+}
+
+#[test]
+fn test_path_tracer() {
+    let path_tracer = PathTracer::new(4000);
+    let floor = Sphere {
+        centre: Vector3::new(0.0, -100.0, 0.0),
+        radius: 100.0,
+    };
+    let down = Ray::new(Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+
+    // Nothing can be lit twice on a convex floor, so with a point light the
+    // path tracer gives exactly the direct light.
+    let point = PointLight {
+        position: Vector3::new(0.5, 2.0, 0.0),
+        color: color::WHITE,
+        intensity: 3.0,
+        falloff: Falloff::InverseSquare,
+    };
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape],
+        lights: vec![&point as &dyn Light],
+        fog: None,
+    };
+    let direct = world.trace(down).red - trace::K_AMBIENT;
+    assert!((path_tracer.trace(&world, down).red - direct).abs() < 1.0e-9);
+
+    // The floor under a spherical lamp, found both by sampling the lamp and
+    // by bouncing off the floor; see `test_emissive_shapes`.
+    let lamp = Surface::new(Sphere {
+                                centre: Vector3::new(0.0, 3.0, 0.0),
+                                radius: 1.0,
+                            },
+                            Material::emissive(color::WHITE, 1));
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape, &lamp],
+        lights: vec![],
+        fog: None,
+    };
+    assert!((path_tracer.trace(&world, down).red - 1.0 / 9.0).abs() < 2.0e-3);
+
+    // Inside a grey sphere of albedo a lit from its centre, light bounces
+    // around for ever. With direct irradiance E the walls have radiance
+    // a E / (pi (1 - a)), which is 1 here.
+    let room = Surface::new(Sphere {
+                                centre: ORIGIN,
+                                radius: 1.0,
+                            },
+                            Material::diffuse(Color::new(0.5, 0.5, 0.5)));
+    let bulb = PointLight {
+        intensity: ::std::f64::consts::PI,
+        position: ORIGIN,
+        ..point
+    };
+    let world = trace::World {
+        shapes: vec![&room as &dyn Shape],
+        lights: vec![&bulb as &dyn Light],
+        fog: None,
+    };
+    let radiance = path_tracer.trace(&world, Ray::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.5)));
+    assert!((radiance.green - 1.0).abs() < 0.05);
+}
+
+#[test]
+fn test_glossy_highlight() {
+    let light = PointLight {
+        position: Vector3::new(0.0, 0.0, 5.0),
+        color: color::WHITE,
+        intensity: 16.0,
+        falloff: Falloff::InverseSquare,
+    };
+    let ball = Sphere {
+        centre: ORIGIN,
+        radius: 1.0,
+    };
+    let grey = Color::new(0.5, 0.5, 0.5);
+    let matte = Surface::new(&ball, Material::diffuse(grey));
+    let glossy = Surface::new(&ball, Material::microfacet(grey, 0.2, 0.0));
+    let rough = Surface::new(&ball, Material::microfacet(grey, 1.0, 0.0));
+    let ray = Ray::new(Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 5.0));
+    let shade = |shape: &dyn Shape| {
+        let world = trace::World {
+            shapes: vec![shape],
+            lights: vec![&light as &dyn Light],
+            fog: None,
+        };
+        return world.trace(ray).red;
+    };
+    // Looking straight down the reflected light, a smooth surface shows a
+    // bright highlight that a rough one spreads out.
+    assert!(shade(&glossy) > 2.0 * shade(&matte));
+    assert!(shade(&rough) < shade(&glossy));
+    // The path tracer sees the same highlight.
+    let world = trace::World {
+        shapes: vec![&glossy as &dyn Shape],
+        lights: vec![&light as &dyn Light],
+        fog: None,
+    };
+    let path_traced = PathTracer::new(1).trace(&world, ray).red;
+    assert!((path_traced - (shade(&glossy) - 0.5 * trace::K_AMBIENT)).abs() < 1.0e-9);
+}
+
+#[test]
+fn test_ambient_occlusion() {
+    let floor = Sphere {
+        centre: Vector3::new(0.0, -100.0, 0.0),
+        radius: 100.0,
+    };
+    let ball = Sphere {
+        centre: Vector3::new(0.0, 1.0, 0.0),
+        radius: 1.0,
+    };
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape, &ball],
+        lights: vec![],
+        fog: None,
+    };
+    let occlusion = trace::AmbientOcclusion {
+        samples: 64,
+        max_distance: 2.0,
+    };
+    let up = Vector3::new(0.0, 1.0, 0.0);
+    let mut rng = Rng::new(1);
+    let visibility = |x: f64, rng: &mut Rng| world.occlusion(Vector3::new(x, 0.0, 0.0), 0.0, up, &occlusion, rng);
+    // Open floor is fully visible; near where the ball touches it is darker.
+    assert_eq!(visibility(5.0, &mut rng), 1.0);
+    let near = visibility(0.3, &mut rng);
+    let further = visibility(1.5, &mut rng);
+    assert!(near < further && further < 1.0);
+
+    // With a short reach only close shapes occlude.
+    let short = trace::AmbientOcclusion {
+        max_distance: 0.1,
+        ..occlusion
+    };
+    assert_eq!(world.occlusion(Vector3::new(1.5, 0.0, 0.0), 0.0, up, &short, &mut rng), 1.0);
+    // Without samples nothing is occluded.
+    let none = trace::AmbientOcclusion {
+        samples: 0,
+        ..occlusion
+    };
+    assert_eq!(world.occlusion(Vector3::new(0.3, 0.0, 0.0), 0.0, up, &none, &mut rng), 1.0);
+
+    // The ambient term is scaled by the visibility, and the pass shows it alone.
+    let down = Ray::new(-up, Vector3::new(1.5, 5.0, 0.0));
+    let pass = world.trace_with(&trace::Integrator::Occlusion(occlusion), down);
+    let shaded = world.trace_with(&trace::Integrator::DirectWithOcclusion(occlusion), down);
+    assert!(pass.red < 1.0 && pass.red == pass.green);
+    assert!((shaded.red - trace::K_AMBIENT * pass.red).abs() < 1.0e-12);
+}
+
+#[test]
+fn test_environment_light() {
+    let floor = Surface::new(Sphere {
+                                 centre: Vector3::new(0.0, -100.0, 0.0),
+                                 radius: 100.0,
+                             },
+                             Material::diffuse(Color::new(0.5, 0.5, 0.5)));
+    let sky = EnvironmentLight::new(Rc::new(environment::Constant(color::WHITE)), 32, 16, 256);
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape],
+        lights: vec![&sky as &dyn Light],
+        fog: None,
+    };
+    // The background is visible where rays miss, both ways.
+    let up = Ray::new(Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+    assert_eq!(world.trace(up), color::WHITE);
+    assert_eq!(PathTracer::new(1).trace(&world, up), color::WHITE);
+
+    // A floor open to a uniform sky has irradiance pi, and so radiance equal
+    // to its albedo.
+    let down = Ray::new(Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+    let direct = world.trace(down).red - 0.5 * trace::K_AMBIENT;
+    assert!((direct - 0.5).abs() < 0.02);
+    let path_traced = PathTracer::new(2000).trace(&world, down).red;
+    assert!((path_traced - 0.5).abs() < 0.02);
+
+    // Importance sampling a map with one bright texel always finds it.
+    let mut image = Image::new(8, 4);
+    image.set(5, 1, Color::new(10.0, 10.0, 10.0));
+    let map = EnvironmentLight::for_map(EnvironmentMap { image, scale: 1.0 }, 1);
+    let rotated = map.transformed(&Transform::rotate(Vector3::new(0.0, 1.0, 0.0), 1.0));
+    let mut rng = Rng::new(2);
+    for _ in 0..100 {
+        let u = (rng.next_f64(), rng.next_f64());
+        for light in [&map as &dyn Light, &*rotated].iter() {
+            let sample = light.illuminate(ORIGIN, u).unwrap();
+            assert!(light.background(sample.direction) != color::BLACK);
+            let pdf = light.pdf(ORIGIN, sample.direction);
+            let radiance = light.background(sample.direction);
+            assert!((sample.color.red * pdf - radiance.red).abs() < 1.0e-9);
+        }
+    }
+}
+
+#[test]
+fn test_spectral() {
+    let sky = EnvironmentLight::new(Rc::new(environment::Constant(color::WHITE)), 32, 16, 256);
+    let grey = Surface::new(Sphere {
+                                centre: Vector3::new(0.0, -100.0, 0.0),
+                                radius: 100.0,
+                            },
+                            Material::diffuse(Color::new(0.5, 0.5, 0.5)));
+    let red = Surface::new(&grey.shape, Material::diffuse(Color::new(0.5, 0.0, 0.0)));
+    let spectral = PathTracer::new(2000).with_spectral(true);
+    let down = Ray::new(Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+    // A grey floor under a white sky looks the same as when rendered in RGB,
+    // and a red one stays red.
+    let world = trace::World {
+        shapes: vec![&grey as &dyn Shape],
+        lights: vec![&sky as &dyn Light],
+        fog: None,
+    };
+    let seen = spectral.trace(&world, down);
+    for &channel in [seen.red, seen.green, seen.blue].iter() {
+        assert!((channel - 0.5).abs() < 0.03, "{:?}", seen);
+    }
+    let world = trace::World {
+        shapes: vec![&red as &dyn Shape],
+        lights: vec![&sky as &dyn Light],
+        fog: None,
+    };
+    let seen = spectral.trace(&world, down);
+    assert!(seen.red > 0.3 && seen.red > 4.0 * seen.green.abs() && seen.red > 4.0 * seen.blue.abs());
+
+    // Dispersive glass bends blue light more than red; plain glass bends
+    // all wavelengths alike.
+    let prism = Material::dielectric(color::WHITE, 0.0, 1.5).with_dispersion(Dispersion::flint());
+    let ior = |material: Material, wavelength: f64| match material.at_wavelength(wavelength).bsdf {
+        Bsdf::Dielectric { ior, .. } => ior,
+        _ => unreachable!(),
+    };
+    let normal = Vector3::new(0.0, 0.0, 1.0);
+    let wo = Vector3::new(0.6, 0.0, 0.8);
+    let bent = |wavelength: f64| microfacet::refract(wo, normal, ior(prism, wavelength)).unwrap();
+    assert!(bent(450.0).dot(-normal) > bent(650.0).dot(-normal));
+    assert_eq!(ior(Material::dielectric(color::WHITE, 0.0, 1.5), 450.0), 1.5);
+    let tinted = prism.at_wavelength(650.0);
+    assert_eq!(tinted.diffuse, color::WHITE * spectrum::value(color::WHITE, 650.0));
+}
+
+#[test]
+fn test_sky() {
+    let sky = Sky {
+        sun: Vector3::new(1.0, 1.0, 0.0).normalize(),
+        up: Vector3::new(0.0, 1.0, 0.0),
+        turbidity: 3.0,
+        intensity: 2.0,
+        ground: color::BLACK,
+    };
+    let zenith = sky.radiance(Vector3::new(0.0, 1.0, 0.0));
+    assert!((zenith.luminance() - 2.0).abs() < 0.01);
+    // Bluish, brightest around the sun, and black below the horizon.
+    assert!(zenith.blue > zenith.red);
+    let near_sun = sky.radiance(Vector3::new(1.0, 0.9, 0.0).normalize());
+    let away = sky.radiance(Vector3::new(-1.0, 0.9, 0.0).normalize());
+    assert!(near_sun.luminance() > 2.0 * away.luminance());
+    assert_eq!(sky.radiance(Vector3::new(0.0, -0.1, 1.0).normalize()), color::BLACK);
+}
+
+#[test]
+fn test_uv() {
+    let close = |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).abs() < 1.0e-9 && (a.1 - b.1).abs() < 1.0e-9;
+
+    // Spheres have the north pole at v = 0 and u = 0.5 facing -x, however
+    // they are placed.
+    let sphere = Sphere { centre: ORIGIN, radius: 2.0 };
+    let down = Vector3::new(0.0, -1.0, 0.0);
+    assert_eq!(sphere.hit(Ray::new(down, Vector3::new(0.0, 5.0, 0.0))).unwrap().uv.1, 0.0);
+    let side = sphere.hit(Ray::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(-5.0, 0.0, 0.0))).unwrap();
+    assert!(close(side.uv, (0.5, 0.5)));
+    let moved = Transformed::new(Sphere { centre: ORIGIN, radius: 1.0 },
+                                 Transform::translate(Vector3::new(3.0, 0.0, 0.0)) *
+                                 Transform::uniform_scale(2.0));
+    let hit = moved.hit(Ray::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(-5.0, 0.0, 0.0))).unwrap();
+    assert!((hit.t - 6.0).abs() < 1.0e-9 && close(hit.uv, (0.5, 0.5)));
+
+    // Planes tile: points a unit apart are a whole tile apart.
+    let grey = Color::new(0.5, 0.5, 0.5);
+    let checker = Checker {
+        even: color::WHITE,
+        odd: grey,
+        size: 1.0,
+    };
+    let floor = Surface::new(Plane {
+                                 normal: Vector3::new(0.0, 1.0, 0.0),
+                                 origin_distance: 0.0,
+                             },
+                             Material::diffuse(color::WHITE))
+        .with_texture(Channel::Ambient, Rc::new(checker));
+    let a = floor.hit(Ray::new(down, Vector3::new(0.25, 1.0, 0.5))).unwrap().uv;
+    let b = floor.hit(Ray::new(down, Vector3::new(1.25, 1.0, 0.5))).unwrap().uv;
+    assert!((((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt() - 1.0).abs() < 1.0e-9);
+    // Looked at from above, the floor shows both of its colours.
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape],
+        lights: vec![],
+        fog: None,
+    };
+    let camera = screen::Camera { eye: Vector3::new(0.0, 2.0, -5.0), ..screen::default_camera() };
+    let pixels: Vec<Color> = camera.screen(16, 8).map(|ray| world.trace(ray)).collect();
+    assert!(pixels.contains(&(color::WHITE * trace::K_AMBIENT)) && pixels.contains(&(grey * trace::K_AMBIENT)));
+
+    // Torus coordinates are the angles around the ring and around the tube,
+    // as turns, in the same layout as its surface samples.
+    let torus = Torus {
+        center: ORIGIN,
+        radius: 1.0,
+        tube_radius: 0.25,
+        rotx: 0.0,
+        roty: 0.0,
+    };
+    let top = torus.hit(Ray::new(Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 1.0, 5.0))).unwrap();
+    assert!(close(top.uv, (0.25, 0.25)));
+    let sample = torus.sample_surface((0.3, 0.6), 0.0).unwrap();
+    assert!(close(torus.uv(sample.point, 0.0), (0.3, 0.6)));
+
+    // Triangles interpolate their vertex coordinates.
+    let triangle = Triangle::new(ORIGIN, Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0))
+        .with_uvs([(0.0, 0.0), (2.0, 0.0), (0.0, 4.0)]);
+    let into = Vector3::new(0.0, 0.0, -1.0);
+    let hit = triangle.hit(Ray::new(into, Vector3::new(0.25, 0.5, 1.0))).unwrap();
+    assert!((hit.t - 1.0).abs() < 1.0e-12 && close(hit.uv, (0.5, 2.0)));
+    assert_eq!(hit.normal, Vector3::new(0.0, 0.0, 1.0));
+    assert!(close(triangle.uv(Vector3::new(0.25, 0.5, 0.0), 0.0), (0.5, 2.0)));
+    assert!(triangle.hit(Ray::new(into, Vector3::new(0.6, 0.6, 1.0))).is_none());
+    let mut rng = Rng::new(3);
+    for _ in 0..100 {
+        let p = triangle.sample_surface((rng.next_f64(), rng.next_f64()), 0.0).unwrap().point;
+        assert!(p.x >= 0.0 && p.y >= 0.0 && p.x + p.y <= 1.0 + 1.0e-12);
+    }
+    assert_eq!(triangle.surface_pdf(ORIGIN, 0.0), 2.0);
+}
+
+#[test]
+fn test_textures() {
+    // Textures can drive scalar parameters, where the BSDF has them.
+    let grey = Color::new(0.3, 0.3, 0.3);
+    let rough = Material::microfacet(color::WHITE, 0.0, 0.0).with_channel(Channel::Roughness, grey);
+    assert_eq!(rough.bsdf,
+               super::material::Bsdf::Microfacet {
+                   roughness: grey.luminance(),
+                   metallic: 0.0,
+               });
+    let lambert = Material::diffuse(color::WHITE);
+    assert_eq!(lambert.with_channel(Channel::Ior, grey), lambert);
+    // Roughness and metallic stay within [0, 1], however they are set.
+    let metal = Material::microfacet(color::WHITE, 2.0, -1.0);
+    assert_eq!(metal.bsdf,
+               super::material::Bsdf::Microfacet {
+                   roughness: 1.0,
+                   metallic: 0.0,
+               });
+    let metal = metal.with_channel(Channel::Metallic, Color::new(3.0, 3.0, 3.0))
+        .with_channel(Channel::Roughness, Color::new(-1.0, -1.0, -1.0));
+    assert_eq!(metal.bsdf,
+               super::material::Bsdf::Microfacet {
+                   roughness: 0.0,
+                   metallic: 1.0,
+               });
+
+    // Unlit, the floor shows its textured ambient colour.
+    let plane = Plane {
+        normal: Vector3::new(0.0, 1.0, 0.0),
+        origin_distance: 0.0,
+    };
+    let point = Vector3::new(-0.25, 0.0, -0.6);
+    let expected = UvTexture.value(plane.uv(point, 0.0), point) * trace::K_AMBIENT;
+    let floor = Surface::new(plane, Material::diffuse(color::WHITE))
+        .with_texture(Channel::Ambient, Rc::new(UvTexture));
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape],
+        lights: vec![],
+        fog: None,
+    };
+    let seen = world.trace(Ray::new(Vector3::new(0.0, -1.0, 0.0), point + Vector3::new(0.0, 1.0, 0.0)));
+    assert!((seen - expected).max_channel().abs() < 1.0e-12 && seen != color::BLACK);
+
+    // A checkered floor, evaluated at the hit point.
+    let checker = Checker {
+        even: color::WHITE,
+        odd: color::BLACK,
+        size: 1.0,
+    };
+    let floor = Surface::new(Plane {
+                                 normal: Vector3::new(0.0, 1.0, 0.0),
+                                 origin_distance: 0.0,
+                             },
+                             Material::diffuse(color::WHITE))
+        .with_texture(Channel::Ambient, Rc::new(checker));
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape],
+        lights: vec![],
+        fog: None,
+    };
+    let down = Vector3::new(0.0, -1.0, 0.0);
+    assert_eq!(world.trace(Ray::new(down, Vector3::new(0.5, 1.0, 0.5))), color::WHITE * trace::K_AMBIENT);
+    assert_eq!(world.trace(Ray::new(down, Vector3::new(1.5, 1.0, 0.5))), color::BLACK);
+    // Moved a unit along x, the checks move with the floor whether the
+    // texture is applied inside or outside the move.
+    let checker = || {
+        Rc::new(Checker {
+            even: color::WHITE,
+            odd: color::BLACK,
+            size: 1.0,
+        })
+    };
+    let plane = || {
+        Plane {
+            normal: Vector3::new(0.0, 1.0, 0.0),
+            origin_distance: 0.0,
+        }
+    };
+    let shift = Transform::translate(Vector3::new(1.0, 0.0, 0.0));
+    let outside = Surface::new(Transformed::new(plane(), shift), Material::diffuse(color::WHITE))
+        .with_texture(Channel::Ambient, checker());
+    let inside = Transformed::new(Surface::new(plane(), Material::diffuse(color::WHITE))
+                                      .with_texture(Channel::Ambient, checker()),
+                                  shift);
+    for floor in [&outside as &dyn Shape, &inside].iter() {
+        let world = trace::World {
+            shapes: vec![*floor],
+            lights: vec![],
+            fog: None,
+        };
+        assert_eq!(world.trace(Ray::new(down, Vector3::new(0.5, 1.0, 0.5))), color::BLACK);
+        assert_eq!(world.trace(Ray::new(down, Vector3::new(1.5, 1.0, 0.5))), color::WHITE * trace::K_AMBIENT);
+    }
+
+    // An emission texture changes both how a lamp looks and how it lights.
+    let panel = || {
+        Triangle::new(Vector3::new(-1.0, 3.0, -1.0),
+                      Vector3::new(1.0, 3.0, -1.0),
+                      Vector3::new(-1.0, 3.0, 1.0))
+    };
+    let lamp = Surface::new(panel(), Material::emissive(color::WHITE, 16));
+    let dim_lamp = Surface::new(panel(), Material::emissive(color::WHITE, 16))
+        .with_texture(Channel::Emission, Rc::new(Color::new(0.5, 0.5, 0.5)));
+    let ground = Sphere {
+        centre: Vector3::new(0.0, -100.0, 0.0),
+        radius: 100.0,
+    };
+    let lit = |lamp: &dyn Shape, ray: Ray| {
+        let world = trace::World {
+            shapes: vec![&ground as &dyn Shape, lamp],
+            lights: vec![],
+            fog: None,
+        };
+        return world.trace(ray);
+    };
+    let up = Ray::new(Vector3::new(0.0, 1.0, 0.0), Vector3::new(-0.5, 0.0, -0.5));
+    assert_eq!(lit(&dim_lamp, up), Color::new(0.5, 0.5, 0.5));
+    let down = Ray::new(Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+    let bright = lit(&lamp, down).red - trace::K_AMBIENT;
+    let dim = lit(&dim_lamp, down).red - trace::K_AMBIENT;
+    assert!(bright > 0.0 && (dim / bright - 0.5).abs() < 1.0e-9);
+}
+
+#[test]
+fn test_ray_differentials() {
+    let close = |a: Vector3, b: Vector3| (a - b).len() < 1.0e-9;
+    // A wall parallel to the screen sees each pixel scaled up by how much
+    // further away it is.
+    let wall = Plane {
+        normal: Vector3::new(0.0, 0.0, 1.0),
+        origin_distance: 0.0,
+    };
+    let ray = screen::default_camera().screen(8, 4).nth(9).unwrap();
+    let hit = wall.hit(ray).unwrap();
+    let scale = -screen::VIEW_Z / (screen::SCREEN_Z - screen::VIEW_Z);
+    let differentials = hit.differentials(&wall, ray).unwrap();
+    assert!(close(differentials.dpdx, Vector3::new(screen::SCREEN_W / 8.0 * scale, 0.0, 0.0)));
+    assert!(close(differentials.dpdy, Vector3::new(0.0, -screen::SCREEN_H / 4.0 * scale, 0.0)));
+    assert!((differentials.footprint() - screen::SCREEN_H / 4.0 * scale).abs() < 1.0e-9);
+    assert!(hit.differentials(&wall, Ray::new(ray.direction, ray.origin)).is_none());
+
+    // A flat mirror only folds the path, so the footprint grows with the
+    // total distance travelled; a convex one spreads it further.
+    let point = ray.at(hit.t);
+    let mirrored = microfacet::reflect(-ray.direction, hit.normal);
+    let reflected = ray.scattered(point, hit.normal, &differentials, mirrored, 1.0);
+    let width = |ray: Ray, t: f64| ray.point_differentials(t, ray.direction).unwrap().0.len();
+    assert!((width(reflected, 2.0) - width(ray, hit.t + 2.0)).abs() < 1.0e-9);
+    let ball = Sphere {
+        centre: point + Vector3::new(0.0, 0.0, 1.0),
+        radius: 1.0,
+    };
+    let ball_hit = ball.hit(ray).unwrap();
+    let bulge = ball_hit.differentials(&ball, ray).unwrap();
+    let from_ball = ray.scattered(ray.at(ball_hit.t),
+                                  ball_hit.normal,
+                                  &bulge,
+                                  microfacet::reflect(-ray.direction, ball_hit.normal),
+                                  1.0);
+    assert!(width(from_ball, 2.0) > 2.0 * width(ray, ball_hit.t + 2.0));
+    // Refraction into the same index carries straight on.
+    let through = ray.scattered(point, hit.normal, &differentials, ray.direction, 1.0);
+    assert!(close(through.differential.unwrap().dddx, ray.differential.unwrap().dddx));
+    assert!(close(through.differential.unwrap().dodx, differentials.dpdx));
+    // Only near-mirror samples are followed; diffuse and rough ones are not.
+    let mut rng = Rng::new(1);
+    let up = Vector3::new(0.0, 1.0, 0.0);
+    let wo = Vector3::new(0.3, 1.0, 0.0).normalize();
+    let specular = |material: Material, rng: &mut Rng| material.sample(wo, up, rng).map(|s| s.specular);
+    for _ in 0..16 {
+        assert_ne!(specular(Material::diffuse(color::WHITE), &mut rng), Some(true));
+        assert_ne!(specular(Material::microfacet(color::WHITE, 0.6, 1.0), &mut rng), Some(true));
+        assert_ne!(specular(Material::microfacet(color::WHITE, 0.05, 1.0), &mut rng), Some(false));
+        assert_ne!(specular(Material::dielectric(color::WHITE, 0.0, 1.5), &mut rng), Some(false));
+    }
+    let plastic = Material::microfacet(color::WHITE, 0.05, 0.0);
+    let lobes: Vec<_> = (0..64).filter_map(|_| specular(plastic, &mut rng)).collect();
+    assert!(lobes.contains(&true) && lobes.contains(&false));
+
+    // A mip mapped checkerboard seen from far enough away is grey.
+    let checker = Checker {
+        even: color::WHITE,
+        odd: color::BLACK,
+        size: 1.0,
+    };
+    let mut image = Image::new(2, 2);
+    for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter().cloned() {
+        image.set(x, y, checker.value((0.0, 0.0), Vector3::new(x as f64, 0.0, y as f64)));
+    }
+    let floor = Surface::new(Plane {
+                                 normal: Vector3::new(0.0, 1.0, 0.0),
+                                 origin_distance: 0.0,
+                             },
+                             Material::diffuse(color::WHITE))
+        .with_texture(Channel::Ambient,
+                      Rc::new(ImageTexture::new(image, Filter::Trilinear, Wrap::Repeat)));
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape],
+        lights: vec![],
+        fog: None,
+    };
+    let down = Ray::new(Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.25, 1.0, 0.25));
+    let sharp = world.trace(down) * (1.0 / trace::K_AMBIENT);
+    assert!(sharp == color::WHITE || sharp == color::BLACK);
+    let far = down.with_differential(Differential {
+        dodx: Vector3::new(4.0, 0.0, 0.0),
+        dddx: Vector3::new(0.0, 0.0, 0.0),
+        dody: Vector3::new(0.0, 0.0, 4.0),
+        dddy: Vector3::new(0.0, 0.0, 0.0),
+    });
+    let blurred = world.trace(far) * (1.0 / trace::K_AMBIENT);
+    assert!((blurred.red - 0.5).abs() < 1.0e-9);
+}
+
+#[test]
+fn test_tangents() {
+    // Moving along the tangents moves the surface coordinates by the same
+    // amount, to first order.
+    let sphere = Sphere {
+        centre: Vector3::new(1.0, 0.0, 0.0),
+        radius: 2.0,
+    };
+    let torus = Torus {
+        center: ORIGIN,
+        radius: 1.0,
+        tube_radius: 0.25,
+        rotx: 0.4,
+        roty: -0.3,
+    };
+    let triangle = Triangle::new(ORIGIN, Vector3::new(2.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 1.0))
+        .with_uvs([(0.5, 0.0), (1.0, 0.5), (0.0, 2.0)]);
+    let stretched = Transformed::new(&sphere, Transform::scale(Vector3::new(1.0, 3.0, 0.5)));
+    let shapes = [&sphere as &dyn Shape, &torus, &triangle, &stretched];
+    for shape in shapes.iter() {
+        let on_surface = shape.sample_surface((0.3, 0.6), 0.0).unwrap();
+        let ray = Ray::new(-on_surface.normal, on_surface.point + on_surface.normal * 0.01);
+        let hit = shape.hit(ray).unwrap();
+        let point = ray.at(hit.t);
+        assert!(hit.dpdu.dot(hit.normal).abs() < 1.0e-9 && hit.dpdv.dot(hit.normal).abs() < 1.0e-9);
+        let step = 1.0e-6;
+        let (u, v) = shape.uv(point + hit.dpdu * step, 0.0);
+        assert!((u - hit.uv.0 - step).abs() < 1.0e-9 && (v - hit.uv.1).abs() < 1.0e-9);
+        let (u, v) = shape.uv(point + hit.dpdv * step, 0.0);
+        assert!((u - hit.uv.0).abs() < 1.0e-9 && (v - hit.uv.1 - step).abs() < 1.0e-9);
+    }
+
+    // At a pole the surface coordinates have no direction along u, but the
+    // tangents still span the surface.
+    let up = Vector3::new(0.0, 1.0, 0.0);
+    let (dpdu, dpdv) = sphere.tangents(sphere.centre + up * sphere.radius, 0.0);
+    assert!(dpdu.cross(dpdv).len() > 0.5);
+    assert!(dpdu.dot(up).abs() < 1.0e-12 && dpdv.dot(up).abs() < 1.0e-12);
+}
+
+#[test]
+fn test_bump_mapping() {
+    let plane = || {
+        Plane {
+            normal: Vector3::new(0.0, 1.0, 0.0),
+            origin_distance: 0.0,
+        }
+    };
+    // A checkered floor, looked at below only in a white square.
+    let floor = || {
+        let checker = Checker {
+            even: color::WHITE,
+            odd: Color::new(0.5, 0.5, 0.5),
+            size: 1.0,
+        };
+        Surface::new(plane(), Material::diffuse(color::WHITE)).with_texture(Channel::Diffuse, Rc::new(checker))
+    };
+    let (t, b) = sampling::orthonormal_basis(Vector3::new(0.0, 1.0, 0.0));
+    let down = Vector3::new(0.0, -1.0, 0.0);
+    let above = |surface: &dyn Shape| {
+        // Where the plane's surface coordinates are about (0.5, 0.5).
+        let point = plane().tangents(ORIGIN, 0.0).0 * 0.5 + plane().tangents(ORIGIN, 0.0).1 * 0.5;
+        return surface.hit(Ray::new(down, point + Vector3::new(0.0, 1.0, 0.0))).unwrap();
+    };
+
+    // Flat height fields and normal maps leave the normal alone.
+    let flat = floor().with_bump(Bump::Height {
+        texture: Rc::new(Color::new(0.5, 0.5, 0.5)),
+        scale: 1.0,
+    });
+    assert_eq!(above(&flat).shading_normal, Vector3::new(0.0, 1.0, 0.0));
+    let flat = floor().with_bump(Bump::Normal(Rc::new(Color::new(0.5, 0.5, 1.0))));
+    assert!((above(&flat).shading_normal - Vector3::new(0.0, 1.0, 0.0)).len() < 1.0e-12);
+
+    // A height rising along u and v tilts the normal back against them.
+    let ramp = floor().with_bump(Bump::Height {
+        texture: Rc::new(UvTexture),
+        scale: 2.0,
+    });
+    let slope_u = 2.0 * Color::new(1.0, 0.0, 0.0).luminance();
+    let slope_v = 2.0 * Color::new(0.0, 1.0, 0.0).luminance();
+    let expected = (Vector3::new(0.0, 1.0, 0.0) - t * slope_u - b * slope_v).normalize();
+    let hit = above(&ramp);
+    assert!((hit.shading_normal - expected).len() < 1.0e-6);
+    assert_eq!(hit.normal, Vector3::new(0.0, 1.0, 0.0));
+
+    // Tilted 45 degrees towards u, a floor lit from straight above gets
+    // cos 45 of the light, but still blocks light from below.
+    let tilted = floor().with_bump(Bump::Normal(Rc::new(Color::new(0.75, 0.5, 0.75))));
+    assert!((above(&tilted).shading_normal - (Vector3::new(0.0, 1.0, 0.0) + t).normalize()).len() < 1.0e-12);
+    let sun = DirectionalLight {
+        direction: down,
+        color: color::WHITE,
+        irradiance: ::std::f64::consts::PI,
+    };
+    let lit = |floor: &dyn Shape| {
+        let world = trace::World {
+            shapes: vec![floor],
+            lights: vec![&sun as &dyn Light],
+            fog: None,
+        };
+        return world.trace(Ray::new(down, Vector3::new(0.3, 1.0, 0.2))).red - trace::K_AMBIENT;
+    };
+    let smooth = floor();
+    assert!((lit(&smooth) - 1.0).abs() < 1.0e-12);
+    assert!((lit(&tilted) - 0.5f64.sqrt()).abs() < 1.0e-12);
+}
+
+#[test]
+fn test_media() {
+    let floor = Surface::new(Plane {
+                                 normal: Vector3::new(0.0, 1.0, 0.0),
+                                 origin_distance: 0.0,
+                             },
+                             Material::diffuse(color::WHITE));
+    let down = Ray::new(Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 5.0, 0.0));
+    let unlit = trace::K_AMBIENT;
+
+    // Fog that only absorbs dims the floor by exp(-extinction * distance).
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape],
+        lights: vec![],
+        fog: Some(Medium::homogeneous(0.2, color::BLACK)),
+    };
+    assert!((world.trace(down).red - unlit * (-1.0f64).exp()).abs() < 1.0e-12);
+    assert!((world.transmittance(down.with_t_max(2.0)) - (-0.4f64).exp()).abs() < 1.0e-12);
+
+    // Fog that scatters glows where it is lit, even with nothing behind it.
+    let lamp = PointLight {
+        position: Vector3::new(0.0, 3.0, 0.0),
+        color: color::WHITE,
+        intensity: 10.0,
+        falloff: Falloff::InverseSquare,
+    };
+    let world = trace::World {
+        shapes: vec![],
+        lights: vec![&lamp as &dyn Light],
+        fog: Some(Medium::homogeneous(0.2, color::WHITE)),
+    };
+    let glow = world.trace(Ray::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(-4.0, 3.0, 0.5)));
+    assert!(glow.red > 0.0 && glow.red == glow.blue);
+
+    // A cloud between the viewer and the floor, two units thick.
+    let cloud = |medium: Medium| {
+        Volume::new(Sphere {
+                        centre: Vector3::new(0.0, 2.0, 0.0),
+                        radius: 1.0,
+                    },
+                    medium)
+    };
+    let dark = cloud(Medium::homogeneous(0.5, color::BLACK));
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape, &dark],
+        lights: vec![],
+        fog: None,
+    };
+    assert!((world.trace(down).red - unlit * (-1.0f64).exp()).abs() < 1.0e-12);
+    // From inside it, only one unit of it.
+    let inside = Ray::new(down.direction, Vector3::new(0.0, 2.0, 0.0));
+    assert!((world.trace(inside).red - unlit * (-0.5f64).exp()).abs() < 1.0e-12);
+    // Sideways, it misses the cloud entirely.
+    assert_eq!(world.trace(Ray::new(down.direction, Vector3::new(2.0, 5.0, 0.0))).red, unlit);
+
+    // A density grid of ones is the same as a uniform medium.
+    let grid = DensityGrid::from_fn((2, 2, 2),
+                                    Vector3::new(-2.0, -2.0, -2.0),
+                                    Vector3::new(2.0, 2.0, 2.0),
+                                    |_| 1.0)
+        .unwrap();
+    // The grid is in the volume's own space, and moves with it.
+    let gridded = Transformed::new(Volume::new(Sphere {
+                                                   centre: ORIGIN,
+                                                   radius: 1.0,
+                                               },
+                                               Medium::homogeneous(0.5, color::BLACK).with_density(grid)),
+                                   Transform::translate(Vector3::new(0.0, 2.0, 0.0)));
+    let world = trace::World {
+        shapes: vec![&floor as &dyn Shape, &gridded],
+        lights: vec![],
+        fog: None,
+    };
+    assert!((world.trace(down).red - unlit * (-1.0f64).exp()).abs() < 1.0e-12);
+
+    // The cloud casts a soft shadow: light through it is dimmed, not blocked.
+    let overhead = PointLight {
+        position: Vector3::new(0.0, 4.0, 0.0),
+        ..lamp
+    };
+    // Seen from the side, clear of the cloud.
+    let aside = Ray::new(Vector3::new(-3.0, -1.0, 0.0).normalize(), Vector3::new(3.0, 1.0, 0.0));
+    let lit = |shapes: Vec<&dyn Shape>| {
+        let world = trace::World {
+            shapes,
+            lights: vec![&overhead as &dyn Light],
+            fog: None,
+        };
+        return world.trace(aside).red - unlit;
+    };
+    let clear = lit(vec![&floor]);
+    let shadowed = lit(vec![&floor, &dark]);
+    assert!((shadowed / clear - (-1.0f64).exp()).abs() < 1.0e-9);
+}
+
+#[test]
+fn test_motion_blur() {
+    let quarter_turn = Quaternion::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), ::std::f64::consts::FRAC_PI_2);
+    let motion = AnimatedTransform::new(vec![Keyframe::new(1.0)
+                                                 .with_translation(Vector3::new(2.0, 0.0, 0.0))
+                                                 .with_rotation(quarter_turn),
+                                             Keyframe::new(0.0).with_translation(Vector3::new(-2.0, 0.0, 0.0))])
+        .unwrap();
+    // Halfway, it has moved halfway and turned an eighth of a turn.
+    let halfway = motion.at(0.5);
+    assert!(halfway.point(ORIGIN).len() < 1.0e-12);
+    let turned = halfway.vector(Vector3::new(1.0, 0.0, 0.0));
+    assert!((turned - Vector3::new(1.0, 1.0, 0.0).normalize()).len() < 1.0e-12);
+    // It holds still outside its keyframes.
+    assert_eq!(motion.at(-1.0), motion.at(0.0));
+    assert_eq!(motion.at(3.0), motion.at(1.0));
+
+    // Each ray sees the ball where it was at the ray's time.
+    let ball = Animated::new(Surface::new(Sphere {
+                                              centre: ORIGIN,
+                                              radius: 0.5,
+                                          },
+                                          Material::diffuse(color::WHITE)),
+                             motion);
+    let ray = Ray::new(Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -5.0));
+    assert!(ball.intersect(ray).is_none());
+    assert!((ball.intersect(ray.at_time(0.5)).unwrap() - 4.5).abs() < 1.0e-9);
+    let hit = ball.hit(Ray::new(ray.direction, Vector3::new(2.0, 0.0, -5.0)).at_time(1.0)).unwrap();
+    assert!((hit.normal - Vector3::new(0.0, 0.0, -1.0)).len() < 1.0e-9);
+
+    // Over the shutter, the ball crosses the middle of the view for a
+    // quarter of the time.
+    let world = trace::World {
+        shapes: vec![&ball as &dyn Shape],
+        lights: vec![],
+        fog: None,
+    };
+    let still = world.trace(ray.at_time(0.5));
+    let shutter = Shutter {
+        open: 0.0,
+        close: 1.0,
+        samples: 64,
+    };
+    let blurred = world.expose(&trace::Integrator::Direct, ray, &shutter);
+    assert!((blurred - still * 0.25).max_channel().abs() < 1.0e-12);
+    assert_eq!(world.expose(&trace::Integrator::Direct, ray, &Shutter::instant(0.5)), still);
+}
+
+#[test]
+fn test_animated_texture() {
+    // A checkered ball that moves one checker along x: its texture, density
+    // and light samples go with it.
+    let checker = Checker {
+        even: color::WHITE,
+        odd: color::BLACK,
+        size: 1.0,
+    };
+    let ball = || {
+        Sphere {
+            centre: ORIGIN,
+            radius: 0.5,
+        }
+    };
+    let motion = AnimatedTransform::new(vec![Keyframe::new(0.0),
+                                             Keyframe::new(1.0).with_translation(Vector3::new(1.0, 0.0, 0.0))])
+        .unwrap();
+    let shift = Vector3::new(1.0, 0.0, 0.0);
+    let textured = Animated::new(Surface::new(ball(), Material::diffuse(color::WHITE))
+                                     .with_texture(Channel::Ambient, Rc::new(checker)),
+                                 motion.clone());
+    let top = Vector3::new(0.0, 0.0, 0.5);
+    let ambient = |point: Vector3, time: f64| textured.material_at(point, (0.0, 0.0), 0.0, time).unwrap().ambient;
+    assert_eq!(ambient(top, 0.0), color::WHITE);
+    assert_eq!(ambient(top + shift, 1.0), color::WHITE);
+    assert!((textured.normal(top + shift, 1.0) - Vector3::new(0.0, 0.0, 1.0)).len() < 1.0e-12);
+    let world = trace::World {
+        shapes: vec![&textured as &dyn Shape],
+        lights: vec![],
+        fog: None,
+    };
+    let down = Ray::new(Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 5.0));
+    let moved = Ray::new(down.direction, down.origin + shift).at_time(1.0);
+    assert!(world.trace(down) != color::BLACK);
+    assert_eq!(world.trace(moved), world.trace(down));
+
+    let sample = textured.sample_surface((0.3, 0.6), 1.0).unwrap();
+    assert!(((sample.point - shift).len() - 0.5).abs() < 1.0e-12);
+    assert!((textured.surface_pdf(sample.point, 1.0) - 1.0 / ::std::f64::consts::PI).abs() < 1.0e-12);
+
+    let grid = DensityGrid::from_fn((2, 2, 2),
+                                    Vector3::new(-1.0, -1.0, -1.0),
+                                    Vector3::new(1.0, 1.0, 1.0),
+                                    |p| p.x + 1.0)
+        .unwrap();
+    let cloud = Animated::new(Volume::new(ball(), Medium::homogeneous(0.5, color::BLACK).with_density(grid)),
+                              motion);
+    assert!((cloud.density(shift, 1.0) - 1.0).abs() < 1.0e-12);
+}
+
+#[test]
+fn test_animation() {
+    // A car driving along x with its camera, easing to a stop.
+    let s = Sphere {
+        centre: ORIGIN,
+        radius: 1.0,
+    };
+    let drive = AnimatedTransform::new(vec![Keyframe::new(0.0).with_curve(Curve::EaseInOut),
+                                            Keyframe::new(2.0).with_translation(Vector3::new(10.0, 0.0, 0.0))])
+        .unwrap();
+    let car = Node::new("car")
+        .with_motion(drive)
+        .with_child(Node::new("wheel").with_shape(&s))
+        .with_camera(screen::default_camera());
+    assert_eq!(car.flatten().camera("car").unwrap().eye, Vector3::new(0.0, 0.0, screen::VIEW_Z));
+    let halfway = car.flatten_at(1.0);
+    assert!((halfway.camera("car").unwrap().eye - Vector3::new(5.0, 0.0, screen::VIEW_Z)).len() < 1.0e-12);
+    let ray = Ray::new(Vector3::new(0.0, 0.0, 1.0), Vector3::new(5.0, 0.0, -5.0));
+    assert!((halfway.world().trace_nearest(ray).unwrap().1 - 4.0).abs() < 1.0e-12);
+    // Eased, so it has gone less than a tenth of the way in a tenth of the time.
+    assert!(car.transform_at(0.2).point(ORIGIN).x < 1.0);
+    assert_eq!(car.transform_at(3.0).point(ORIGIN), Vector3::new(10.0, 0.0, 0.0));
+
+    // Frames are spaced by the frame rate, with the shutter open for part of each.
+    let sequence = FrameSequence::new(3, 24.0)
+        .with_exposure(0.5, 4)
+        .with_prefix(::std::env::temp_dir().join("rust_tracer_test_").to_str().unwrap());
+    assert_eq!(sequence.time(12), 0.5);
+    assert_eq!(sequence.shutter(12),
+               Shutter {
+                   open: 0.5,
+                   close: 0.5 + 0.5 / 24.0,
+                   samples: 4,
+               });
+    assert!(sequence.path(7).to_str().unwrap().ends_with("rust_tracer_test_0007.ppm"));
+
+    // Each frame renders a light whose colour and position are animated.
+    let color = Track::new(vec![Key::new(0.0, color::BLACK), Key::new(2.0 / 24.0, color::WHITE)]).unwrap();
+    let height = Track::new(vec![Key::new(0.0, 2.0).with_curve(Curve::Cubic), Key::new(1.0, 4.0)]).unwrap();
+    let floor = Plane {
+        normal: Vector3::new(0.0, 1.0, 0.0),
+        origin_distance: 0.0,
+    };
+    let mut brightness = Vec::new();
+    let paths = sequence.render(|shutter, file| {
+            let light = PointLight {
+                position: Vector3::new(0.0, height.at(shutter.open), 0.0),
+                color: color.at(shutter.open),
+                intensity: 1.0,
+                falloff: Falloff::InverseSquare,
+            };
+            let world = trace::World {
+                shapes: vec![&floor as &dyn Shape],
+                lights: vec![&light as &dyn Light],
+                fog: None,
+            };
+            let down = Ray::new(Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+            let pixel = world.expose(&trace::Integrator::Direct, down, &shutter);
+            brightness.push(pixel.red);
+            super::pnm::write_pnm(vec![pixel].into_iter(), 1, 1, file);
+        })
+        .unwrap();
+    assert_eq!(paths.len(), 3);
+    for path in paths.iter() {
+        assert_eq!(::std::fs::read(path).unwrap().len(), "P6\n1 1\n255\n".len() + 3);
+        ::std::fs::remove_file(path).unwrap();
+    }
+    assert!(brightness[0] < brightness[1] && brightness[1] < brightness[2]);
+}
@@ -0,0 +1,402 @@
+use vec3::Vector3;
+use color::Color;
+use image::Image;
+use noise::{Noise, Worley};
+use shape::Hit;
+use std::rc::Rc;
+
+/// A colour that varies over a surface.
+pub trait Texture {
+    /// The colour at surface coordinates `uv` and `point`, which is in the
+    /// textured shape's own space.
+    fn value(&self, uv: (f64, f64), point: Vector3) -> Color;
+
+    /// The colour averaged over about `footprint`, the width of the area
+    /// being looked up in surface coordinates. Only image textures filter;
+    /// others give the colour at the point.
+    fn filtered(&self, uv: (f64, f64), point: Vector3, _footprint: f64) -> Color {
+        return self.value(uv, point);
+    }
+}
+
+// A plain colour is a texture that is the same everywhere.
+impl Texture for Color {
+    fn value(&self, _: (f64, f64), _: Vector3) -> Color {
+        return *self;
+    }
+}
+
+/// Shows the surface coordinates themselves, `u` in red and `v` in green,
+/// repeating every unit. Useful for checking a shape's parameterisation.
+pub struct UvTexture;
+
+impl Texture for UvTexture {
+    fn value(&self, (u, v): (f64, f64), _: Vector3) -> Color {
+        return Color::new(u.rem_euclid(1.0), v.rem_euclid(1.0), 0.0);
+    }
+}
+
+// From `a` at 0 to `b` at 1.
+fn mix(a: Color, b: Color, t: f64) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    return a * (1.0 - t) + b * t;
+}
+
+/// Alternating cubes of two colours filling space, `size` wide.
+///
+/// Cube faces are nudged off the integer multiples of `size`, so a plane
+/// lying on one does not flicker between the colours.
+pub struct Checker {
+    pub even: Color,
+    pub odd: Color,
+    pub size: f64,
+}
+
+impl Texture for Checker {
+    fn value(&self, _: (f64, f64), point: Vector3) -> Color {
+        let cell = |x: f64| (x / self.size + 1.0e-6).floor() as i64;
+        if (cell(point.x) + cell(point.y) + cell(point.z)).rem_euclid(2) == 0 {
+            return self.even;
+        }
+        return self.odd;
+    }
+}
+
+/// Fractal noise blending between two colours, with features about
+/// `scale` across.
+pub struct NoiseTexture {
+    pub noise: Noise,
+    pub low: Color,
+    pub high: Color,
+    pub scale: f64,
+    pub octaves: u32,
+    /// Sum the absolute value of each octave, for billowing clouds.
+    pub turbulence: bool,
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _: (f64, f64), point: Vector3) -> Color {
+        let p = point * (1.0 / self.scale);
+        if self.turbulence {
+            return mix(self.low, self.high, self.noise.turbulence(p, self.octaves));
+        }
+        return mix(self.low, self.high, 0.5 + 0.5 * self.noise.fbm(p, self.octaves));
+    }
+}
+
+/// Veins running across the x axis, `period` apart, distorted by
+/// turbulence.
+pub struct Marble {
+    pub noise: Noise,
+    pub base: Color,
+    pub vein: Color,
+    pub period: f64,
+    /// How far the veins wander, in periods.
+    pub distortion: f64,
+    pub octaves: u32,
+}
+
+impl Texture for Marble {
+    fn value(&self, _: (f64, f64), point: Vector3) -> Color {
+        let p = point * (1.0 / self.period);
+        let phase = p.x + self.distortion * self.noise.turbulence(p, self.octaves);
+        // Sharpen the sine into thin veins.
+        let vein = (1.0 - (phase * ::std::f64::consts::PI).sin().abs()).powi(4);
+        return mix(self.base, self.vein, vein);
+    }
+}
+
+/// Growth rings around the y axis, `spacing` apart, made irregular by
+/// noise.
+pub struct Wood {
+    pub noise: Noise,
+    pub light: Color,
+    pub dark: Color,
+    pub spacing: f64,
+    /// How far the rings wander, in ring spacings.
+    pub distortion: f64,
+}
+
+impl Texture for Wood {
+    fn value(&self, _: (f64, f64), point: Vector3) -> Color {
+        let p = point * (1.0 / self.spacing);
+        let radius = (p.x * p.x + p.z * p.z).sqrt() + self.distortion * self.noise.fbm(p * 0.5, 3);
+        let ring = radius - radius.floor();
+        // Each ring darkens gradually then ends sharply.
+        return mix(self.light, self.dark, ring * ring);
+    }
+}
+
+/// Cells around scattered points, `size` apart, dark at their centres
+/// and light along the borders between them.
+pub struct Cellular {
+    pub worley: Worley,
+    pub centre: Color,
+    pub border: Color,
+    pub size: f64,
+}
+
+impl Texture for Cellular {
+    fn value(&self, _: (f64, f64), point: Vector3) -> Color {
+        let (nearest, second) = self.worley.distances(point * (1.0 / self.size));
+        // Zero exactly on the borders, where the two nearest points are
+        // equally far.
+        let edge = ((second - nearest) * 2.0).min(1.0);
+        return mix(self.border, self.centre, edge);
+    }
+}
+
+/// How an image texture is looked up between texels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// The closest texel.
+    Nearest,
+    /// A blend of the four closest texels.
+    Bilinear,
+    /// Bilinear lookups in the two mip map levels closest to the footprint
+    /// of the lookup, blended together.
+    Trilinear,
+}
+
+/// How an image texture is looked up outside the unit square.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wrap {
+    /// The image tiles.
+    Repeat,
+    /// The edge texels stretch out forever.
+    Clamp,
+    /// The image tiles, flipped in every other tile so the edges match.
+    Mirror,
+}
+
+impl Wrap {
+    // The texel for index `i` of a row or column of `n`.
+    fn texel(self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        match self {
+            Wrap::Repeat => return i.rem_euclid(n) as usize,
+            Wrap::Clamp => return i.clamp(0, n - 1) as usize,
+            Wrap::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                return if i < n { i } else { 2 * n - 1 - i } as usize;
+            }
+        }
+    }
+}
+
+/// An image stretched over the unit square of surface coordinates, with
+/// `(0, 0)` at its top left.
+///
+/// Convert sRGB images with `Image::srgb_to_linear` before using them as
+/// colors.
+pub struct ImageTexture {
+    /// The image and, for trilinear filtering, successive halvings of it
+    /// down to a single texel.
+    levels: Vec<Image>,
+    pub filter: Filter,
+    pub wrap: Wrap,
+}
+
+impl ImageTexture {
+    pub fn new(image: Image, filter: Filter, wrap: Wrap) -> ImageTexture {
+        let mut levels = vec![image];
+        if filter == Filter::Trilinear {
+            while levels[levels.len() - 1].width > 1 || levels[levels.len() - 1].height > 1 {
+                let next = levels[levels.len() - 1].downsample();
+                levels.push(next);
+            }
+        }
+        return ImageTexture { levels, filter, wrap };
+    }
+
+    /// The texture at `uv`, averaged over about `footprint`, the width of
+    /// the area being looked up in surface coordinates. Only trilinear
+    /// filtering uses the footprint.
+    pub fn sample(&self, (u, v): (f64, f64), footprint: f64) -> Color {
+        match self.filter {
+            Filter::Nearest => {
+                let image = &self.levels[0];
+                let x = self.wrap.texel((u * image.width as f64).floor() as i64, image.width);
+                let y = self.wrap.texel((v * image.height as f64).floor() as i64, image.height);
+                return image.get(x, y);
+            }
+            Filter::Bilinear => return self.bilinear(0, u, v),
+            Filter::Trilinear => {
+                let image = &self.levels[0];
+                let texels = footprint * image.width.max(image.height) as f64;
+                let level = texels.max(1.0).log2().min((self.levels.len() - 1) as f64);
+                let lower = level.floor() as usize;
+                if lower + 1 >= self.levels.len() {
+                    return self.bilinear(lower, u, v);
+                }
+                let f = level - lower as f64;
+                return self.bilinear(lower, u, v) * (1.0 - f) + self.bilinear(lower + 1, u, v) * f;
+            }
+        }
+    }
+
+    fn bilinear(&self, level: usize, u: f64, v: f64) -> Color {
+        let image = &self.levels[level];
+        // Texel centres are at half integers.
+        let (x, y) = (u * image.width as f64 - 0.5, v * image.height as f64 - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let texel = |dx: i64, dy: i64| {
+            image.get(self.wrap.texel(x0 as i64 + dx, image.width),
+                      self.wrap.texel(y0 as i64 + dy, image.height))
+        };
+        return (texel(0, 0) * (1.0 - fx) + texel(1, 0) * fx) * (1.0 - fy) +
+               (texel(0, 1) * (1.0 - fx) + texel(1, 1) * fx) * fy;
+    }
+}
+
+// Lookups without a footprint use the full resolution image.
+impl Texture for ImageTexture {
+    fn value(&self, uv: (f64, f64), _: Vector3) -> Color {
+        return self.sample(uv, 0.0);
+    }
+
+    fn filtered(&self, uv: (f64, f64), _: Vector3, footprint: f64) -> Color {
+        return self.sample(uv, footprint);
+    }
+}
+
+/// Fakes small bumps in a surface by tilting its shading normal.
+pub enum Bump {
+    /// A height field: the surface is displaced along its normal by the
+    /// texture's luminance times `scale`, and the normal follows the
+    /// slopes, found by finite differences.
+    Height { texture: Rc<dyn Texture>, scale: f64 },
+    /// A tangent space normal map, with the red, green and blue channels
+    /// mapping `[0, 1]` to `[-1, 1]` along the `u` direction, up the image
+    /// (decreasing `v`) and the normal, as in OpenGL conventions.
+    Normal(Rc<dyn Texture>),
+}
+
+// Step in surface coordinates for differentiating height fields.
+const BUMP_DELTA: f64 = 1.0e-3;
+
+impl Bump {
+    /// The shading normal at `point` for a surface hit at `hit`, whose
+    /// geometric normal it stays on the same side of. `local` takes points
+    /// near the hit into the space the texture is looked up in.
+    pub fn shading_normal(&self, hit: &Hit, point: Vector3, local: &dyn Fn(Vector3) -> Vector3) -> Vector3 {
+        let n = hit.normal;
+        let perturbed = match *self {
+            Bump::Height { ref texture, scale } => {
+                let (u, v) = hit.uv;
+                let height = |du: f64, dv: f64| {
+                    let p = point + hit.dpdu * du + hit.dpdv * dv;
+                    texture.value((u + du, v + dv), local(p)).luminance() * scale
+                };
+                let base = height(0.0, 0.0);
+                let slope_u = (height(BUMP_DELTA, 0.0) - base) / BUMP_DELTA;
+                let slope_v = (height(0.0, BUMP_DELTA) - base) / BUMP_DELTA;
+                let normal = (hit.dpdu + n * slope_u).cross(hit.dpdv + n * slope_v);
+                if normal.dot(n) < 0.0 { -normal } else { normal }
+            }
+            Bump::Normal(ref texture) => {
+                let c = texture.value(hit.uv, local(point));
+                let tangent = (hit.dpdu - n * n.dot(hit.dpdu)).normalize();
+                let mut up = n.cross(tangent);
+                if up.dot(hit.dpdv) > 0.0 {
+                    up = -up;
+                }
+                tangent * (2.0 * c.red - 1.0) + up * (2.0 * c.green - 1.0) + n * (2.0 * c.blue - 1.0)
+            }
+        };
+        let perturbed = perturbed.normalize();
+        // Degenerate frames, and normal maps tilted past the horizon, are
+        // left unperturbed.
+        if perturbed.x.is_nan() || perturbed.dot(n) <= 0.0 {
+            return n;
+        }
+        return perturbed;
+    }
+}
+
+#[test]
+fn test_image_texture() {
+    let mut image = Image::new(2, 2);
+    image.set(1, 0, Color::new(1.0, 0.0, 0.0));
+    image.set(0, 1, Color::new(0.0, 1.0, 0.0));
+    image.set(1, 1, Color::new(0.0, 0.0, 1.0));
+
+    let nearest = ImageTexture::new(image.clone(), Filter::Nearest, Wrap::Repeat);
+    assert_eq!(nearest.sample((0.75, 0.25), 0.0), Color::new(1.0, 0.0, 0.0));
+    assert_eq!(nearest.sample((1.75, -0.75), 0.0), Color::new(1.0, 0.0, 0.0));
+    let mirror = ImageTexture::new(image.clone(), Filter::Nearest, Wrap::Mirror);
+    assert_eq!(mirror.sample((1.25, 0.25), 0.0), Color::new(1.0, 0.0, 0.0));
+    let clamp = ImageTexture::new(image.clone(), Filter::Nearest, Wrap::Clamp);
+    assert_eq!(clamp.sample((5.0, -5.0), 0.0), Color::new(1.0, 0.0, 0.0));
+
+    // Halfway between texel centres, and exactly on one.
+    let bilinear = ImageTexture::new(image.clone(), Filter::Bilinear, Wrap::Clamp);
+    assert_eq!(bilinear.sample((0.5, 0.25), 0.0), Color::new(0.5, 0.0, 0.0));
+    assert_eq!(bilinear.sample((0.75, 0.75), 0.0), Color::new(0.0, 0.0, 1.0));
+
+    // A footprint of the whole image blurs to the average.
+    let trilinear = ImageTexture::new(image, Filter::Trilinear, Wrap::Repeat);
+    assert_eq!(trilinear.levels.len(), 2);
+    assert_eq!(trilinear.sample((0.75, 0.75), 0.0), bilinear.sample((0.75, 0.75), 0.0));
+    assert_eq!(trilinear.sample((0.3, 0.9), 1.0), Color::new(0.25, 0.25, 0.25));
+    let halfway = trilinear.sample((0.75, 0.75), 2.0f64.sqrt() / 2.0);
+    assert!((halfway.blue - 0.625).abs() < 1.0e-12);
+}
+
+#[test]
+fn test_procedural_textures() {
+    let black = Color::new(0.0, 0.0, 0.0);
+    let white = Color::new(1.0, 1.0, 1.0);
+    let checker = Checker { even: black, odd: white, size: 0.5 };
+    let at = |x: f64, y: f64, z: f64| checker.value((0.0, 0.0), Vector3::new(x, y, z));
+    assert_eq!(at(0.25, 0.25, 0.25), black);
+    assert_eq!(at(0.75, 0.25, 0.25), white);
+    assert_eq!(at(-0.25, 0.25, 0.25), white);
+    // A floor at a cube face is the same colour just above and below it.
+    assert_eq!(at(0.25, 1.0e-12, 0.25), at(0.25, -1.0e-12, 0.25));
+
+    let wood = Wood {
+        noise: Noise::new(3),
+        light: white,
+        dark: black,
+        spacing: 0.1,
+        distortion: 0.0,
+    };
+    // Without noise, rings are a function of the distance from the axis.
+    let ring = |r: f64| wood.value((0.0, 0.0), Vector3::new(r, 5.0, 0.0));
+    assert_eq!(ring(0.025), wood.value((0.0, 0.0), Vector3::new(0.0, -1.0, 0.025)));
+    assert!(ring(0.09).red < ring(0.01).red);
+
+    let marble = Marble {
+        noise: Noise::new(3),
+        base: black,
+        vein: white,
+        period: 1.0,
+        distortion: 2.0,
+        octaves: 4,
+    };
+    let cellular = Cellular {
+        worley: Worley { seed: 3 },
+        centre: black,
+        border: white,
+        size: 0.3,
+    };
+    let fbm = NoiseTexture {
+        noise: Noise::new(3),
+        low: black,
+        high: white,
+        scale: 0.2,
+        octaves: 4,
+        turbulence: false,
+    };
+    // All stay between their two colours, and vary.
+    for texture in [&marble as &dyn Texture, &cellular, &fbm].iter() {
+        let values: Vec<f64> = (0..200)
+            .map(|i| texture.value((0.0, 0.0), Vector3::new(i as f64 * 0.037, 0.3, -0.2)).red)
+            .collect();
+        assert!(values.iter().all(|v| (0.0..=1.0).contains(v)));
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        assert!(values.iter().any(|&v| (v - mean).abs() > 0.1));
+    }
+}
@@ -1,101 +1,531 @@
-use vec3::Vector3;
+use vec3::{Vector3, Quaternion};
 use super::Ray;
-use shape::Shape;
-use shape::Light;
+use shape::{Shape, Hit};
+use shape::{Light, LightSample, PointLight, Falloff};
 use screen;
+use screen::{Camera, Shutter};
 use super::color;
 use super::color::Color;
 use super::pnm;
-use super::shape::{ORIGIN, Sphere, Torus};
-use std::io::stdout;
+use super::shape::{Torus, Transformed, Plane, Surface};
+use super::transform::{Transform, AnimatedTransform};
+use sampling;
+use sampling::Rng;
+use path::PathTracer;
+use material::{Material, Channel};
+use texture::Checker;
+use medium::{Medium, Density};
+use std::env;
+use std::io::{stdout, Write};
+use animation::{Track, Key, Curve};
+use sequence::FrameSequence;
 
-struct World<'a> {
-    shapes: Vec<&'a Shape>,
-    lights: Vec<&'a Light>,
+pub fn trace_nearest<'a>(ray: Ray, shapes: &[&'a dyn Shape]) -> Option<(&'a dyn Shape, f64, Vector3)> {
+    return nearest_hit(ray, shapes).map(|(shape, hit)| (shape, hit.t, hit.normal));
 }
 
-impl<'a> World<'a> {
-    pub fn trace_nearest(&self, ray: Ray) -> Option<(&Shape, f64, Vector3)> {
+/// The first shape along `ray` and where it is hit. The boundaries of
+/// volumes are not surfaces, and are passed through.
+pub fn nearest_hit<'a>(ray: Ray, shapes: &[&'a dyn Shape]) -> Option<(&'a dyn Shape, Hit)> {
 
-        let mut closest: Option<(&Shape, f64, Vector3)> = None;
-        let mut closest_distance: f64 = 1.0 / 0.0;
-        let mut iter = self.shapes.iter();
+    let mut closest: Option<(&dyn Shape, Hit)> = None;
+    let mut closest_distance: f64 = ray.t_max;
 
-        while let Some(shape) = iter.next() {
-            if let Some((distance, normal)) = shape.intersect_with_normal(ray) {
-                if distance < closest_distance {
-                    closest_distance = distance;
-                    closest = Some((*shape, distance, normal));
-                }
+    for shape in shapes.iter() {
+        if shape.medium().is_some() {
+            continue;
+        }
+        // Shapes only need to look nearer than the closest hit so far.
+        if let Some(hit) = shape.hit(ray.with_t_max(closest_distance)) {
+            if hit.t < closest_distance {
+                closest_distance = hit.t;
+                closest = Some((*shape, hit));
             }
         }
+    }
 
-        return closest;
+    return closest;
+}
+
+/// Fog and volumes are only seen by the direct lighting integrators;
+/// path traced renders (`--path` and `--spectral`) pass straight through
+/// them.
+pub struct World<'a> {
+    pub shapes: Vec<&'a dyn Shape>,
+    pub lights: Vec<&'a dyn Light>,
+    /// A medium filling all of space, such as fog.
+    pub fog: Option<Medium>,
+}
+
+// A stretch of a ray through a medium: either the fog, or the inside of a volume.
+struct MediumSegment<'m> {
+    medium: &'m Medium,
+    volume: Option<&'m dyn Shape>,
+    start: f64,
+    end: f64,
+}
+
+impl<'m> MediumSegment<'m> {
+    fn density(&self, point: Vector3, time: f64) -> f64 {
+        return self.volume.map_or_else(|| self.medium.density(point), |v| v.density(point, time));
     }
 
-    fn trace_collision(&self, ray: Ray, max_distance: f64) -> bool {
+    // Integral of the extinction along the segment.
+    fn optical_depth(&self, ray: Ray) -> f64 {
+        let (dir, origin) = (ray.direction, ray.origin);
+        if let Density::Uniform = self.medium.density {
+            return self.medium.extinction * (self.end - self.start);
+        }
+        // Midpoints of equal steps.
+        let n = self.medium.samples.max(1);
+        let step = (self.end - self.start) / n as f64;
+        return (0..n)
+            .map(|i| self.density(origin + dir * (self.start + (i as f64 + 0.5) * step), ray.time))
+            .sum::<f64>() * self.medium.extinction * step;
+    }
+}
 
+// The parts of `ray` within its interval that are inside a closed shape.
+fn inside_segments(shape: &dyn Shape, ray: Ray) -> Vec<(f64, f64)> {
+    let (dir, origin) = (ray.direction, ray.origin);
+    let max_distance = ray.t_max;
+    let mut segments = Vec::new();
+    let mut entered: Option<f64> = None;
+    let mut t = 0.0;
+    // A few crossings at most, so grazing hits cannot go on forever.
+    for _ in 0..16 {
+        let hit = match shape.hit(ray.spawn(dir, origin + dir * t)) {
+            Some(h) => h,
+            None => break,
+        };
+        t += hit.t;
+        if dir.dot(hit.normal) > 0.0 {
+            // Leaving, so inside since entering or since the start.
+            segments.push((entered.unwrap_or(0.0), t.min(max_distance)));
+            entered = None;
+        } else {
+            entered = Some(t);
+        }
+        if t >= max_distance {
+            break;
+        }
+    }
+    if let Some(start) = entered {
+        if start < max_distance {
+            segments.push((start, max_distance));
+        }
+    }
+    return segments.into_iter()
+        .map(|(start, end)| (start.max(ray.t_min), end))
+        .filter(|&(start, end)| start < end)
+        .collect();
+}
+
+impl<'a> World<'a> {
+    pub fn trace_nearest(&self, ray: Ray) -> Option<(&'a dyn Shape, f64, Vector3)> {
+        return trace_nearest(ray, &self.shapes);
+    }
+
+    pub fn nearest_hit(&self, ray: Ray) -> Option<(&'a dyn Shape, Hit)> {
+        return nearest_hit(ray, &self.shapes);
+    }
+
+    /// Whether no surface blocks `ray` within its interval. Media only dim
+    /// the light; see `transmittance`.
+    pub fn trace_collision(&self, ray: Ray) -> bool {
+        return self.shapes
+            .iter()
+            .all(|shape| shape.medium().is_some() || shape.intersect(ray).is_none());
+    }
+
+    /// The media along `ray` within its interval, fog first.
+    fn media_along(&self, ray: Ray) -> Vec<MediumSegment<'_>> {
+        let mut segments = Vec::new();
+        if let Some(ref fog) = self.fog {
+            let extent = match fog.density {
+                Density::Uniform => Some((ray.t_min, ray.t_max)),
+                Density::Grid(ref grid) => grid.clip(ray),
+            };
+            if let Some((start, end)) = extent {
+                segments.push(MediumSegment {
+                    medium: fog,
+                    volume: None,
+                    start,
+                    end,
+                });
+            }
+        }
         for shape in self.shapes.iter() {
-            if let Some(s) = shape.intersect(ray) {
-                if s < max_distance {
-                    return false;
+            if let Some(medium) = shape.medium() {
+                for (start, end) in inside_segments(*shape, ray) {
+                    segments.push(MediumSegment {
+                        medium,
+                        volume: Some(*shape),
+                        start,
+                        end,
+                    });
                 }
             }
         }
-        return true;
+        return segments;
     }
 
-    fn trace_lights(&self,
-                    point: Vector3,
-                    normal: Vector3)
-                    -> Box<Iterator<Item = &'a &Light> + 'a> {
-        return Box::new(self.lights.iter().filter(move |l| {
-            let ray_to_light = l.position - point;
-            if ray_to_light.dot(normal) < 0.0 {
-                return false;
-            } else {
-                return self.trace_collision((ray_to_light.normalize(), point), ray_to_light.len());
-            }
-        }));
+    /// The fraction of light travelling along `ray` that gets through the
+    /// media within its interval.
+    ///
+    /// Light from infinitely far away, such as sunlight, is not dimmed by
+    /// media that go on forever, which would block it completely.
+    pub fn transmittance(&self, ray: Ray) -> f64 {
+        let depth: f64 = self.media_along(ray)
+            .iter()
+            .filter(|segment| segment.end.is_finite())
+            .map(|segment| segment.optical_depth(ray))
+            .sum();
+        return (-depth).exp();
     }
 
-    fn trace(&self, (dir, origin): Ray) -> Color {
-        if let Some((shape, t, normal)) = self.trace_nearest((dir, origin)) {
-            let point = origin + dir * t;
-            let lights = self.trace_lights(point, normal);
-            let mut color = color::BLACK;
-            for light in lights {
-                color = color + diffuse(shape, light, normal, point);
+    /// The light reaching `point` at `time` that is in front of `normal`,
+    /// if given, and not in shadow, from both lights and emissive shapes.
+    /// Each gets several shadow rays, stratified over its surface, and the
+    /// returned samples are weighted so that they sum to its total
+    /// contribution.
+    fn trace_lights(&self, point: Vector3, time: f64, normal: Option<Vector3>, rng: &mut Rng) -> Vec<LightSample> {
+        let in_front = |direction: Vector3| normal.is_none_or(|n| direction.dot(n) >= 0.0);
+        let mut samples = Vec::new();
+        for light in self.lights.iter() {
+            let n = light.sample_count();
+            let weight = 1.0 / n as f64;
+            for u in sampling::stratified(n, rng) {
+                if let Some(sample) = light.illuminate(point, u) {
+                    let shadow_ray = Ray::new(sample.direction, point).at_time(time).with_t_max(sample.distance);
+                    if in_front(sample.direction) && self.trace_collision(shadow_ray) {
+                        let weight = weight * self.transmittance(shadow_ray);
+                        samples.push(LightSample { color: sample.color * weight, ..sample });
+                    }
+                }
+            }
+        }
+        for shape in self.shapes.iter() {
+            let light_samples = match shape.material() {
+                Some(m) if m.is_emissive() => m.light_samples,
+                _ => continue,
+            };
+            let weight = 1.0 / light_samples as f64;
+            for u in sampling::stratified(light_samples, rng) {
+                let on_light = match shape.sample_surface(u, time) {
+                    Some(s) => s,
+                    None => break,
+                };
+                let emission = emission_at(*shape, on_light.point, time);
+                let ray_to_light = on_light.point - point;
+                let distance = ray_to_light.len();
+                let direction = ray_to_light / distance;
+                let cosine = -direction.dot(on_light.normal);
+                // Stop the shadow ray just short of the light so it does not hit it.
+                let shadow_ray = Ray::new(direction, point)
+                    .at_time(time)
+                    .with_t_max(distance * (1.0 - SHADOW_EPSILON));
+                if cosine > 0.0 && in_front(direction) && self.trace_collision(shadow_ray) {
+                    let factor = weight * cosine * self.transmittance(shadow_ray) /
+                                 (distance * distance * on_light.pdf);
+                    samples.push(LightSample {
+                        direction,
+                        distance,
+                        color: emission * factor,
+                    });
+                }
             }
-            color = color + ambient(shape);
-            return color;
+        }
+        return samples;
+    }
+
+    /// The fraction of the hemisphere above `point` that is open for
+    /// `occlusion.max_distance` at `time`, weighted by the cosine with
+    /// `normal`. With no samples, nothing occludes.
+    pub fn occlusion(&self,
+                     point: Vector3,
+                     time: f64,
+                     normal: Vector3,
+                     occlusion: &AmbientOcclusion,
+                     rng: &mut Rng)
+                     -> f64 {
+        if occlusion.samples == 0 {
+            return 1.0;
+        }
+        let open = sampling::stratified(occlusion.samples, rng)
+            .into_iter()
+            .filter(|&(u, v)| {
+                let direction = sampling::cosine_hemisphere(normal, u, v);
+                self.trace_collision(Ray::new(direction, point).at_time(time).with_t_max(occlusion.max_distance))
+            })
+            .count();
+        return open as f64 / occlusion.samples as f64;
+    }
+
+    pub fn trace_with(&self, integrator: &Integrator, ray: Ray) -> Color {
+        match *integrator {
+            Integrator::Direct => return self.trace(ray),
+            Integrator::DirectWithOcclusion(ref occlusion) => return self.trace_direct(ray, Some(occlusion)),
+            Integrator::Occlusion(ref occlusion) => return self.trace_occlusion(ray, occlusion),
+            Integrator::Path(ref path_tracer) => return path_tracer.trace(self, ray),
+        }
+    }
+
+    /// The average of `trace_with` over the times the shutter samples,
+    /// starting from `ray`.
+    pub fn expose(&self, integrator: &Integrator, ray: Ray, shutter: &Shutter) -> Color {
+        let times = shutter.times(&mut Rng::for_ray(ray));
+        let total = times.iter()
+            .fold(color::BLACK, |sum, &time| sum + self.trace_with(integrator, ray.at_time(time)));
+        return total * (1.0 / times.len() as f64);
+    }
+
+    /// Direct lighting plus a constant ambient term.
+    pub fn trace(&self, ray: Ray) -> Color {
+        return self.trace_direct(ray, None);
+    }
+
+    /// Direct lighting plus an ambient term, darkened by ambient occlusion
+    /// if given, seen through any media in the way.
+    pub fn trace_direct(&self, ray: Ray, occlusion: Option<&AmbientOcclusion>) -> Color {
+        let mut rng = Rng::for_ray(ray);
+        let (distance, mut color) = match self.nearest_hit(ray) {
+            Some((shape, hit)) => (hit.t, self.shade(ray, shape, &hit, occlusion, &mut rng)),
+            None => (1.0 / 0.0, self.background(ray.direction)),
+        };
+        // Working back towards the viewer.
+        let mut media = self.media_along(ray.with_t_max(distance));
+        media.sort_by(|a, b| b.start.partial_cmp(&a.start).unwrap());
+        for segment in media.iter() {
+            color = self.through_medium(segment, ray, color, &mut rng);
+        }
+        return color;
+    }
+
+    fn shade(&self,
+             ray: Ray,
+             shape: &dyn Shape,
+             hit: &Hit,
+             occlusion: Option<&AmbientOcclusion>,
+             rng: &mut Rng)
+             -> Color {
+        let dir = ray.direction;
+        let point = ray.at(hit.t);
+        let footprint = hit.differentials(shape, ray).map_or(0.0, |d| d.footprint());
+        let material = material_of(shape, point, hit.uv, footprint, ray.time);
+        let mut color = color::BLACK;
+        // Both normals facing the viewer; lights are culled by the
+        // geometric normal but shaded with the bumped one.
+        let (normal, shading_normal) = if dir.dot(hit.normal) < 0.0 {
+            color = material.emission;
+            (hit.normal, hit.shading_normal)
         } else {
-            return color::BLACK;
+            (-hit.normal, -hit.shading_normal)
+        };
+        for sample in self.trace_lights(point, ray.time, Some(normal), rng) {
+            color = color + direct(&material, -dir, &sample, shading_normal);
         }
+        let visibility = occlusion.map_or(1.0, |o| self.occlusion(point, ray.time, normal, o, rng));
+        color = color + material.ambient * K_AMBIENT * visibility;
+        return color;
+    }
+
+    // Light arriving along `ray` through a segment of a medium: `behind`,
+    // from the far end, dimmed by the medium, plus light from the lights
+    // scattered once towards the viewer.
+    fn through_medium(&self, segment: &MediumSegment, ray: Ray, behind: Color, rng: &mut Rng) -> Color {
+        let (dir, origin) = (ray.direction, ray.origin);
+        let medium = segment.medium;
+        let n = medium.samples.max(1);
+        let mut scattered = color::BLACK;
+        match medium.density {
+            Density::Uniform => {
+                let extinction = medium.extinction;
+                if extinction <= 0.0 || segment.end <= segment.start {
+                    return behind;
+                }
+                let transmittance = (-extinction * (segment.end - segment.start)).exp();
+                for i in 0..n {
+                    // Stratified distances, drawn in proportion to how much
+                    // of their light reaches the viewer.
+                    let u = (i as f64 + rng.next_f64()) / n as f64;
+                    let s = segment.start - (1.0 - u * (1.0 - transmittance)).ln() / extinction;
+                    scattered = scattered + self.in_scattered(medium, ray.spawn(dir, origin + dir * s), rng);
+                }
+                return behind * transmittance + medium.albedo * scattered * ((1.0 - transmittance) / n as f64);
+            }
+            Density::Grid(_) => {
+                // Ray marching, with a jittered point in each step.
+                let step = (segment.end - segment.start) / n as f64;
+                let mut depth = 0.0;
+                for i in 0..n {
+                    let offset = rng.next_f64();
+                    let point = origin + dir * (segment.start + (i as f64 + offset) * step);
+                    let scattering = ray.spawn(dir, point);
+                    let extinction = medium.extinction * segment.density(point, ray.time);
+                    if extinction > 0.0 {
+                        let transmittance = (-(depth + extinction * step * offset)).exp();
+                        scattered = scattered +
+                                    self.in_scattered(medium, scattering, rng) * (transmittance * extinction * step);
+                    }
+                    depth += extinction * step;
+                }
+                return behind * (-depth).exp() + medium.albedo * scattered;
+            }
+        }
+    }
+
+    // Light from the lights scattered at the origin of `ray`, in a medium,
+    // back along it.
+    fn in_scattered(&self, medium: &Medium, ray: Ray, rng: &mut Rng) -> Color {
+        return self.trace_lights(ray.origin, ray.time, None, rng)
+            .iter()
+            .fold(color::BLACK,
+                  |sum, sample| sum + sample.color * medium.phase(ray.direction.dot(sample.direction)));
     }
+
+    /// What is seen looking out of the scene in `direction`: the
+    /// environment lights, or black if there are none.
+    pub fn background(&self, direction: Vector3) -> Color {
+        return self.lights.iter().fold(color::BLACK, |sum, l| sum + l.background(direction));
+    }
+
+    /// The ambient occlusion pass on its own, white where the surface is
+    /// fully open. Rays that miss everything are black.
+    pub fn trace_occlusion(&self, ray: Ray, occlusion: &AmbientOcclusion) -> Color {
+        let mut rng = Rng::for_ray(ray);
+        return match self.trace_nearest(ray) {
+            Some((_, t, normal)) => {
+                let normal = if ray.direction.dot(normal) < 0.0 { normal } else { -normal };
+                color::WHITE * self.occlusion(ray.at(t), ray.time, normal, occlusion, &mut rng)
+            }
+            None => color::BLACK,
+        };
+    }
+}
+
+/// Settings for ambient occlusion, which darkens the ambient term in
+/// creases and where shapes nearly touch.
+#[derive(Debug, Clone, Copy)]
+pub struct AmbientOcclusion {
+    /// Rays cast into the hemisphere above each shading point.
+    pub samples: u32,
+    /// Only shapes within this distance occlude.
+    pub max_distance: f64,
+}
+
+/// How a camera ray is turned into a color.
+pub enum Integrator {
+    /// `World::trace`: direct light only, with a constant ambient term
+    /// standing in for indirect light. Fast and noise free.
+    Direct,
+    /// As `Direct`, with the ambient term darkened by ambient occlusion.
+    DirectWithOcclusion(AmbientOcclusion),
+    /// Only the ambient occlusion pass, in greyscale.
+    Occlusion(AmbientOcclusion),
+    /// Full global illumination by path tracing.
+    Path(PathTracer),
 }
 
-pub const K_DIFFUSE: f64 = 5.0;
 pub const K_AMBIENT: f64 = 0.1;
+/// Fraction of the distance to an emissive shape that shadow rays stop short by.
+pub const SHADOW_EPSILON: f64 = 1.0e-4;
 
+/// The material of a shape at a point on it with surface coordinates `uv`
+/// at `time`, with textures filtered over `footprint`, or a diffuse one in
+/// its colours if it has none.
+pub fn material_of(shape: &dyn Shape, point: Vector3, uv: (f64, f64), footprint: f64, time: f64) -> Material {
+    return match shape.material_at(point, uv, footprint, time) {
+        Some(m) => m,
+        None => {
+            Material {
+                ambient: shape.color_ambient(),
+                ..Material::diffuse(shape.color_diffuse())
+            }
+        }
+    };
+}
 
-fn diffuse(shape: &Shape, light: &Light, normal: Vector3, point: Vector3) -> Color {
-    let ray_to_light = light.position - point;
-    let distance = ray_to_light.len();
-    let cosine = normal.dot(ray_to_light.normalize());
+// Light from one light sample scattered towards `wo`.
+fn direct(material: &Material, wo: Vector3, sample: &LightSample, normal: Vector3) -> Color {
+    let cosine = normal.dot(sample.direction);
     if cosine.is_nan() {
         panic!("Cosine is NaN");
     }
-    let factor = K_DIFFUSE * normal.dot(ray_to_light.normalize()) * 1.0 / (distance * distance);
-    return (shape.color_diffuse() * light.color) * factor;
+    return (material.eval(wo, sample.direction, normal) * sample.color) * cosine.abs();
 }
 
-fn ambient(shape: &Shape) -> Color {
-    return shape.color_ambient() * K_AMBIENT;
+/// The emitted radiance at `point` on an emissive shape at `time`, with
+/// any emission texture applied.
+pub fn emission_at(shape: &dyn Shape, point: Vector3, time: f64) -> Color {
+    return material_of(shape, point, shape.uv(point, time), 0.0, time).emission;
 }
 
-pub fn simple_trace() {
-    let screen = screen::get_screen();
+// `--path` to path trace, `--spectral` to path trace spectrally, `--ao` for
+// ambient occlusion, or `--ao-pass` for the ambient occlusion pass alone.
+fn integrator_from_args() -> Integrator {
+    let occlusion = AmbientOcclusion {
+        samples: 16,
+        max_distance: 0.5,
+    };
+    for arg in env::args() {
+        match arg.as_str() {
+            "--path" => return Integrator::Path(PathTracer::new(64)),
+            "--spectral" => return Integrator::Path(PathTracer::new(64).with_spectral(true)),
+            "--ao" => return Integrator::DirectWithOcclusion(occlusion),
+            "--ao-pass" => return Integrator::Occlusion(occlusion),
+            _ => {}
+        }
+    }
+    return Integrator::Direct;
+}
+
+// `--frames N` to render N frames of the animation to numbered files,
+// optionally at `--fps F`. Values that are missing or out of range are
+// errors.
+fn sequence_from_args() -> Result<Option<FrameSequence>, String> {
+    let args: Vec<String> = env::args().collect();
+    let value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .map(|i| args.get(i + 1).map_or("", |v| v.as_str()))
+    };
+    let frames = match value("--frames") {
+        Some(v) => v.parse::<u32>().map_err(|_| format!("--frames needs a whole number of frames, not {:?}", v))?,
+        None => return Ok(None),
+    };
+    let frames_per_second = match value("--fps") {
+        Some(v) => {
+            v.parse::<f64>()
+                .ok()
+                .filter(|&fps| fps > 0.0 && fps.is_finite())
+                .ok_or_else(|| format!("--fps needs a positive number, not {:?}", v))?
+        }
+        None => 24.0,
+    };
+    return Ok(Some(FrameSequence::new(frames, frames_per_second)));
+}
+
+// Two linked tori, turning in front of a light that wanders and warms, seen
+// by a camera slowly circling them. The first frame is the still image.
+fn render_demo(integrator: &Integrator, shutter: Shutter, out: &mut dyn Write) {
+    use std::f64::consts::PI;
+    let time = shutter.open;
+    let spin = Track::new(vec![Key::new(0.0, 0.0), Key::new(4.0, 2.0 * PI)]).unwrap();
+    let tilt = Track::new(vec![Key::new(0.0, 0.0).with_curve(Curve::Cubic),
+                               Key::new(2.0, 0.3).with_curve(Curve::Cubic),
+                               Key::new(4.0, 0.0)]).unwrap();
+    let light_position = Track::new(vec![Key::new(0.0, Vector3::new(-1.5, 1.0, -3.0)).with_curve(Curve::EaseInOut),
+                                         Key::new(2.0, Vector3::new(1.5, 1.0, -3.0)).with_curve(Curve::EaseInOut),
+                                         Key::new(4.0, Vector3::new(-1.5, 1.0, -3.0))]).unwrap();
+    let light_color = Track::new(vec![Key::new(0.0, color::RED).with_curve(Curve::EaseInOut),
+                                      Key::new(2.0, Color::new(1.0, 0.5, 0.0)).with_curve(Curve::EaseInOut),
+                                      Key::new(4.0, color::RED)]).unwrap();
+    let orbit = AnimatedTransform::rotating(Track::new(vec![
+        Key::new(0.0, Quaternion::identity()),
+        Key::new(4.0, Quaternion::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), PI / 8.0)),
+    ]).unwrap());
+
     // let s: &Sphere = &Sphere {
     //     centre: ORIGIN,
     //     radius: 0.5,
@@ -104,29 +534,59 @@ pub fn simple_trace() {
         center: Vector3::new(0.0, 0.0, 0.0),
         radius: 0.8,
         tube_radius: 0.25,
-        rotx: 0.0,
-        roty: 0.0,
-    };
-    let t2 = &Torus {
-        center: Vector3::new(0.8, 0.0, 0.0),
-        radius: 0.8,
-        tube_radius: 0.25,
-        rotx: 3.1415926 / 2.0,
-        roty: 0.0,
+        rotx: tilt.at(time),
+        roty: spin.at(time),
     };
-    let the_shapes: Vec<&Shape> = vec![t, t2];
-    let l: &Light = &Light {
-        position: Vector3::new(-1.5, 1.0, -3.0),
-        color: color::RED,
+    // A second instance of the same torus, linked through the first.
+    let t2 = &Transformed::new(t,
+                               Transform::translate(Vector3::new(0.8, 0.0, 0.0)) *
+                               Transform::rotate(Vector3::new(1.0, 0.0, 0.0),
+                                                 ::std::f64::consts::FRAC_PI_2));
+    let floor = &Surface::new(Plane {
+                                  normal: Vector3::new(0.0, -1.0, 0.0),
+                                  origin_distance: 1.2,
+                              },
+                              Material::diffuse(color::WHITE))
+        .with_texture(Channel::Diffuse,
+                      ::std::rc::Rc::new(Checker {
+                          even: color::WHITE,
+                          odd: Color::new(0.3, 0.3, 0.3),
+                          size: 0.5,
+                      }));
+    let the_shapes: Vec<&dyn Shape> = vec![t, t2, floor];
+    let l: &dyn Light = &PointLight {
+        position: light_position.at(time),
+        color: light_color.at(time),
+        intensity: 5.0 * ::std::f64::consts::PI,
+        falloff: Falloff::InverseSquare,
     };
     let world = World {
         shapes: the_shapes,
         lights: vec![l],
+        fog: None,
     };
-    let pixels = screen.map(|ray| {
-        return world.trace(ray);
+    let camera = Camera { shutter, ..screen::default_camera().transformed(&orbit.at(time)) };
+    let pixels = camera.screen(screen::RES_W, screen::RES_H).map(|ray| {
+        return world.expose(integrator, ray, &shutter);
     });
     // pnm::write_console(pixels, screen::RES_W);
 
-    pnm::write_pnm(pixels, screen::RES_W, screen::RES_H, &mut stdout());
+    pnm::write_pnm(pixels, screen::RES_W, screen::RES_H, out);
+}
+
+pub fn simple_trace() {
+    let integrator = integrator_from_args();
+    match sequence_from_args() {
+        Err(e) => {
+            eprintln!("{}", e);
+            ::std::process::exit(1);
+        }
+        Ok(Some(sequence)) => {
+            match sequence.render(|shutter, file| render_demo(&integrator, shutter, file)) {
+                Ok(paths) => eprintln!("Wrote {} frames", paths.len()),
+                Err(e) => eprintln!("Could not write frames: {}", e),
+            }
+        }
+        Ok(None) => render_demo(&integrator, screen::default_camera().shutter, &mut stdout()),
+    }
 }
@@ -0,0 +1,332 @@
+use vec3::{Vector3, Quaternion};
+use animation::{Animatable, Track, Key, Curve};
+use Ray;
+
+/// A 4x4 matrix acting on column vectors, stored row by row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+pub const IDENTITY: Matrix4 = Matrix4 {
+    m: [[1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0]],
+};
+
+impl Matrix4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Matrix4 {
+        Matrix4 { m }
+    }
+
+    pub fn translation(v: Vector3) -> Matrix4 {
+        Matrix4::new([[1.0, 0.0, 0.0, v.x],
+                      [0.0, 1.0, 0.0, v.y],
+                      [0.0, 0.0, 1.0, v.z],
+                      [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    pub fn scaling(v: Vector3) -> Matrix4 {
+        Matrix4::new([[v.x, 0.0, 0.0, 0.0],
+                      [0.0, v.y, 0.0, 0.0],
+                      [0.0, 0.0, v.z, 0.0],
+                      [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    /// Right-handed rotation by `angle` radians about `axis`, which need not be normalized.
+    pub fn rotation(axis: Vector3, angle: f64) -> Matrix4 {
+        let Vector3 { x, y, z } = axis.normalize();
+        let c = angle.cos();
+        let s = angle.sin();
+        let t = 1.0 - c;
+        Matrix4::new([[t * x * x + c, t * x * y - s * z, t * x * z + s * y, 0.0],
+                      [t * x * y + s * z, t * y * y + c, t * y * z - s * x, 0.0],
+                      [t * x * z - s * y, t * y * z + s * x, t * z * z + c, 0.0],
+                      [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        return Matrix4::new(m);
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting.
+    /// Returns `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.m;
+        let mut inv = IDENTITY.m;
+        for col in 0..4 {
+            let mut pivot = col;
+            for row in (col + 1)..4 {
+                if a[row][col].abs() > a[pivot][col].abs() {
+                    pivot = row;
+                }
+            }
+            if a[pivot][col].abs() < 1.0e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+        return Some(Matrix4::new(inv));
+    }
+
+    pub fn transform_point(&self, p: Vector3) -> Vector3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if w == 1.0 {
+            return Vector3::new(x, y, z);
+        } else {
+            return Vector3::new(x, y, z) / w;
+        }
+    }
+
+    /// Determinant of the upper left 3x3 block, the factor by which the
+    /// matrix scales volumes.
+    pub fn determinant3(&self) -> f64 {
+        let m = &self.m;
+        return m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) -
+               m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0]) +
+               m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    }
+
+    /// Transforms a direction, ignoring the translation part.
+    pub fn transform_vector(&self, v: Vector3) -> Vector3 {
+        let m = &self.m;
+        Vector3::new(m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+                     m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+                     m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z)
+    }
+}
+
+impl ::std::ops::Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        return Matrix4::new(m);
+    }
+}
+
+/// An affine transform from object space to world space, stored with its inverse so that
+/// rays can be taken into object space without inverting a matrix per ray.
+///
+/// Transforms compose like matrices: `a * b` applies `b` first, then `a`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub matrix: Matrix4,
+    pub inverse: Matrix4,
+    /// The inverse transpose, which normals transform by.
+    normal_matrix: Matrix4,
+}
+
+impl Transform {
+    fn new(matrix: Matrix4, inverse: Matrix4) -> Transform {
+        Transform {
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
+        }
+    }
+
+    pub fn identity() -> Transform {
+        Transform::new(IDENTITY, IDENTITY)
+    }
+
+    /// Returns `None` if `matrix` is not invertible.
+    pub fn from_matrix(matrix: Matrix4) -> Option<Transform> {
+        matrix.inverse().map(|inverse| Transform::new(matrix, inverse))
+    }
+
+    pub fn translate(v: Vector3) -> Transform {
+        Transform::new(Matrix4::translation(v), Matrix4::translation(-v))
+    }
+
+    pub fn rotate(axis: Vector3, angle: f64) -> Transform {
+        let matrix = Matrix4::rotation(axis, angle);
+        Transform::new(matrix, matrix.transpose())
+    }
+
+    pub fn rotate_quaternion(q: Quaternion) -> Transform {
+        let matrix = q.to_matrix();
+        Transform::new(matrix, matrix.transpose())
+    }
+
+    /// Non-uniform scale; every component of `v` must be non-zero.
+    pub fn scale(v: Vector3) -> Transform {
+        assert!(v.x != 0.0 && v.y != 0.0 && v.z != 0.0);
+        Transform::new(Matrix4::scaling(v),
+                       Matrix4::scaling(Vector3::new(1.0 / v.x, 1.0 / v.y, 1.0 / v.z)))
+    }
+
+    pub fn uniform_scale(s: f64) -> Transform {
+        Transform::scale(Vector3::new(s, s, s))
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform::new(self.inverse, self.matrix)
+    }
+
+    pub fn point(&self, p: Vector3) -> Vector3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vector3) -> Vector3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Normals transform by the inverse transpose so they stay perpendicular to the surface.
+    pub fn normal(&self, n: Vector3) -> Vector3 {
+        self.normal_matrix.transform_vector(n).normalize()
+    }
+
+    /// The factor by which the transform scales small patches of a surface
+    /// with unit normal `n`.
+    pub fn area_scale(&self, n: Vector3) -> f64 {
+        let scaled_normal = self.normal_matrix.transform_vector(n);
+        return self.matrix.determinant3().abs() * scaled_normal.len();
+    }
+
+    /// Takes a world space ray into object space.
+    /// The direction is renormalized; the returned factor converts a
+    /// distance along the object space ray into one along the world space ray.
+    pub fn ray_to_object(&self, ray: Ray) -> (Ray, f64) {
+        let local_dir = self.inverse.transform_vector(ray.direction);
+        let len = local_dir.len();
+        let local_ray = ray.in_frame(local_dir / len, self.inverse.transform_point(ray.origin))
+            .with_interval(ray.t_min * len, ray.t_max * len);
+        return (local_ray, 1.0 / len);
+    }
+}
+
+impl ::std::ops::Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, other: Transform) -> Transform {
+        Transform::new(self.matrix * other.matrix, other.inverse * self.inverse)
+    }
+}
+
+/// The pose of an animated transform at one moment: a scale, then a
+/// rotation, then a translation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: Vector3,
+    pub rotation: Quaternion,
+    pub scale: Vector3,
+    /// How the pose moves on to the next keyframe.
+    pub curve: Curve,
+}
+
+impl Keyframe {
+    /// No movement, at `time`.
+    pub fn new(time: f64) -> Keyframe {
+        Keyframe {
+            time,
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::identity(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            curve: Curve::Linear,
+        }
+    }
+
+    pub fn with_translation(self, translation: Vector3) -> Keyframe {
+        Keyframe { translation, ..self }
+    }
+
+    pub fn with_rotation(self, rotation: Quaternion) -> Keyframe {
+        Keyframe { rotation, ..self }
+    }
+
+    pub fn with_scale(self, scale: Vector3) -> Keyframe {
+        Keyframe { scale, ..self }
+    }
+
+    pub fn with_curve(self, curve: Curve) -> Keyframe {
+        Keyframe { curve, ..self }
+    }
+
+    pub fn transform(&self) -> Transform {
+        return Transform::translate(self.translation) * Transform::rotate_quaternion(self.rotation) *
+               Transform::scale(self.scale);
+    }
+}
+
+/// A transform that changes over time: a scale, then a rotation, then a
+/// translation, each animated separately. Rotations turn along the
+/// shortest arc.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimatedTransform {
+    pub translation: Track<Vector3>,
+    pub rotation: Track<Quaternion>,
+    pub scale: Track<Vector3>,
+}
+
+impl AnimatedTransform {
+    /// Moves through whole poses. Errors if there are none, or a time is not
+    /// finite.
+    pub fn new(keyframes: Vec<Keyframe>) -> Result<AnimatedTransform, &'static str> {
+        fn track<T: Animatable>(keyframes: &[Keyframe],
+                                value: fn(&Keyframe) -> T)
+                                -> Result<Track<T>, &'static str> {
+            return Track::new(keyframes.iter().map(|k| Key::new(k.time, value(k)).with_curve(k.curve)).collect());
+        }
+        return Ok(AnimatedTransform {
+            translation: track(&keyframes, |k| k.translation)?,
+            rotation: track(&keyframes, |k| k.rotation)?,
+            scale: track(&keyframes, |k| k.scale)?,
+        });
+    }
+
+    /// Rotation alone, about the origin.
+    pub fn rotating(rotation: Track<Quaternion>) -> AnimatedTransform {
+        AnimatedTransform {
+            translation: Track::constant(Vector3::new(0.0, 0.0, 0.0)),
+            rotation,
+            scale: Track::constant(Vector3::new(1.0, 1.0, 1.0)),
+        }
+    }
+
+    /// When the first of the tracks starts moving.
+    pub fn start(&self) -> f64 {
+        return self.translation.keys()[0]
+            .time
+            .min(self.rotation.keys()[0].time)
+            .min(self.scale.keys()[0].time);
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        return Transform::translate(self.translation.at(time)) *
+               Transform::rotate_quaternion(self.rotation.at(time)) * Transform::scale(self.scale.at(time));
+    }
+}
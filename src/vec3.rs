@@ -1,3 +1,4 @@
+use transform::Matrix4;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vector3 {
@@ -8,7 +9,7 @@ pub struct Vector3 {
 
 impl Vector3 {
     pub fn new(x: f64, y: f64, z: f64) -> Vector3 {
-        Vector3 { x: x, y: y, z: z }
+        Vector3 { x, y, z }
     }
 
     pub fn dot(self, v: Vector3) -> f64 {
@@ -124,3 +125,162 @@ impl ::std::ops::Div<f64> for Vector3 {
         self * (1.0 / num)
     }
 }
+
+/// A rotation stored as a unit quaternion `w + xi + yj + zk`.
+/// Unlike `Vector3::rotate` it can represent any orientation and
+/// interpolates smoothly without gimbal lock.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// Right-handed rotation by `angle` radians about `axis`, which need not be normalized.
+    pub fn from_axis_angle(axis: Vector3, angle: f64) -> Quaternion {
+        let a = axis.normalize();
+        let s = (angle / 2.0).sin();
+        return Quaternion::new((angle / 2.0).cos(), a.x * s, a.y * s, a.z * s);
+    }
+
+    /// Returns the rotation axis and angle in radians.
+    /// The axis is arbitrary for the identity rotation.
+    pub fn to_axis_angle(self) -> (Vector3, f64) {
+        let q = self.normalize();
+        let angle = 2.0 * q.w.clamp(-1.0, 1.0).acos();
+        let axis = Vector3::new(q.x, q.y, q.z).normalize();
+        if axis.len() == 0.0 {
+            return (Vector3::new(1.0, 0.0, 0.0), 0.0);
+        }
+        return (axis, angle);
+    }
+
+    pub fn dot(self, q: Quaternion) -> f64 {
+        self.w * q.w + self.x * q.x + self.y * q.y + self.z * q.z
+    }
+
+    pub fn len(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Quaternion {
+        let len = self.len();
+        if len == 0.0 {
+            return Quaternion::identity();
+        }
+        return self * (1.0 / len);
+    }
+
+    /// The inverse rotation, for unit quaternions.
+    pub fn conjugate(self) -> Quaternion {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn rotate(self, v: Vector3) -> Vector3 {
+        // v' = v + 2w(u x v) + 2u x (u x v), with u the vector part.
+        let u = Vector3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        return v + t * self.w + u.cross(t);
+    }
+
+    /// Spherical linear interpolation along the shortest arc, `t` in [0, 1].
+    pub fn slerp(self, other: Quaternion, t: f64) -> Quaternion {
+        let mut cosine = self.dot(other);
+        let mut end = other;
+        // q and -q are the same rotation; pick the one that is closer.
+        if cosine < 0.0 {
+            cosine = -cosine;
+            end = end * -1.0;
+        }
+        if cosine > 0.9995 {
+            // Nearly parallel, so sin(angle) is too small to divide by.
+            return (self * (1.0 - t) + end * t).normalize();
+        }
+        let angle = cosine.acos();
+        let sine = angle.sin();
+        let a = ((1.0 - t) * angle).sin() / sine;
+        let b = (t * angle).sin() / sine;
+        return self * a + end * b;
+    }
+
+    pub fn to_matrix(self) -> Matrix4 {
+        let Quaternion { w, x, y, z } = self.normalize();
+        Matrix4::new([[1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+                      [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+                      [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+                      [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    /// Extracts the rotation from the upper 3x3 of `m`, which must be orthonormal.
+    pub fn from_matrix(m: &Matrix4) -> Quaternion {
+        let m = &m.m;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        // Divide by the largest of the four candidate components for stability.
+        let q = if trace > 0.0 {
+            let s = 2.0 * (trace + 1.0).sqrt();
+            Quaternion::new(s / 4.0,
+                            (m[2][1] - m[1][2]) / s,
+                            (m[0][2] - m[2][0]) / s,
+                            (m[1][0] - m[0][1]) / s)
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2.0 * (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt();
+            Quaternion::new((m[2][1] - m[1][2]) / s,
+                            s / 4.0,
+                            (m[0][1] + m[1][0]) / s,
+                            (m[0][2] + m[2][0]) / s)
+        } else if m[1][1] > m[2][2] {
+            let s = 2.0 * (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt();
+            Quaternion::new((m[0][2] - m[2][0]) / s,
+                            (m[0][1] + m[1][0]) / s,
+                            s / 4.0,
+                            (m[1][2] + m[2][1]) / s)
+        } else {
+            let s = 2.0 * (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt();
+            Quaternion::new((m[1][0] - m[0][1]) / s,
+                            (m[0][2] + m[2][0]) / s,
+                            (m[1][2] + m[2][1]) / s,
+                            s / 4.0)
+        };
+        return q.normalize();
+    }
+}
+
+// Hamilton product: `a * b` rotates by `b`, then by `a`.
+impl ::std::ops::Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, q: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * q.w - self.x * q.x - self.y * q.y - self.z * q.z,
+            x: self.w * q.x + self.x * q.w + self.y * q.z - self.z * q.y,
+            y: self.w * q.y - self.x * q.z + self.y * q.w + self.z * q.x,
+            z: self.w * q.z + self.x * q.y - self.y * q.x + self.z * q.w,
+        }
+    }
+}
+
+impl ::std::ops::Mul<f64> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, num: f64) -> Quaternion {
+        Quaternion::new(self.w * num, self.x * num, self.y * num, self.z * num)
+    }
+}
+
+impl ::std::ops::Add for Quaternion {
+    type Output = Quaternion;
+
+    fn add(self, q: Quaternion) -> Quaternion {
+        Quaternion::new(self.w + q.w, self.x + q.x, self.y + q.y, self.z + q.z)
+    }
+}